@@ -18,9 +18,9 @@ fn main() {
         .insert_resource(ClearColor(Color::rgb(0.752, 0.992, 0.984)))
         .insert_resource(RapierConfiguration::default())
         .add_plugins(DefaultPlugins)
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
         // .add_plugin(RapierDebugRenderPlugin::default())
         .add_plugin(FpsControllerPlugin)
+        .add_plugin(FpsControllerPhysicsPlugin::<NoUserData>::default())
         .add_startup_system(setup)
         .add_system(manage_cursor)
         .run();
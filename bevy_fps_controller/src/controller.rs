@@ -1,23 +1,130 @@
 use std::f32::consts::*;
+use std::marker::PhantomData;
 
+use bevy::ecs::query::WorldQuery;
 use bevy::input::mouse::MouseMotion;
+use bevy::time::{FixedTimestep, FixedTimesteps};
+use bevy::transform::TransformSystem;
 use bevy::{math::Vec3Swizzles, prelude::*};
 use bevy_rapier3d::prelude::*;
 
+/// Seconds simulated by each controller tick, independent of the frame rate.
+pub const TIMESTEP: f64 = 1.0 / 60.0;
+
+/// Label of the controller's [`FixedTimestep`], for reading the overstep from [`FixedTimesteps`].
+pub const FPS_CONTROLLER_TIMESTEP: &str = "fps_controller_timestep";
+
 pub struct FpsControllerPlugin;
 
 impl Plugin for FpsControllerPlugin {
     fn build(&self, app: &mut App) {
-        // TODO: these need to be sequential (exclusive system set)
         app.register_type::<FpsController>()
             .register_type::<FpsControllerInput>()
-            .add_system(fps_controller_input)
-            .add_system(fps_controller_look)
-            .add_system(fps_controller_move)
-            .add_system(fps_controller_render);
+            .add_system(fps_controller_input.label(FpsControllerSystems::Input))
+            .add_stage_after(
+                CoreStage::Update,
+                FpsControllerStage::Fixed,
+                Schedule::default()
+                    .with_run_criteria(
+                        FixedTimestep::step(TIMESTEP).with_label(FPS_CONTROLLER_TIMESTEP),
+                    )
+                    .with_stage(
+                        FpsControllerStage::Move,
+                        SystemStage::single_threaded()
+                            .with_system(fps_controller_look.label(FpsControllerSystems::Look))
+                            .with_system(
+                                fps_controller_move
+                                    .label(FpsControllerSystems::Move)
+                                    .after(FpsControllerSystems::Look),
+                            ),
+                    ),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                fps_controller_render
+                    .label(FpsControllerSystems::Render)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Runs rapier inside the controller's fixed timestep instead of once per frame.
+///
+/// Use in place of [`RapierPhysicsPlugin`], after [`FpsControllerPlugin`]. Together the same input
+/// gives the same movement at any frame rate.
+pub struct FpsControllerPhysicsPlugin<PhysicsHooksData = ()>(PhantomData<PhysicsHooksData>);
+
+impl<PhysicsHooksData> Default for FpsControllerPhysicsPlugin<PhysicsHooksData> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<PhysicsHooksData: 'static + WorldQuery + Send + Sync> Plugin
+    for FpsControllerPhysicsPlugin<PhysicsHooksData>
+{
+    fn build(&self, app: &mut App) {
+        app.add_plugin(
+            RapierPhysicsPlugin::<PhysicsHooksData>::default().with_default_system_setup(false),
+        );
+        app.world
+            .resource_mut::<RapierConfiguration>()
+            .timestep_mode = TimestepMode::Fixed {
+            dt: TIMESTEP as f32,
+            substeps: 1,
+        };
+
+        let physics_stage = |stage| {
+            SystemStage::parallel()
+                .with_system_set(RapierPhysicsPlugin::<PhysicsHooksData>::get_systems(stage))
+        };
+        app.stage(FpsControllerStage::Fixed, |schedule: &mut Schedule| {
+            schedule
+                .add_stage_after(
+                    FpsControllerStage::Move,
+                    PhysicsStages::SyncBackend,
+                    physics_stage(PhysicsStages::SyncBackend),
+                )
+                .add_stage_after(
+                    PhysicsStages::SyncBackend,
+                    PhysicsStages::StepSimulation,
+                    physics_stage(PhysicsStages::StepSimulation),
+                )
+                .add_stage_after(
+                    PhysicsStages::StepSimulation,
+                    PhysicsStages::Writeback,
+                    physics_stage(PhysicsStages::Writeback),
+                )
+        })
+        .add_system_set_to_stage(
+            CoreStage::Last,
+            RapierPhysicsPlugin::<PhysicsHooksData>::get_systems(PhysicsStages::DetectDespawn),
+        );
     }
 }
 
+/// Stages added by [`FpsControllerPlugin`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, StageLabel)]
+pub enum FpsControllerStage {
+    /// Fixed timestep schedule after [`CoreStage::Update`], runs zero or more times a frame
+    Fixed,
+    /// Look and move, the first stage inside `Fixed`
+    Move,
+}
+
+/// Labels for the controller systems, which run in this order.
+///
+/// `Input` runs every frame in [`CoreStage::Update`], systems that write [`FpsControllerInput`]
+/// themselves should run after it. `Look` and `Move` run each tick in [`FpsControllerStage::Move`],
+/// `Render` interpolates between ticks every frame in [`CoreStage::PostUpdate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, SystemLabel)]
+pub enum FpsControllerSystems {
+    Input,
+    Look,
+    Move,
+    Render,
+}
+
 #[derive(Clone, Copy, Debug, Reflect)]
 pub enum MoveMode {
     Noclip,
@@ -58,16 +165,48 @@ pub struct FpsController {
     pub friction: f32,
     pub friction_cutoff: f32,
     pub jump_speed: f32,
+    /// Tallest ledge the player walks up without jumping, also how far down the player snaps when walking off one
+    pub max_step_height: f32,
+    /// Steepest walkable ground in radians, the player slides down anything steeper
+    pub max_slope_angle: f32,
     pub fly_speed: f32,
     pub fast_fly_speed: f32,
     pub fly_friction: f32,
+    /// Max ground speed while crouched, sprinting has no effect
+    pub crouch_speed: f32,
+    /// Fraction of the standing capsule segment kept while crouched, the bottom of the capsule stays in place
+    pub crouch_height: f32,
+    /// How quickly the camera follows the capsule height, higher is faster
+    pub camera_height_smoothing: f32,
+    pub crouched: bool,
+    /// Capsule segment when standing, taken from the collider the first time the controller moves
+    #[reflect(ignore)]
+    pub standing_segment: Option<(Vec3, Vec3)>,
+    /// Smoothed camera height above the logical player
+    #[reflect(ignore)]
+    pub camera_height: Option<f32>,
+    /// Collider the player is standing on, the player is carried along when it moves
+    #[reflect(ignore)]
+    pub ground_entity: Option<Entity>,
+    /// Transform of [`ground_entity`] last update
+    #[reflect(ignore)]
+    pub ground_matrix: Option<Mat4>,
+    /// Velocity inherited from [`ground_entity`], kept when leaving it such as jumping off a moving platform
+    pub platform_velocity: Vec3,
+    /// Position at the start of the last tick, rendering interpolates from here to the current position
+    #[reflect(ignore)]
+    pub previous_translation: Option<Vec3>,
     pub pitch: f32,
     pub yaw: f32,
     pub velocity: Vec3,
     pub ground_tick: u8,
     pub stop_speed: f32,
     pub sensitivity: f32,
+    pub invert_y: bool,
     pub enable_input: bool,
+    /// Whether [`fps_controller_input`] reads the keyboard, mouse and gamepads, turn it off when
+    /// another system writes [`FpsControllerInput`]. See [`apply_look`] for looking around.
+    pub device_input: bool,
     #[reflect(ignore)]
     pub key_forward: KeyCode,
     #[reflect(ignore)]
@@ -88,6 +227,20 @@ pub struct FpsController {
     pub key_fly: Option<KeyCode>,
     #[reflect(ignore)]
     pub key_crouch: Option<KeyCode>,
+    /// Look speed in radians per second with the right stick fully deflected
+    pub gamepad_sensitivity: f32,
+    /// Stick deflection below this is ignored, the rest of the range is rescaled to 0..1
+    pub gamepad_deadzone: f32,
+    /// Exponent applied to look stick deflection, higher values give finer aim near the center
+    pub gamepad_look_curve: f32,
+    #[reflect(ignore)]
+    pub gamepad_sprint: Option<GamepadButtonType>,
+    #[reflect(ignore)]
+    pub gamepad_jump: Option<GamepadButtonType>,
+    #[reflect(ignore)]
+    pub gamepad_fly: Option<GamepadButtonType>,
+    #[reflect(ignore)]
+    pub gamepad_crouch: Option<GamepadButtonType>,
 }
 
 impl Default for FpsController {
@@ -108,13 +261,26 @@ impl Default for FpsController {
             friction: 10.0,
             friction_cutoff: 0.1,
             fly_friction: 0.5,
+            crouch_speed: 5.0,
+            crouch_height: 0.5,
+            camera_height_smoothing: 12.0,
+            crouched: false,
+            standing_segment: None,
+            camera_height: None,
+            ground_entity: None,
+            ground_matrix: None,
+            platform_velocity: Vec3::ZERO,
+            previous_translation: None,
             pitch: 0.0,
             yaw: 0.0,
             velocity: Vec3::ZERO,
             ground_tick: 0,
             stop_speed: 1.0,
             jump_speed: 8.5,
+            max_step_height: 0.3,
+            max_slope_angle: FRAC_PI_4,
             enable_input: true,
+            device_input: true,
             key_forward: KeyCode::W,
             key_back: KeyCode::S,
             key_left: KeyCode::A,
@@ -126,6 +292,14 @@ impl Default for FpsController {
             key_fly: Some(KeyCode::F),
             key_crouch: Some(KeyCode::LControl),
             sensitivity: 0.001,
+            invert_y: false,
+            gamepad_sensitivity: 3.0,
+            gamepad_deadzone: 0.15,
+            gamepad_look_curve: 2.0,
+            gamepad_sprint: Some(GamepadButtonType::LeftThumb),
+            gamepad_jump: Some(GamepadButtonType::South),
+            gamepad_fly: None,
+            gamepad_crouch: Some(GamepadButtonType::East),
         }
     }
 }
@@ -139,51 +313,154 @@ impl Default for FpsController {
 
 const ANGLE_EPSILON: f32 = 0.001953125;
 
+/// Turn `input` by `mouse_delta` in pixels and `look_stick` held for `delta_seconds`, using the
+/// controller's sensitivity settings.
+pub fn apply_look(
+    controller: &FpsController,
+    input: &mut FpsControllerInput,
+    mouse_delta: Vec2,
+    look_stick: Vec2,
+    delta_seconds: f32,
+) {
+    let mut delta = mouse_delta * controller.sensitivity;
+
+    // Curve the magnitude only, so diagonals keep their direction
+    let look_length = look_stick.length();
+    if look_length > 0.0 {
+        let curved = look_length.powf(controller.gamepad_look_curve);
+        // Stick up is positive, mouse up is negative
+        delta += look_stick / look_length
+            * curved
+            * controller.gamepad_sensitivity
+            * delta_seconds
+            * Vec2::new(1.0, -1.0);
+    }
+
+    if controller.invert_y {
+        delta.y = -delta.y;
+    }
+
+    input.pitch =
+        (input.pitch - delta.y).clamp(-FRAC_PI_2 + ANGLE_EPSILON, FRAC_PI_2 - ANGLE_EPSILON);
+    input.yaw -= delta.x;
+}
+
 pub fn fps_controller_input(
+    time: Res<Time>,
     key_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
     mut windows: ResMut<Windows>,
     mut mouse_events: EventReader<MouseMotion>,
     mut query: Query<(&FpsController, &mut FpsControllerInput)>,
 ) {
+    let mouse_delta: Vec2 = mouse_events.iter().map(|event| event.delta).sum();
     for (controller, mut input) in query.iter_mut() {
-        if !controller.enable_input {
+        if !controller.enable_input || !controller.device_input {
             continue;
         }
         let window = windows.get_primary_mut().unwrap();
         if window.is_focused() {
-            let mut mouse_delta = Vec2::ZERO;
-            for mouse_event in mouse_events.iter() {
-                mouse_delta += mouse_event.delta;
-            }
-            mouse_delta *= controller.sensitivity;
-
-            input.pitch = (input.pitch - mouse_delta.y)
-                .clamp(-FRAC_PI_2 + ANGLE_EPSILON, FRAC_PI_2 - ANGLE_EPSILON);
-            input.yaw = input.yaw - mouse_delta.x;
+            let look_stick = gamepad_stick(
+                &gamepads,
+                &gamepad_axes,
+                GamepadAxisType::RightStickX,
+                GamepadAxisType::RightStickY,
+                controller.gamepad_deadzone,
+            );
+            apply_look(
+                controller,
+                &mut input,
+                mouse_delta,
+                look_stick,
+                time.delta_seconds(),
+            );
         }
 
-        input.movement = Vec3::new(
+        let move_stick = gamepad_stick(
+            &gamepads,
+            &gamepad_axes,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+            controller.gamepad_deadzone,
+        );
+        let mut movement = Vec3::new(
             get_axis(&key_input, controller.key_right, controller.key_left),
             get_axis(&key_input, controller.key_up, controller.key_down),
             get_axis(&key_input, controller.key_forward, controller.key_back),
-        );
+        ) + Vec3::new(move_stick.x, 0.0, move_stick.y);
+        movement.x = movement.x.clamp(-1.0, 1.0);
+        movement.z = movement.z.clamp(-1.0, 1.0);
+        input.movement = movement;
+
+        let gamepad_pressed = |button: Option<GamepadButtonType>| {
+            button
+                .map(|button| {
+                    gamepads
+                        .iter()
+                        .any(|gamepad| gamepad_buttons.pressed(GamepadButton(*gamepad, button)))
+                })
+                .unwrap_or(false)
+        };
+        let gamepad_just_pressed = |button: Option<GamepadButtonType>| {
+            button
+                .map(|button| {
+                    gamepads.iter().any(|gamepad| {
+                        gamepad_buttons.just_pressed(GamepadButton(*gamepad, button))
+                    })
+                })
+                .unwrap_or(false)
+        };
+
         input.sprint = controller
             .key_sprint
             .map(|key| key_input.pressed(key))
-            .unwrap_or(false);
+            .unwrap_or(false)
+            || gamepad_pressed(controller.gamepad_sprint);
         input.jump = controller
             .key_jump
             .map(|key| key_input.pressed(key))
-            .unwrap_or(false);
-        input.fly = controller
+            .unwrap_or(false)
+            || gamepad_pressed(controller.gamepad_jump);
+        // Held until the next tick consumes it, there may not be one this frame
+        input.fly |= controller
             .key_fly
             .map(|key| key_input.just_pressed(key))
-            .unwrap_or(false);
+            .unwrap_or(false)
+            || gamepad_just_pressed(controller.gamepad_fly);
         input.crouch = controller
             .key_crouch
             .map(|key| key_input.pressed(key))
-            .unwrap_or(false);
+            .unwrap_or(false)
+            || gamepad_pressed(controller.gamepad_crouch);
+    }
+}
+
+/// Reads a stick from all connected gamepads, with a radial deadzone applied.
+///
+/// The result is rescaled so it starts at zero at the edge of the deadzone and has a length of at most 1.
+pub fn gamepad_stick(
+    gamepads: &Gamepads,
+    axes: &Axis<GamepadAxis>,
+    x: GamepadAxisType,
+    y: GamepadAxisType,
+    deadzone: f32,
+) -> Vec2 {
+    let mut stick = Vec2::ZERO;
+    for gamepad in gamepads.iter() {
+        stick += Vec2::new(
+            axes.get(GamepadAxis(*gamepad, x)).unwrap_or(0.0),
+            axes.get(GamepadAxis(*gamepad, y)).unwrap_or(0.0),
+        );
     }
+
+    let length = stick.length();
+    if length <= deadzone {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - deadzone) / (1.0 - deadzone).max(1e-6)).min(1.0);
+    stick / length * scaled
 }
 
 pub fn fps_controller_look(mut query: Query<(&mut FpsController, &FpsControllerInput)>) {
@@ -194,31 +471,37 @@ pub fn fps_controller_look(mut query: Query<(&mut FpsController, &FpsControllerI
 }
 
 pub fn fps_controller_move(
-    time: Res<Time>,
     physics_context: Res<RapierContext>,
+    ground_transforms: Query<&GlobalTransform>,
     mut query: Query<(
         Entity,
-        &FpsControllerInput,
+        &mut FpsControllerInput,
         &mut FpsController,
-        &Collider,
+        &mut Collider,
         &mut Transform,
         &mut Velocity,
     )>,
 ) {
-    let dt = time.delta_seconds();
+    let dt = TIMESTEP as f32;
+
+    for (entity, mut input, mut controller, mut collider, mut transform, mut velocity) in
+        query.iter_mut()
+    {
+        controller.previous_translation = Some(transform.translation);
 
-    for (entity, input, mut controller, collider, transform, mut velocity) in query.iter_mut() {
         if input.fly {
             controller.move_mode = match controller.move_mode {
                 MoveMode::Noclip => MoveMode::Ground,
                 MoveMode::Ground => MoveMode::Noclip,
-            }
+            };
+            // Consumed, a frame with several ticks shouldn't toggle back
+            input.fly = false;
         }
 
         let orientation = look_quat(input.pitch, input.yaw);
         let right = orientation * Vec3::X;
         let forward = orientation * -Vec3::Z;
-        let position = transform.translation;
+        let mut position = transform.translation;
 
         match controller.move_mode {
             MoveMode::Noclip => {
@@ -234,7 +517,9 @@ pub fn fps_controller_move(
                     } else {
                         controller.fly_speed
                     };
-                    controller.velocity = input.movement.normalize() * fly_speed;
+                    // Partial stick deflection flies slower
+                    let analog_scale = input.movement.length().min(1.0);
+                    controller.velocity = input.movement.normalize() * fly_speed * analog_scale;
                 }
                 velocity.linvel = controller.velocity.x * right
                     + controller.velocity.y * Vec3::Y
@@ -242,6 +527,15 @@ pub fn fps_controller_move(
             }
 
             MoveMode::Ground => {
+                crouch(
+                    entity,
+                    input.crouch,
+                    &physics_context,
+                    &transform,
+                    &mut controller,
+                    &mut collider,
+                );
+
                 if let Some(capsule) = collider.as_capsule() {
                     let capsule = capsule.raw;
                     let mut start_velocity = controller.velocity;
@@ -262,16 +556,76 @@ pub fn fps_controller_move(
                         .exclude_rigid_body(entity)
                         .exclude_sensors();
 
-                    let ground_hit = physics_context
-                        .cast_shape(
+                    let ground_hit = physics_context.cast_shape(
+                        position,
+                        transform.rotation,
+                        cast_velocity,
+                        &cast_capsule,
+                        max_distance,
+                        groups,
+                    );
+                    let mut ground_entity = ground_hit.map(|(ground_entity, _)| ground_entity);
+
+                    // Offset between the shrunk cast capsule and the real one
+                    let skin = capsule.radius - capsule.radius * 0.9375;
+                    let feet_offset = transform.rotation * Vec3::from(capsule.segment.a);
+                    let normal_distance =
+                        capsule.radius + max_distance + controller.max_step_height;
+                    let max_slope_angle = controller.max_slope_angle;
+                    let walkable = |normal: Vec3| normal.angle_between(Vec3::Y) <= max_slope_angle;
+
+                    let mut floor_normal = ground_hit.map(|_| {
+                        ground_normal(
+                            &physics_context,
+                            position + feet_offset,
+                            normal_distance,
+                            groups,
+                        )
+                    });
+
+                    // Stay on the ground when walking down stairs or over the top of a ramp
+                    if ground_hit.is_none()
+                        && controller.ground_tick >= 1
+                        && !input.jump
+                        && controller.velocity.y <= 0.0
+                    {
+                        let snap_hit = physics_context.cast_shape(
                             position,
-                            orientation,
+                            transform.rotation,
                             cast_velocity,
                             &cast_capsule,
-                            max_distance,
+                            controller.max_step_height,
                             groups,
-                        )
-                        .map(|(_handle, hit)| hit);
+                        );
+                        if let Some((snap_entity, hit)) = snap_hit {
+                            let normal = ground_normal(
+                                &physics_context,
+                                position + feet_offset,
+                                normal_distance,
+                                groups,
+                            );
+                            if walkable(normal) {
+                                let snap = (hit.toi - skin).max(0.0);
+                                position.y -= snap;
+                                transform.translation.y -= snap;
+                                floor_normal = Some(normal);
+                                ground_entity = Some(snap_entity);
+                            }
+                        }
+                    }
+
+                    let grounded = floor_normal.map(walkable).unwrap_or(false);
+                    let ground_entity = ground_entity.filter(|_| grounded);
+
+                    let platform_velocity = platform_motion(
+                        &mut controller,
+                        &mut input,
+                        ground_entity.and_then(|ground| {
+                            ground_transforms.get(ground).ok().map(|t| (ground, t))
+                        }),
+                        position,
+                        dt,
+                    );
 
                     let mut wish_direction = input.movement.z * controller.forward_speed * forward
                         + input.movement.x * controller.side_speed * right;
@@ -281,15 +635,19 @@ pub fn fps_controller_move(
                         wish_direction /= wish_speed; // Effectively normalize, avoid length computation twice
                     }
 
-                    let max_speed = if input.sprint {
+                    let max_speed = if controller.crouched {
+                        controller.crouch_speed
+                    } else if input.sprint {
                         controller.run_speed
                     } else {
                         controller.walk_speed
                     };
+                    // Partial stick deflection walks slower, keys always give full speed
+                    let max_speed = max_speed * input.movement.xz().length().min(1.0);
 
                     wish_speed = f32::min(wish_speed, max_speed);
 
-                    if let Some(_ground_hit) = ground_hit {
+                    if grounded {
                         // Only apply friction after at least one tick, allows b-hopping without losing speed
                         if controller.ground_tick >= 1 {
                             if lateral_speed > controller.friction_cutoff {
@@ -337,25 +695,221 @@ pub fn fps_controller_move(
                             end_velocity.x *= ratio;
                             end_velocity.z *= ratio;
                         }
+
+                        if let Some(normal) = floor_normal {
+                            // Too steep, don't let the player push uphill and slide down along the slope
+                            let uphill = -Vec3::new(normal.x, 0.0, normal.z).normalize_or_zero();
+                            let into_slope = end_velocity.dot(uphill);
+                            if into_slope > 0.0 {
+                                end_velocity -= uphill * into_slope;
+                            }
+                            let into_ground = end_velocity.dot(normal);
+                            if into_ground < 0.0 {
+                                end_velocity -= normal * into_ground;
+                            }
+                        }
+                    }
+
+                    if grounded && !input.jump {
+                        if let Some(step_height) = step_up(
+                            &physics_context,
+                            position,
+                            transform.rotation,
+                            &cast_capsule,
+                            end_velocity * dt,
+                            capsule.radius * 0.25,
+                            controller.max_step_height,
+                            groups,
+                        ) {
+                            let normal = ground_normal(
+                                &physics_context,
+                                position + feet_offset + Vec3::Y * step_height,
+                                normal_distance,
+                                groups,
+                            );
+                            if walkable(normal) {
+                                position.y += step_height;
+                                transform.translation.y += step_height;
+                            }
+                        }
                     }
 
-                    // At this point our collider may be intersecting with the ground
-                    // Fix up our collider by offsetting it to be flush with the ground
-                    // if end_vel.y < -1e6 {
-                    //     if let Some(ground_hit) = ground_hit {
-                    //         let normal = Vec3::from(*ground_hit.normal2);
-                    //         next_translation += normal * ground_hit.toi;
-                    //     }
-                    // }
+                    if !grounded && controller.platform_velocity != Vec3::ZERO {
+                        // Keep the platform's momentum after leaving it
+                        end_velocity += controller.platform_velocity;
+                        start_velocity += controller.platform_velocity;
+                    }
+                    controller.platform_velocity = platform_velocity;
 
                     controller.velocity = end_velocity;
                     velocity.linvel = (start_velocity + end_velocity) * 0.5;
+
+                    if grounded && !input.jump {
+                        if let Some(normal) = floor_normal {
+                            // Follow the slope instead of bumping into it going up or launching off it going down
+                            velocity.linvel.y = -(normal.x * velocity.linvel.x
+                                + normal.z * velocity.linvel.z)
+                                / normal.y;
+                        }
+                    }
+                    velocity.linvel += platform_velocity;
                 }
             }
         }
     }
 }
 
+/// Velocity of the point under the player on a moving ground collider, also turns the player with it.
+///
+/// Works for anything that moves its transform, including kinematic bodies and plain colliders.
+fn platform_motion(
+    controller: &mut FpsController,
+    input: &mut FpsControllerInput,
+    ground: Option<(Entity, &GlobalTransform)>,
+    position: Vec3,
+    dt: f32,
+) -> Vec3 {
+    let (ground_entity, ground_transform) = match ground {
+        Some(ground) => ground,
+        None => {
+            controller.ground_entity = None;
+            controller.ground_matrix = None;
+            return Vec3::ZERO;
+        }
+    };
+
+    let matrix = ground_transform.compute_matrix();
+    let last_matrix = controller
+        .ground_matrix
+        .filter(|_| controller.ground_entity == Some(ground_entity));
+    controller.ground_entity = Some(ground_entity);
+    controller.ground_matrix = Some(matrix);
+
+    match last_matrix {
+        Some(last_matrix) if dt > 0.0 => {
+            let delta = matrix * last_matrix.inverse();
+            let (_, rotation, _) = delta.to_scale_rotation_translation();
+            input.yaw += rotation.to_euler(EulerRot::YXZ).0;
+            (delta.transform_point3(position) - position) / dt
+        }
+        _ => Vec3::ZERO,
+    }
+}
+
+/// Normal of the ground below `feet`, assumes flat ground if the ray misses such as on an edge.
+fn ground_normal(
+    physics_context: &RapierContext,
+    feet: Vec3,
+    max_distance: f32,
+    filter: QueryFilter,
+) -> Vec3 {
+    physics_context
+        .cast_ray_and_get_normal(feet, -Vec3::Y, max_distance, true, filter)
+        .map(|(_, hit)| hit.normal)
+        .unwrap_or(Vec3::Y)
+}
+
+/// Height to raise the player by to walk onto a ledge in front of them.
+///
+/// Returns `None` if nothing is in the way, or the ledge is taller than `max_step_height`.
+#[allow(clippy::too_many_arguments)]
+fn step_up(
+    physics_context: &RapierContext,
+    position: Vec3,
+    rotation: Quat,
+    cast_capsule: &Collider,
+    movement: Vec3,
+    look_ahead: f32,
+    max_step_height: f32,
+    filter: QueryFilter,
+) -> Option<f32> {
+    let lateral = Vec3::new(movement.x, 0.0, movement.z);
+    let lateral_distance = lateral.length();
+    if max_step_height <= 0.0 || lateral_distance < 1e-6 {
+        return None;
+    }
+    let direction = lateral / lateral_distance;
+    let distance = lateral_distance + look_ahead;
+
+    // Something is in the way at the current height
+    physics_context.cast_shape(
+        position,
+        rotation,
+        direction,
+        cast_capsule,
+        distance,
+        filter,
+    )?;
+
+    // But not when raised by the max step height
+    let raised = position + Vec3::Y * max_step_height;
+    if physics_context
+        .cast_shape(raised, rotation, direction, cast_capsule, distance, filter)
+        .is_some()
+    {
+        return None;
+    }
+
+    // Find the top of the ledge
+    let (_, hit) = physics_context.cast_shape(
+        raised + direction * distance,
+        rotation,
+        -Vec3::Y,
+        cast_capsule,
+        max_step_height,
+        filter,
+    )?;
+    let step_height = max_step_height - hit.toi;
+    (step_height > 1e-3).then_some(step_height)
+}
+
+/// Shrinks or restores the capsule, only standing up when there is headroom.
+fn crouch(
+    entity: Entity,
+    wants_crouch: bool,
+    physics_context: &RapierContext,
+    transform: &Transform,
+    controller: &mut FpsController,
+    collider: &mut Collider,
+) {
+    let radius = match collider.as_capsule() {
+        Some(capsule) => {
+            if controller.standing_segment.is_none() {
+                controller.standing_segment = Some((capsule.segment().a(), capsule.segment().b()));
+            }
+            capsule.radius()
+        }
+        None => return,
+    };
+    let (bottom, top) = match controller.standing_segment {
+        Some(segment) => segment,
+        None => return,
+    };
+    let crouched_top = bottom.lerp(top, controller.crouch_height.clamp(0.0, 1.0));
+
+    if wants_crouch && !controller.crouched {
+        controller.crouched = true;
+        *collider = Collider::capsule(bottom, crouched_top, radius);
+    } else if !wants_crouch && controller.crouched {
+        // Cast the crouched capsule up to where the top of the standing capsule would be
+        let cast_capsule = Collider::capsule(bottom, crouched_top, radius * 0.9375);
+        let headroom_hit = physics_context.cast_shape(
+            transform.translation,
+            transform.rotation,
+            Vec3::Y,
+            &cast_capsule,
+            top.distance(crouched_top),
+            QueryFilter::default()
+                .exclude_rigid_body(entity)
+                .exclude_sensors(),
+        );
+        if headroom_hit.is_none() {
+            controller.crouched = false;
+            *collider = Collider::capsule(bottom, top, radius);
+        }
+    }
+}
+
 fn look_quat(pitch: f32, yaw: f32) -> Quat {
     Quat::from_euler(EulerRot::ZYX, 0.0, yaw, pitch)
 }
@@ -401,24 +955,56 @@ fn get_axis(key_input: &Res<Input<KeyCode>>, key_pos: KeyCode, key_neg: KeyCode)
 // ╚═╝  ╚═╝╚══════╝╚═╝  ╚═══╝╚═════╝ ╚══════╝╚═╝  ╚═╝
 
 pub fn fps_controller_render(
-    logical_query: Query<
-        (&Transform, &Collider, &FpsController, &LogicalPlayer),
+    time: Res<Time>,
+    fixed_timesteps: Res<FixedTimesteps>,
+    mut logical_query: Query<
+        (
+            &Transform,
+            &Collider,
+            &mut FpsController,
+            &FpsControllerInput,
+            &LogicalPlayer,
+        ),
         With<LogicalPlayer>,
     >,
     mut render_query: Query<(&mut Transform, &RenderPlayer), Without<LogicalPlayer>>,
 ) {
+    // How far into the next tick this frame is, rendering lags up to a tick behind to stay smooth
+    let alpha = fixed_timesteps
+        .get(FPS_CONTROLLER_TIMESTEP)
+        .map_or(1.0, |state| {
+            state.overstep_percentage().clamp(0.0, 1.0) as f32
+        });
+
     // TODO: inefficient O(N^2) loop, use hash map?
-    for (logical_transform, collider, controller, logical_player_id) in logical_query.iter() {
+    for (logical_transform, collider, mut controller, input, logical_player_id) in
+        logical_query.iter_mut()
+    {
         if let Some(capsule) = collider.as_capsule() {
+            // TODO: let this be more configurable
+            let target_height = capsule.segment().b().y + capsule.radius() * 0.75;
+            // Ease towards the new height when crouching, instead of snapping
+            let camera_height = match controller.camera_height {
+                Some(height) => {
+                    let t =
+                        1.0 - (-controller.camera_height_smoothing * time.delta_seconds()).exp();
+                    height + (target_height - height) * t
+                }
+                None => target_height,
+            };
+            controller.camera_height = Some(camera_height);
+
             for (mut render_transform, render_player_id) in render_query.iter_mut() {
                 if logical_player_id.0 != render_player_id.0 {
                     continue;
                 }
-                // TODO: let this be more configurable
-                let camera_height = capsule.segment().b().y + capsule.radius() * 0.75;
-                render_transform.translation =
-                    logical_transform.translation + Vec3::Y * camera_height;
-                render_transform.rotation = look_quat(controller.pitch, controller.yaw);
+                let translation = match controller.previous_translation {
+                    Some(previous) => previous.lerp(logical_transform.translation, alpha),
+                    None => logical_transform.translation,
+                };
+                render_transform.translation = translation + Vec3::Y * camera_height;
+                // Straight from input, looking around shouldn't wait for the next tick
+                render_transform.rotation = look_quat(input.pitch, input.yaw);
             }
         }
     }
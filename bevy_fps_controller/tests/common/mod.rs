@@ -0,0 +1,156 @@
+//! Headless app for driving the controller over test geometry, no window or renderer needed.
+
+#![allow(dead_code)]
+
+use std::time::{Duration, Instant};
+
+use bevy::{
+    asset::AssetPlugin, core::TaskPoolOptions, hierarchy::HierarchyPlugin, input::InputPlugin,
+    prelude::*, scene::ScenePlugin, transform::TransformPlugin,
+};
+use bevy_fps_controller::controller::*;
+use bevy_rapier3d::prelude::*;
+
+pub const DT: f32 = 1.0 / 60.0;
+
+pub struct TestWorld {
+    pub app: App,
+    pub player: Entity,
+    now: Instant,
+}
+
+impl TestWorld {
+    /// An empty world with a flat floor at `y = 0` and the player's feet at `position`.
+    pub fn new(position: Vec3) -> Self {
+        TaskPoolOptions::default().create_default_pools();
+
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .insert_resource(RapierConfiguration::default())
+            .init_resource::<Windows>()
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(InputPlugin)
+            .add_plugin(AssetPlugin)
+            .add_plugin(ScenePlugin)
+            .add_asset::<Mesh>()
+            .add_plugin(FpsControllerPlugin)
+            .add_plugin(FpsControllerPhysicsPlugin::<NoUserData>::default());
+
+        let player = app
+            .world
+            .spawn()
+            .insert(Collider::capsule(Vec3::Y * 0.5, Vec3::Y * 1.5, 0.5))
+            .insert(Velocity::zero())
+            .insert(RigidBody::Dynamic)
+            .insert(Sleeping::disabled())
+            .insert(LockedAxes::ROTATION_LOCKED)
+            .insert(AdditionalMassProperties::Mass(1.0))
+            .insert(GravityScale(0.0))
+            .insert(Ccd { enabled: true })
+            .insert_bundle(TransformBundle::from(Transform::from_translation(position)))
+            .insert(LogicalPlayer(0))
+            .insert(FpsControllerInput::default())
+            .insert(FpsController {
+                // Input is written directly by the tests
+                enable_input: false,
+                walk_speed: 4.0,
+                ..default()
+            })
+            .id();
+
+        let mut world = TestWorld {
+            app,
+            player,
+            now: Instant::now(),
+        };
+        world.add_box(Vec3::new(0.0, -0.5, 0.0), Vec3::new(20.0, 0.5, 40.0));
+        world
+    }
+
+    pub fn add_box(&mut self, center: Vec3, half_extents: Vec3) -> Entity {
+        self.add_rotated_box(center, half_extents, Quat::IDENTITY)
+    }
+
+    pub fn add_rotated_box(&mut self, center: Vec3, half_extents: Vec3, rotation: Quat) -> Entity {
+        self.app
+            .world
+            .spawn()
+            .insert(Collider::cuboid(
+                half_extents.x,
+                half_extents.y,
+                half_extents.z,
+            ))
+            .insert_bundle(TransformBundle::from(
+                Transform::from_translation(center).with_rotation(rotation),
+            ))
+            .id()
+    }
+
+    /// A ramp rising towards `-Z`, with its bottom edge on the floor at `z`.
+    pub fn add_ramp(&mut self, z: f32, angle: f32, length: f32) {
+        let half_length = length * 0.5;
+        self.add_rotated_box(
+            Vec3::new(
+                0.0,
+                half_length * angle.sin(),
+                z - half_length * angle.cos(),
+            ),
+            Vec3::new(2.0, 0.01, half_length),
+            Quat::from_rotation_x(-angle),
+        );
+    }
+
+    /// Stairs going up towards `-Z`, starting at `z`.
+    pub fn add_stairs(&mut self, z: f32, steps: usize, height: f32, depth: f32) {
+        for i in 0..steps {
+            let top = height * (i + 1) as f32;
+            self.add_box(
+                Vec3::new(0.0, top * 0.5, z - depth * (i as f32 + 0.5)),
+                Vec3::new(2.0, top * 0.5, depth * 0.5),
+            );
+        }
+    }
+
+    /// Advance one frame with the given [`FpsControllerInput::movement`].
+    pub fn step(&mut self, movement: Vec3) {
+        self.app
+            .world
+            .get_mut::<FpsControllerInput>(self.player)
+            .unwrap()
+            .movement = movement;
+        self.frame(Duration::from_secs_f32(DT));
+    }
+
+    /// Advance one frame of `frame_time`, running however many controller ticks fit in it.
+    pub fn frame(&mut self, frame_time: Duration) {
+        self.now += frame_time;
+        self.app
+            .world
+            .resource_mut::<Time>()
+            .update_with_instant(self.now);
+        self.app.update();
+    }
+
+    pub fn run(&mut self, seconds: f32, movement: Vec3) {
+        for _ in 0..(seconds / DT).round() as usize {
+            self.step(movement);
+        }
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.app
+            .world
+            .get::<Transform>(self.player)
+            .unwrap()
+            .translation
+    }
+
+    pub fn controller(&self) -> &FpsController {
+        self.app.world.get::<FpsController>(self.player).unwrap()
+    }
+
+    pub fn grounded(&self) -> bool {
+        self.controller().ground_tick > 0
+    }
+}
@@ -0,0 +1,112 @@
+//! The same input each tick gives the same movement, whatever the frame rate.
+
+mod common;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_fps_controller::controller::*;
+use common::TestWorld;
+
+const SECONDS: f64 = 5.0;
+
+/// Player positions at the start of each tick.
+#[derive(Default)]
+struct Recording {
+    tick: usize,
+    trajectory: Vec<Vec3>,
+}
+
+/// Input for a tick, walks around jumping, sprinting, crouching and turning over some stairs.
+fn script(tick: usize) -> FpsControllerInput {
+    let movement = match tick {
+        0..=119 => Vec3::new(0.0, 0.0, 1.0),
+        120..=239 => Vec3::new(1.0, 0.0, 1.0),
+        _ => Vec3::new(-0.5, 0.0, -1.0),
+    };
+    FpsControllerInput {
+        sprint: tick % 90 < 45,
+        jump: (60..200).contains(&tick),
+        crouch: (240..270).contains(&tick),
+        pitch: 0.0,
+        yaw: tick as f32 * 0.01,
+        movement,
+        ..default()
+    }
+}
+
+fn scripted_input(
+    mut recording: ResMut<Recording>,
+    mut query: Query<(&Transform, &mut FpsControllerInput)>,
+) {
+    let (transform, mut input) = query.single_mut();
+    recording.trajectory.push(transform.translation);
+    *input = script(recording.tick);
+    recording.tick += 1;
+}
+
+/// Runs the script for [`SECONDS`] with frames cycling through `frame_times`.
+fn trajectory(frame_times: &[f64]) -> Vec<Vec3> {
+    let mut world = TestWorld::new(Vec3::new(0.0, 0.1, 0.0));
+    world.add_stairs(-2.0, 5, 0.2, 0.4);
+    world.add_box(Vec3::new(4.0, 1.0, -4.0), Vec3::new(0.5, 1.0, 4.0));
+
+    world.app.init_resource::<Recording>();
+    world
+        .app
+        .stage(FpsControllerStage::Fixed, |schedule: &mut Schedule| {
+            schedule.add_system_to_stage(
+                FpsControllerStage::Move,
+                scripted_input.before(FpsControllerSystems::Look),
+            )
+        });
+
+    let mut elapsed = 0.0;
+    for frame_time in frame_times.iter().cycle() {
+        if elapsed >= SECONDS {
+            break;
+        }
+        world.frame(Duration::from_secs_f64(*frame_time));
+        elapsed += frame_time;
+    }
+
+    world
+        .app
+        .world
+        .remove_resource::<Recording>()
+        .unwrap()
+        .trajectory
+}
+
+fn assert_same_trajectory(frame_times: &[f64]) {
+    let expected = trajectory(&[TIMESTEP]);
+    let actual = trajectory(frame_times);
+
+    // Leftover time at the end can be a tick short either way
+    let ticks = (SECONDS / TIMESTEP) as usize;
+    assert!(expected.len() + 1 >= ticks, "only {} ticks", expected.len());
+    assert!(actual.len() + 1 >= ticks, "only {} ticks", actual.len());
+    assert!(
+        expected[0].distance(*expected.last().unwrap()) > 5.0,
+        "the script should move the player"
+    );
+
+    for (tick, (expected, actual)) in expected.iter().zip(&actual).enumerate() {
+        assert_eq!(expected, actual, "diverged at tick {tick}");
+    }
+}
+
+#[test]
+fn same_trajectory_at_30_fps() {
+    assert_same_trajectory(&[1.0 / 30.0]);
+}
+
+#[test]
+fn same_trajectory_at_144_fps() {
+    assert_same_trajectory(&[1.0 / 144.0]);
+}
+
+#[test]
+fn same_trajectory_with_uneven_frames() {
+    assert_same_trajectory(&[0.005, 0.023, 0.011, 0.04, 0.0167]);
+}
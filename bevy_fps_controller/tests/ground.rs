@@ -0,0 +1,110 @@
+//! Step-up, slope and ground snapping behaviour.
+
+mod common;
+
+use bevy::prelude::*;
+use common::TestWorld;
+
+const FORWARD: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+const BACK: Vec3 = Vec3::new(0.0, 0.0, -1.0);
+
+#[test]
+fn stands_on_flat_ground() {
+    let mut world = TestWorld::new(Vec3::new(0.0, 0.1, 0.0));
+    world.run(1.0, Vec3::ZERO);
+
+    assert!(world.grounded());
+    assert!(world.position().y.abs() < 0.1, "{}", world.position());
+}
+
+#[test]
+fn walks_up_stairs() {
+    let mut world = TestWorld::new(Vec3::ZERO);
+    world.add_stairs(-2.0, 5, 0.2, 0.4);
+    // Landing at the top
+    world.add_box(Vec3::new(0.0, 0.5, -9.0), Vec3::new(2.0, 0.5, 5.0));
+
+    world.run(2.5, FORWARD);
+
+    let position = world.position();
+    assert!(position.z < -5.0, "didn't reach the landing: {position}");
+    assert!(position.y > 0.9, "didn't climb the stairs: {position}");
+}
+
+#[test]
+fn blocked_by_ledge_taller_than_max_step() {
+    let mut world = TestWorld::new(Vec3::ZERO);
+    world.add_box(Vec3::new(0.0, 0.3, -6.0), Vec3::new(2.0, 0.3, 3.0));
+
+    world.run(3.0, FORWARD);
+
+    let position = world.position();
+    assert!(position.z > -3.0, "walked through the ledge: {position}");
+    assert!(position.y < 0.1, "climbed the ledge: {position}");
+}
+
+#[test]
+fn stays_grounded_walking_down_stairs() {
+    let mut world = TestWorld::new(Vec3::new(0.0, 1.0, -6.0));
+    world.add_box(Vec3::new(0.0, 0.5, -9.0), Vec3::new(2.0, 0.5, 5.0));
+    world.add_stairs(-2.0, 5, 0.2, 0.4);
+    world.run(0.5, Vec3::ZERO);
+
+    let mut airborne_frames = 0;
+    for _ in 0..(3.0 / common::DT) as usize {
+        world.step(BACK);
+        if !world.grounded() {
+            airborne_frames += 1;
+        }
+    }
+
+    let position = world.position();
+    assert!(position.z > 0.0, "didn't reach the bottom: {position}");
+    assert!(position.y < 0.1, "{position}");
+    assert!(
+        airborne_frames <= 2,
+        "left the ground for {airborne_frames} frames"
+    );
+}
+
+#[test]
+fn walks_up_walkable_slope() {
+    let mut world = TestWorld::new(Vec3::ZERO);
+    world.add_ramp(-2.0, 20f32.to_radians(), 20.0);
+
+    world.run(3.0, FORWARD);
+
+    let position = world.position();
+    assert!(position.y > 1.5, "didn't climb the slope: {position}");
+}
+
+#[test]
+fn cannot_walk_up_steep_slope() {
+    let mut world = TestWorld::new(Vec3::ZERO);
+    world.add_ramp(-2.0, 60f32.to_radians(), 10.0);
+
+    world.run(3.0, FORWARD);
+
+    let position = world.position();
+    assert!(position.y < 0.5, "climbed the steep slope: {position}");
+}
+
+#[test]
+fn slides_down_steep_slope() {
+    let angle = 60f32.to_radians();
+    let mut world = TestWorld::new(Vec3::ZERO);
+    world.add_ramp(-2.0, angle, 10.0);
+
+    // Just above the slope, 2m up
+    world
+        .app
+        .world
+        .get_mut::<Transform>(world.player)
+        .unwrap()
+        .translation = Vec3::new(0.0, 2.6, -2.0 - 2.0 / angle.tan());
+
+    world.run(2.0, Vec3::ZERO);
+
+    let position = world.position();
+    assert!(position.y < 0.5, "didn't slide down: {position}");
+}
@@ -0,0 +1,82 @@
+//! Riding moving platforms.
+
+mod common;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use common::{TestWorld, DT};
+
+fn add_platform(world: &mut TestWorld) -> Entity {
+    let platform = world.add_box(Vec3::new(0.0, 0.25, 0.0), Vec3::new(2.0, 0.25, 2.0));
+    world
+        .app
+        .world
+        .entity_mut(platform)
+        .insert(RigidBody::KinematicPositionBased);
+    platform
+}
+
+fn platform_transform(world: &mut TestWorld, platform: Entity) -> Mut<Transform> {
+    world.app.world.get_mut::<Transform>(platform).unwrap()
+}
+
+#[test]
+fn carried_by_platform_moving_sideways() {
+    let mut world = TestWorld::new(Vec3::new(0.0, 0.5, 0.0));
+    let platform = add_platform(&mut world);
+    world.run(0.5, Vec3::ZERO);
+
+    let speed = 1.5;
+    for _ in 0..(2.0 / DT) as usize {
+        platform_transform(&mut world, platform).translation.x += speed * DT;
+        world.step(Vec3::ZERO);
+    }
+
+    let platform_x = platform_transform(&mut world, platform).translation.x;
+    let position = world.position();
+    assert!(
+        (position.x - platform_x).abs() < 0.25,
+        "left behind by the platform: {position}, platform at {platform_x}"
+    );
+    assert!(world.grounded());
+}
+
+#[test]
+fn carried_by_platform_moving_up() {
+    let mut world = TestWorld::new(Vec3::new(0.0, 0.5, 0.0));
+    let platform = add_platform(&mut world);
+    world.run(0.5, Vec3::ZERO);
+
+    for _ in 0..(2.0 / DT) as usize {
+        platform_transform(&mut world, platform).translation.y += DT;
+        world.step(Vec3::ZERO);
+    }
+
+    let position = world.position();
+    assert!(
+        position.y > 2.2,
+        "didn't rise with the platform: {position}"
+    );
+    assert!(world.grounded());
+}
+
+#[test]
+fn turns_with_rotating_platform() {
+    let mut world = TestWorld::new(Vec3::new(0.0, 0.5, 0.0));
+    let platform = add_platform(&mut world);
+    world.run(0.5, Vec3::ZERO);
+
+    let start_yaw = world.controller().yaw;
+    let turn = std::f32::consts::FRAC_PI_2;
+    for _ in 0..(1.0 / DT) as usize {
+        platform_transform(&mut world, platform).rotate_y(turn * DT);
+        world.step(Vec3::ZERO);
+    }
+    world.step(Vec3::ZERO);
+
+    let turned = world.controller().yaw - start_yaw;
+    assert!(
+        (turned - turn).abs() < 0.2,
+        "turned {turned} radians, expected {turn}"
+    );
+}
@@ -0,0 +1,493 @@
+//! Maps keyboard, mouse and gamepad inputs to game [`Action`]s.
+//!
+//! Systems read `Res<Input<Action>>` instead of raw key codes so bindings can be changed at runtime.
+//! Bindings are persisted to [`BINDINGS_PATH`].
+
+use std::{collections::HashMap, fs};
+
+use bevy::{
+    input::{mouse::MouseMotion, InputSystem},
+    prelude::*,
+    utils::HashSet,
+};
+use bevy_fps_controller::controller::{
+    apply_look, gamepad_stick, FpsController, FpsControllerInput, FpsControllerSystems,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::entity::phone::PhoneUiVisible;
+
+pub const BINDINGS_PATH: &str = "bindings.json";
+
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputBindings::load())
+            .init_resource::<Input<Action>>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_actions.label(ActionsSystem).after(InputSystem),
+            )
+            .add_system(controller_input_from_actions.after(FpsControllerSystems::Input));
+    }
+}
+
+/// Label of the system updating [`Input<Action>`] in [`CoreStage::PreUpdate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, SystemLabel)]
+pub struct ActionsSystem;
+
+/// While this resource exists devices are ignored, so something else can drive [`Input<Action>`]
+/// and [`FpsControllerInput`], such as a replay.
+pub struct ActionsOverride;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    FlyUp,
+    FlyDown,
+    Jump,
+    Sprint,
+    Crouch,
+    Fly,
+    Interact,
+    /// Throw the carried prop, interact picks it up and puts it down
+    Throw,
+    /// Turn the carried prop while held
+    RotateProp,
+    /// Select the next held item to use
+    NextItem,
+    PreviousItem,
+    ToggleCursor,
+    ReleaseCursor,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    Phone0,
+    Phone1,
+    Phone2,
+    Phone3,
+    Phone4,
+    Phone5,
+    Phone6,
+    Phone7,
+    Phone8,
+    Phone9,
+    PhoneBackspace,
+    /// Press the focused phone key, which is moved with the menu actions
+    PhoneSelect,
+    PhoneClose,
+}
+
+impl Action {
+    pub const ALL: [Action; 34] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::FlyUp,
+        Action::FlyDown,
+        Action::Jump,
+        Action::Sprint,
+        Action::Crouch,
+        Action::Fly,
+        Action::Interact,
+        Action::Throw,
+        Action::RotateProp,
+        Action::NextItem,
+        Action::PreviousItem,
+        Action::ToggleCursor,
+        Action::ReleaseCursor,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::MenuLeft,
+        Action::MenuRight,
+        Action::Phone0,
+        Action::Phone1,
+        Action::Phone2,
+        Action::Phone3,
+        Action::Phone4,
+        Action::Phone5,
+        Action::Phone6,
+        Action::Phone7,
+        Action::Phone8,
+        Action::Phone9,
+        Action::PhoneBackspace,
+        Action::PhoneSelect,
+        Action::PhoneClose,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "Forward",
+            Action::MoveBack => "Back",
+            Action::MoveLeft => "Left",
+            Action::MoveRight => "Right",
+            Action::FlyUp => "Fly Up",
+            Action::FlyDown => "Fly Down",
+            Action::Jump => "Jump",
+            Action::Sprint => "Sprint",
+            Action::Crouch => "Crouch",
+            Action::Fly => "Fly",
+            Action::Interact => "Interact",
+            Action::Throw => "Throw",
+            Action::RotateProp => "Rotate Prop",
+            Action::NextItem => "Next Item",
+            Action::PreviousItem => "Previous Item",
+            Action::ToggleCursor => "Toggle Cursor",
+            Action::ReleaseCursor => "Release Cursor",
+            Action::MenuUp => "Menu Up",
+            Action::MenuDown => "Menu Down",
+            Action::MenuLeft => "Menu Left",
+            Action::MenuRight => "Menu Right",
+            Action::Phone0 => "Phone 0",
+            Action::Phone1 => "Phone 1",
+            Action::Phone2 => "Phone 2",
+            Action::Phone3 => "Phone 3",
+            Action::Phone4 => "Phone 4",
+            Action::Phone5 => "Phone 5",
+            Action::Phone6 => "Phone 6",
+            Action::Phone7 => "Phone 7",
+            Action::Phone8 => "Phone 8",
+            Action::Phone9 => "Phone 9",
+            Action::PhoneBackspace => "Phone Delete",
+            Action::PhoneSelect => "Phone Select",
+            Action::PhoneClose => "Phone Close",
+        }
+    }
+}
+
+/// A single physical input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// A button on any connected gamepad
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    fn pressed(
+        &self,
+        keys: &Input<KeyCode>,
+        mouse_buttons: &Input<MouseButton>,
+        gamepad_buttons: &Input<GamepadButton>,
+        gamepads: &Gamepads,
+    ) -> bool {
+        match self {
+            Binding::Key(key) => keys.pressed(*key),
+            Binding::Mouse(button) => mouse_buttons.pressed(*button),
+            Binding::Gamepad(button) => gamepads
+                .iter()
+                .any(|gamepad| gamepad_buttons.pressed(GamepadButton(*gamepad, *button))),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{key:?}"),
+            Binding::Mouse(button) => format!("Mouse {button:?}"),
+            Binding::Gamepad(button) => format!("Pad {button:?}"),
+        }
+    }
+
+    /// The first input pressed this frame, used when rebinding.
+    pub fn just_pressed(
+        keys: &Input<KeyCode>,
+        mouse_buttons: &Input<MouseButton>,
+        gamepad_buttons: &Input<GamepadButton>,
+    ) -> Option<Binding> {
+        keys.get_just_pressed()
+            .next()
+            .map(|key| Binding::Key(*key))
+            .or_else(|| {
+                mouse_buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Mouse(*button))
+            })
+            .or_else(|| {
+                gamepad_buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Gamepad(button.1))
+            })
+    }
+}
+
+/// One or more inputs for each [`Action`].
+#[derive(Clone, Debug, Deref, DerefMut, Serialize, Deserialize)]
+pub struct InputBindings(pub HashMap<Action, Vec<Binding>>);
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use Binding::*;
+        use GamepadButtonType as Pad;
+
+        InputBindings(HashMap::from([
+            (Action::MoveForward, vec![Key(KeyCode::W)]),
+            (Action::MoveBack, vec![Key(KeyCode::S)]),
+            (Action::MoveLeft, vec![Key(KeyCode::A)]),
+            (Action::MoveRight, vec![Key(KeyCode::D)]),
+            (Action::FlyUp, vec![Key(KeyCode::Q)]),
+            (Action::FlyDown, vec![Key(KeyCode::E)]),
+            // Jumping and flying are disabled unless bound from the menu
+            (Action::Jump, vec![]),
+            (
+                Action::Sprint,
+                vec![Key(KeyCode::LShift), Gamepad(Pad::LeftThumb)],
+            ),
+            (
+                Action::Crouch,
+                vec![Key(KeyCode::LControl), Gamepad(Pad::RightThumb)],
+            ),
+            (Action::Fly, vec![]),
+            (
+                Action::Interact,
+                vec![Mouse(MouseButton::Left), Gamepad(Pad::South)],
+            ),
+            (
+                Action::Throw,
+                vec![Mouse(MouseButton::Right), Gamepad(Pad::RightTrigger2)],
+            ),
+            (
+                Action::RotateProp,
+                vec![Key(KeyCode::R), Gamepad(Pad::LeftTrigger2)],
+            ),
+            (
+                Action::NextItem,
+                vec![Key(KeyCode::X), Gamepad(Pad::RightTrigger)],
+            ),
+            (
+                Action::PreviousItem,
+                vec![Key(KeyCode::Z), Gamepad(Pad::LeftTrigger)],
+            ),
+            (
+                Action::ToggleCursor,
+                vec![Key(KeyCode::Tab), Gamepad(Pad::Start)],
+            ),
+            (Action::ReleaseCursor, vec![Key(KeyCode::Escape)]),
+            (
+                Action::MenuUp,
+                vec![Key(KeyCode::Up), Key(KeyCode::W), Gamepad(Pad::DPadUp)],
+            ),
+            (
+                Action::MenuDown,
+                vec![Key(KeyCode::Down), Key(KeyCode::S), Gamepad(Pad::DPadDown)],
+            ),
+            (
+                Action::MenuLeft,
+                vec![Key(KeyCode::Left), Key(KeyCode::A), Gamepad(Pad::DPadLeft)],
+            ),
+            (
+                Action::MenuRight,
+                vec![
+                    Key(KeyCode::Right),
+                    Key(KeyCode::D),
+                    Gamepad(Pad::DPadRight),
+                ],
+            ),
+            (
+                Action::Phone0,
+                vec![Key(KeyCode::Key0), Key(KeyCode::Numpad0)],
+            ),
+            (
+                Action::Phone1,
+                vec![Key(KeyCode::Key1), Key(KeyCode::Numpad1)],
+            ),
+            (
+                Action::Phone2,
+                vec![Key(KeyCode::Key2), Key(KeyCode::Numpad2)],
+            ),
+            (
+                Action::Phone3,
+                vec![Key(KeyCode::Key3), Key(KeyCode::Numpad3)],
+            ),
+            (
+                Action::Phone4,
+                vec![Key(KeyCode::Key4), Key(KeyCode::Numpad4)],
+            ),
+            (
+                Action::Phone5,
+                vec![Key(KeyCode::Key5), Key(KeyCode::Numpad5)],
+            ),
+            (
+                Action::Phone6,
+                vec![Key(KeyCode::Key6), Key(KeyCode::Numpad6)],
+            ),
+            (
+                Action::Phone7,
+                vec![Key(KeyCode::Key7), Key(KeyCode::Numpad7)],
+            ),
+            (
+                Action::Phone8,
+                vec![Key(KeyCode::Key8), Key(KeyCode::Numpad8)],
+            ),
+            (
+                Action::Phone9,
+                vec![Key(KeyCode::Key9), Key(KeyCode::Numpad9)],
+            ),
+            (
+                Action::PhoneBackspace,
+                vec![Key(KeyCode::Back), Gamepad(Pad::West)],
+            ),
+            (
+                Action::PhoneSelect,
+                vec![Key(KeyCode::Return), Gamepad(Pad::South)],
+            ),
+            (Action::PhoneClose, vec![Gamepad(Pad::East)]),
+        ]))
+    }
+}
+
+impl InputBindings {
+    /// Load the bindings file, falling back to defaults for missing actions.
+    pub fn load() -> InputBindings {
+        let mut bindings = InputBindings::default();
+        if let Ok(bytes) = fs::read(BINDINGS_PATH) {
+            match serde_json::from_slice::<InputBindings>(&bytes) {
+                Ok(saved) => bindings.extend(saved.0),
+                Err(err) => warn!(%err, "Invalid bindings file, using defaults"),
+            }
+        }
+        bindings
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_vec_pretty(self)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| fs::write(BINDINGS_PATH, bytes).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            warn!(%err, "Failed to save bindings");
+        }
+    }
+
+    /// Replace all inputs of an action with a single binding.
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        self.insert(action, vec![binding]);
+    }
+
+    pub fn label(&self, action: Action) -> String {
+        match self.get(&action) {
+            Some(bindings) if !bindings.is_empty() => bindings
+                .iter()
+                .map(|binding| binding.label())
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => String::from("none"),
+        }
+    }
+}
+
+fn update_actions(
+    bindings: Res<InputBindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepads: Res<Gamepads>,
+    overridden: Option<Res<ActionsOverride>>,
+    phone_ui_visible: Res<PhoneUiVisible>,
+    mut held_over_phone: Local<bool>,
+    mut actions: ResMut<Input<Action>>,
+) {
+    actions.clear();
+    if overridden.is_some() {
+        return;
+    }
+
+    let mut pressed = HashSet::new();
+    for (action, inputs) in bindings.iter() {
+        if inputs
+            .iter()
+            .any(|input| input.pressed(&keys, &mouse_buttons, &gamepad_buttons, &gamepads))
+        {
+            pressed.insert(*action);
+        }
+    }
+
+    // The phone's keys share buttons with interact, so it doesn't reach the level behind the
+    // phone, even once it's closed, until let go
+    if !pressed.contains(&Action::Interact) {
+        *held_over_phone = false;
+    } else if **phone_ui_visible {
+        *held_over_phone = true;
+    }
+    if *held_over_phone {
+        pressed.remove(&Action::Interact);
+    }
+
+    for action in Action::ALL {
+        if pressed.contains(&action) {
+            actions.press(action);
+        } else if actions.pressed(action) {
+            actions.release(action);
+        }
+    }
+}
+
+/// Drives the player from [`Input<Action>`], the controller doesn't read devices itself so
+/// nothing moves the player under [`ActionsOverride`] but the override.
+fn controller_input_from_actions(
+    time: Res<Time>,
+    actions: Res<Input<Action>>,
+    gamepads: Res<Gamepads>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    windows: Res<Windows>,
+    mut mouse_events: EventReader<MouseMotion>,
+    overridden: Option<Res<ActionsOverride>>,
+    mut query: Query<(&FpsController, &mut FpsControllerInput)>,
+) {
+    let mouse_delta: Vec2 = mouse_events.iter().map(|event| event.delta).sum();
+    if overridden.is_some() {
+        return;
+    }
+    let focused = windows
+        .get_primary()
+        .map_or(false, |window| window.is_focused());
+
+    let axis = |pos: Action, neg: Action| {
+        actions.pressed(pos) as u32 as f32 - actions.pressed(neg) as u32 as f32
+    };
+
+    for (controller, mut input) in query.iter_mut() {
+        if !controller.enable_input {
+            continue;
+        }
+        let stick = gamepad_stick(
+            &gamepads,
+            &gamepad_axes,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+            controller.gamepad_deadzone,
+        );
+        if focused {
+            let look_stick = gamepad_stick(
+                &gamepads,
+                &gamepad_axes,
+                GamepadAxisType::RightStickX,
+                GamepadAxisType::RightStickY,
+                controller.gamepad_deadzone,
+            );
+            apply_look(
+                controller,
+                &mut input,
+                mouse_delta,
+                look_stick,
+                time.delta_seconds(),
+            );
+        }
+        input.movement = Vec3::new(
+            (axis(Action::MoveRight, Action::MoveLeft) + stick.x).clamp(-1.0, 1.0),
+            axis(Action::FlyUp, Action::FlyDown),
+            (axis(Action::MoveForward, Action::MoveBack) + stick.y).clamp(-1.0, 1.0),
+        );
+        input.sprint = actions.pressed(Action::Sprint);
+        input.jump = actions.pressed(Action::Jump);
+        input.crouch = actions.pressed(Action::Crouch);
+        input.fly |= actions.just_pressed(Action::Fly);
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use bevy::{
     prelude::*,
     render::mesh::{Indices, VertexAttributeValues},
@@ -5,7 +7,7 @@ use bevy::{
 use bevy_asset_loader::prelude::*;
 use bevy_kira_audio::AudioSource;
 
-use crate::scene_hook::SceneLoaded;
+use crate::{item::ItemDefinitions, logic_graph::LogicGraph, scene_hook::SceneLoaded};
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 pub enum GameState {
@@ -19,12 +21,14 @@ pub struct FontAssets {
     pub fira_mono_medium: Handle<Font>,
 }
 
+#[derive(AssetCollection)]
+pub struct DataAssets {
+    #[asset(path = "data/game.items")]
+    pub items: Handle<ItemDefinitions>,
+}
+
 #[derive(AssetCollection)]
 pub struct ImageAssets {
-    #[asset(path = "images/key.png")]
-    pub key: Handle<Image>,
-    #[asset(path = "images/money.png")]
-    pub money: Handle<Image>,
     #[asset(path = "images/phone/base.png")]
     pub phone_base: Handle<Image>,
     #[asset(path = "images/phone/0.png")]
@@ -99,6 +103,24 @@ pub struct ModelAssets {
     pub test_area: Handle<Scene>,
 }
 
+#[derive(AssetCollection)]
+pub struct LogicAssets {
+    #[asset(path = "models/Elevator Export.logic")]
+    pub elevator_level: Handle<LogicGraph>,
+    #[asset(path = "models/Level 1 Parking Garage Export.logic")]
+    pub level1_garage: Handle<LogicGraph>,
+    #[asset(path = "models/Level 2 Lobby.logic")]
+    pub level2_lobby: Handle<LogicGraph>,
+    #[asset(path = "models/Level 3 Chair Export.logic")]
+    pub level3_chair: Handle<LogicGraph>,
+    #[asset(path = "models/Level 4 Chairs Pile Export.logic")]
+    pub level4_chairs_pile: Handle<LogicGraph>,
+    #[asset(path = "models/Level 5 Parking Garage Lobby Export.logic")]
+    pub level5_garage_lobby: Handle<LogicGraph>,
+    #[asset(path = "models/test_area.logic")]
+    pub test_area: Handle<LogicGraph>,
+}
+
 #[derive(AssetCollection)]
 pub struct SoundAssets {
     #[asset(path = "sounds/gate.flac")]
@@ -141,28 +163,74 @@ pub struct SoundAssets {
     pub ending: Handle<AudioSource>,
 }
 
-pub fn get_verts_indices(mesh: &Mesh) -> (Vec<Vec3>, Vec<[u32; 3]>) {
-    let vertices = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
-        None => panic!("Mesh does not contain vertex positions"),
-        Some(vertex_values) => match &vertex_values {
-            VertexAttributeValues::Float32x3(positions) => positions
-                .iter()
-                .map(|[x, y, z]| Vec3::new(*x, *y, *z))
-                .collect(),
-            _ => panic!("Unexpected types in {:?}", Mesh::ATTRIBUTE_POSITION),
-        },
-    };
+impl SoundAssets {
+    /// Look up a sound by its field name, for data driven sounds.
+    pub fn by_name(&self, name: &str) -> Option<Handle<AudioSource>> {
+        let handle = match name {
+            "gate" => &self.gate,
+            "elevator_transport" => &self.elevator_transport,
+            "click" => &self.click,
+            "bad_click" => &self.bad_click,
+            "phone_call" => &self.phone_call,
+            "door_open" => &self.door_open,
+            "keys_pickup" => &self.keys_pickup,
+            "phone_hangup" => &self.phone_hangup,
+            "phone_key_press" => &self.phone_key_press,
+            "phone_number_not_available" => &self.phone_number_not_available,
+            "phone_pickup" => &self.phone_pickup,
+            "phone_background" => &self.phone_background,
+            "drone1" => &self.drone1,
+            "lobby_in_garage" => &self.lobby_in_garage,
+            "chairs" => &self.chairs,
+            "rings" => &self.rings,
+            "lobby_music" => &self.lobby_music,
+            "ending" => &self.ending,
+            _ => return None,
+        };
+        Some(handle.clone())
+    }
+}
+
+/// Why a mesh can't be turned into a collider.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshError {
+    MissingPositions,
+    UnsupportedPositions,
+    /// The vertices don't enclose a volume, so there's no convex hull
+    DegenerateHull,
+}
 
-    let indices = match mesh.indices().unwrap() {
-        Indices::U16(_) => {
-            panic!("expected u32 indices");
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::MissingPositions => write!(f, "no vertex positions"),
+            MeshError::UnsupportedPositions => write!(f, "vertex positions aren't Float32x3"),
+            MeshError::DegenerateHull => write!(f, "vertices are flat, no convex hull"),
         }
-        Indices::U32(indices) => indices
-            .chunks(3)
-            .map(|chunk| [chunk[0], chunk[1], chunk[2]])
+    }
+}
+
+pub fn get_verts_indices(mesh: &Mesh) -> Result<(Vec<Vec3>, Vec<[u32; 3]>), MeshError> {
+    let vertices: Vec<Vec3> = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        None => return Err(MeshError::MissingPositions),
+        Some(VertexAttributeValues::Float32x3(positions)) => positions
+            .iter()
+            .map(|[x, y, z]| Vec3::new(*x, *y, *z))
             .collect(),
+        Some(_) => return Err(MeshError::UnsupportedPositions),
+    };
+
+    let indices: Vec<u32> = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.iter().map(|i| *i as u32).collect(),
+        Some(Indices::U32(indices)) => indices.clone(),
+        // Unindexed, every three vertices are a triangle
+        None => (0..vertices.len() as u32).collect(),
     };
-    (vertices, indices)
+    let indices = indices
+        .chunks_exact(3)
+        .map(|chunk| [chunk[0], chunk[1], chunk[2]])
+        .collect();
+    Ok((vertices, indices))
 }
 
 // If an entity has no mesh, but has a child with a mesh and no children copy it's name to the child
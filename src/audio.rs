@@ -1,27 +1,64 @@
+//! Sounds which come from somewhere in the level.
+//!
+//! Each [`AudioComponent`] plays on its own channel, whose panning, volume and pitch follow where
+//! it is relative to the [`PlayerCamera`]. How it carries is set by an optional
+//! [`SpatialAudio`], with the defaults if there isn't one.
+//!
+//! Emitters are updated at a fixed [`AUDIO_STEP`] rather than every frame, each change is a
+//! command to the audio thread and 30 a second is smooth enough to hear. The Doppler shift is
+//! measured over the real time between updates, so it's the same when the step runs twice or
+//! not at all in a frame.
+//!
+//! Occlusion only lowers the volume, sounds behind walls aren't muffled. bevy_kira_audio's
+//! channels have no way to add kira effects, so a low-pass filter would mean playing emitters
+//! on kira tracks built with a `FilterBuilder`, outside the plugin. Until then it isn't done.
+
 use std::time::Duration;
 
-use bevy::{prelude::*, utils::Uuid};
-use bevy_kira_audio::prelude::{AudioControl, DynamicAudioChannel, DynamicAudioChannels};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Uuid},
+};
+use bevy_kira_audio::prelude::{AudioApp, AudioControl, DynamicAudioChannel, DynamicAudioChannels};
+use bevy_rapier3d::prelude::{QueryFilter, RapierContext};
 use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{levels::Level, menu::GameSettings, PlayerCamera};
+
+/// How often emitters are updated.
+const AUDIO_STEP: Duration = Duration::from_millis(32); // 30 fps
 
-use crate::PlayerCamera;
+/// Metres per second, for the Doppler effect.
+const SPEED_OF_SOUND: f32 = 343.0;
+
+/// Volume of a sound with something solid between it and the camera. It's only quieter rather
+/// than duller, see the module docs.
+const OCCLUDED_VOLUME: f32 = 0.35;
 
 pub struct AudioComponentPlugin;
 
 impl Plugin for AudioComponentPlugin {
     fn build(&self, app: &mut App) {
         let mut audio_stage = SystemStage::parallel();
-        audio_stage.add_system(pan);
+        audio_stage.add_system(spatialize);
+
+        app.add_audio_channel::<MusicChannel>();
+        app.register_type::<SpatialAudio>();
+        // Sees everything despawned in `Update`, before removals are cleared for the next frame
+        app.add_system_to_stage(CoreStage::PostUpdate, stop_removed_emitters);
 
         app.add_stage_before(
             CoreStage::Update,
             "audio_fixed_update",
-            FixedTimestepStage::new(Duration::from_millis(32)) // 30 fps
-                .with_stage(audio_stage),
+            FixedTimestepStage::new(AUDIO_STEP).with_stage(audio_stage),
         );
     }
 }
 
+/// Channel for music and drones, so they can be mixed separately from sound effects.
+pub struct MusicChannel;
+
 #[derive(Component, Clone)]
 pub struct AudioComponent(String);
 
@@ -50,24 +87,262 @@ impl AudioComponent {
     }
 }
 
-fn pan(
+/// How quickly a sound gets quieter with distance, between its `min_distance`, where it's at
+/// full volume, and `max_distance`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum DistanceModel {
+    /// Fades evenly, silent from the max distance
+    Linear,
+    /// Halves each time the distance doubles, like in real life
+    Inverse,
+    /// Fades faster than [`DistanceModel::Inverse`] for a rolloff over 1
+    Exponential,
+}
+
+impl DistanceModel {
+    /// Volume from 0 to 1 at `distance`. A larger `rolloff` fades faster, the volume stops
+    /// changing past `max_distance`.
+    pub fn volume(self, distance: f32, min_distance: f32, max_distance: f32, rolloff: f32) -> f32 {
+        let min_distance = min_distance.max(f32::EPSILON);
+        let max_distance = max_distance.max(min_distance);
+        let distance = distance.clamp(min_distance, max_distance);
+        let volume = match self {
+            DistanceModel::Linear if max_distance > min_distance => {
+                1.0 - rolloff * (distance - min_distance) / (max_distance - min_distance)
+            }
+            DistanceModel::Linear => 1.0,
+            DistanceModel::Inverse => {
+                min_distance / (min_distance + rolloff * (distance - min_distance))
+            }
+            DistanceModel::Exponential => (distance / min_distance).powf(-rolloff),
+        };
+        volume.clamp(0.0, 1.0)
+    }
+}
+
+impl Default for DistanceModel {
+    fn default() -> Self {
+        DistanceModel::Inverse
+    }
+}
+
+/// How an [`AudioComponent`]'s sound carries.
+#[derive(Clone, Debug, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+#[serde(default)]
+pub struct SpatialAudio {
+    /// Volume up close
+    pub volume: f32,
+    pub distance_model: DistanceModel,
+    /// Full volume within this distance
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub rolloff: f32,
+    /// Strength of the pitch shift as the sound and camera move towards or away from each
+    /// other, 0 for none. Off by default, it only suits sounds which move.
+    pub doppler: f32,
+    /// Whether it's quieter with something solid in the way, it isn't low-pass filtered
+    pub occlusion: bool,
+}
+
+impl Default for SpatialAudio {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            distance_model: DistanceModel::default(),
+            min_distance: 10.0,
+            max_distance: 500.0,
+            rolloff: 1.0,
+            doppler: 0.0,
+            occlusion: false,
+        }
+    }
+}
+
+/// Playback rate for a sound moving away from the camera at `speed`, negative when getting
+/// closer, scaled by the emitter's `doppler`.
+pub fn doppler_rate(speed: f32, doppler: f32) -> f32 {
+    let speed = (speed * doppler).clamp(-SPEED_OF_SOUND * 0.5, SPEED_OF_SOUND * 0.5);
+    SPEED_OF_SOUND / (SPEED_OF_SOUND + speed)
+}
+
+/// Distance to an emitter at some time since startup, in seconds.
+#[derive(Clone, Copy)]
+struct Measured {
+    distance: f32,
+    time: f64,
+}
+
+fn spatialize(
     audio: Res<DynamicAudioChannels>,
-    query: Query<(&Transform, &AudioComponent)>,
+    emitters: Query<(
+        Entity,
+        &GlobalTransform,
+        &AudioComponent,
+        Option<&SpatialAudio>,
+    )>,
     player_camera: Query<&Transform, With<PlayerCamera>>,
+    parents: Query<&Parent>,
+    physics_context: Res<RapierContext>,
+    game_settings: Res<GameSettings>,
+    time: Res<Time>,
+    level: Res<Level>,
+    mut distances: Local<HashMap<Entity, Measured>>,
+) {
+    let cam_trans = match player_camera.iter().next() {
+        Some(cam_trans) => cam_trans,
+        None => return,
+    };
+    let default_spatial = SpatialAudio::default();
+    // Everything moves when the level changes
+    if level.is_changed() {
+        distances.clear();
+    }
+    // Emitters which are gone are dropped
+    let mut last_distances = std::mem::take(&mut *distances);
+    let now = time.seconds_since_startup();
+
+    for (entity, aud_trans, ch, spatial) in &emitters {
+        let spatial = spatial.unwrap_or(&default_spatial);
+        let ch = ch.channel(&audio);
+        let camera_to_aud = aud_trans.translation() - cam_trans.translation;
+        let dist = camera_to_aud.length();
+        // Get dot product between camera right vector, and vector pointing from camera to sound src
+        // When pointing at sound src dot is 0.0, when pointing right dot is 1.0, left is -1.0
+        let mut pan = cam_trans.right().dot(camera_to_aud.normalize_or_zero());
+        pan = pan * 0.5 + 0.5; // pan input expects 0.0 to 1.0
+        ch.set_panning(pan as f64);
+
+        let mut level = spatial.volume
+            * spatial.distance_model.volume(
+                dist,
+                spatial.min_distance,
+                spatial.max_distance,
+                spatial.rolloff,
+            );
+        if spatial.occlusion
+            && is_occluded(
+                entity,
+                cam_trans,
+                dist,
+                camera_to_aud,
+                &parents,
+                &physics_context,
+            )
+        {
+            level *= OCCLUDED_VOLUME;
+        }
+        ch.set_volume(level as f64 * game_settings.sfx());
+
+        // The pitch follows how quickly the distance changes, so needs an earlier measurement
+        let measured = Measured {
+            distance: dist,
+            time: now,
+        };
+        let measured = match last_distances.remove(&entity) {
+            // A second step in the same frame, nothing has moved since the first
+            Some(last) if last.time >= now => last,
+            Some(last) => {
+                if spatial.doppler != 0.0 {
+                    let elapsed = (now - last.time) as f32;
+                    let speed = (dist - last.distance) / elapsed;
+                    // Faster than sound is a teleport rather than movement
+                    let rate = if speed.abs() < SPEED_OF_SOUND {
+                        doppler_rate(speed, spatial.doppler)
+                    } else {
+                        1.0
+                    };
+                    ch.set_playback_rate(rate as f64);
+                }
+                measured
+            }
+            None => measured,
+        };
+        distances.insert(entity, measured);
+    }
+}
+
+/// Stop the sounds of emitters which were despawned, such as with their level, or looped ones
+/// would carry on.
+fn stop_removed_emitters(
+    added: Query<(Entity, &AudioComponent), Added<AudioComponent>>,
+    removed: RemovedComponents<AudioComponent>,
+    mut emitters: Local<HashMap<Entity, AudioComponent>>,
+    audio: Res<DynamicAudioChannels>,
 ) {
-    if let Some(cam_trans) = player_camera.iter().next() {
-        for (aud_trans, ch) in &query {
-            let ch = ch.channel(&audio);
-            let camera_to_aud = (aud_trans.translation - cam_trans.translation).normalize_or_zero();
-            // Get dot product between camera right vector, and vector pointing from camera to sound src
-            // When pointing at sound src dot is 0.0, when pointing right dot is 1.0, left is -1.0
-            let mut pan = cam_trans.right().dot(camera_to_aud);
-            pan = pan * 0.5 + 0.5; // pan input expects 0.0 to 1.0
-            ch.set_panning(pan as f64);
-
-            let dist = aud_trans.translation.distance(cam_trans.translation) * 0.1;
-            let level = (1.0 / dist).min(1.0); // not accurate falloff
-            ch.set_volume(level as f64);
+    for (entity, ch) in &added {
+        emitters.insert(entity, ch.clone());
+    }
+    for entity in removed.iter() {
+        if let Some(ch) = emitters.remove(&entity) {
+            ch.channel(&audio).stop();
         }
     }
 }
+
+/// Whether something solid is between the camera and an emitter, other than the emitter's own
+/// colliders.
+fn is_occluded(
+    emitter: Entity,
+    cam_trans: &Transform,
+    dist: f32,
+    camera_to_aud: Vec3,
+    parents: &Query<&Parent>,
+    physics_context: &RapierContext,
+) -> bool {
+    let is_solid =
+        |hit: Entity| hit != emitter && parents.get(hit).map_or(true, |parent| **parent != emitter);
+    // Sensors and the player's own body don't block sound
+    let filter = QueryFilter::default()
+        .exclude_sensors()
+        .exclude_dynamic()
+        .predicate(&is_solid);
+    physics_context
+        .cast_ray(
+            cam_trans.translation,
+            camera_to_aud.normalize_or_zero(),
+            dist,
+            true,
+            filter,
+        )
+        .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance_models_fade_between_min_and_max() {
+        for model in [
+            DistanceModel::Linear,
+            DistanceModel::Inverse,
+            DistanceModel::Exponential,
+        ] {
+            assert_eq!(model.volume(1.0, 2.0, 20.0, 1.0), 1.0, "{model:?}");
+            let near = model.volume(5.0, 2.0, 20.0, 1.0);
+            let far = model.volume(15.0, 2.0, 20.0, 1.0);
+            assert!(1.0 > near && near > far && far >= 0.0, "{model:?}");
+            assert_eq!(
+                model.volume(20.0, 2.0, 20.0, 1.0),
+                model.volume(100.0, 2.0, 20.0, 1.0),
+                "{model:?}"
+            );
+        }
+        assert_eq!(DistanceModel::Linear.volume(20.0, 2.0, 20.0, 1.0), 0.0);
+        assert!((DistanceModel::Inverse.volume(4.0, 2.0, 20.0, 1.0) - 0.5).abs() < 1e-6);
+        // Rolloff steepens the fade
+        assert!(
+            DistanceModel::Exponential.volume(4.0, 2.0, 20.0, 2.0)
+                < DistanceModel::Exponential.volume(4.0, 2.0, 20.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn doppler_raises_pitch_when_closing_in() {
+        assert_eq!(doppler_rate(0.0, 1.0), 1.0);
+        assert!(doppler_rate(-20.0, 1.0) > 1.0);
+        assert!(doppler_rate(20.0, 1.0) < 1.0);
+        assert_eq!(doppler_rate(-20.0, 0.0), 1.0);
+    }
+}
@@ -0,0 +1,68 @@
+//! On-screen list of problems with the loaded scenes, in debug builds.
+//!
+//! Shows [`EntityErrors`] so broken exports are noticed without watching the log.
+
+use bevy::prelude::*;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    assets::{FontAssets, GameState},
+    entity::error::EntityErrors,
+};
+
+pub struct DiagnosticsOverlayPlugin;
+
+impl Plugin for DiagnosticsOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_enter_system(GameState::RunLevel, create_overlay)
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::RunLevel)
+                    .with_system(update_overlay)
+                    .into(),
+            );
+    }
+}
+
+#[derive(Component)]
+struct DiagnosticsOverlay;
+
+fn create_overlay(mut cmds: Commands, font_assets: Res<FontAssets>) {
+    cmds.spawn_bundle(
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font_assets.fira_mono_medium.clone(),
+                font_size: 12.0,
+                color: Color::RED,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(2.0),
+                left: Val::Px(2.0),
+                ..default()
+            },
+            ..default()
+        }),
+    )
+    .insert(DiagnosticsOverlay);
+}
+
+fn update_overlay(
+    errors: Res<EntityErrors>,
+    mut overlay: Query<&mut Text, With<DiagnosticsOverlay>>,
+) {
+    if !errors.is_changed() {
+        return;
+    }
+    for mut text in &mut overlay {
+        if let Some(section) = text.sections.first_mut() {
+            section.value = errors
+                .iter()
+                .map(|(name, error)| format!("{name}: {error}\n"))
+                .collect();
+        }
+    }
+}
@@ -1,20 +1,38 @@
 pub mod block;
 pub mod button;
 pub mod collider;
+pub mod door_hinged;
 pub mod door_linear;
+pub mod error;
+pub mod item_target;
+pub mod name;
 pub mod phone;
+pub mod pickup;
+pub mod prop;
+pub mod sound;
 pub mod teleport;
 pub mod teleport_destination;
 pub mod trigger;
+pub mod tween;
 
-use bevy::prelude::*;
+use bevy::{prelude::*, transform::systems::transform_propagate_system};
+use bevy_fps_controller::controller::{FpsControllerStage, FpsControllerSystems};
+use iyes_loopless::prelude::*;
 
-use crate::{impl_named, register_entity};
+use crate::{assets::GameState, impl_named, register_entity};
+
+use self::name::NameQuery;
 
 pub struct EntityPlugin;
 
+/// Label for the systems moving doors and tweens, which run each controller tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, SystemLabel)]
+pub struct MoveEntitiesSystem;
+
 impl Plugin for EntityPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<error::EntityErrors>();
+
         // Block
         register_entity!(app, block);
 
@@ -24,19 +42,34 @@ impl Plugin for EntityPlugin {
             button,
             events = [ButtonEvent],
             resources = [NamedButtonStatuses],
-            systems = [button_interact_events]
+            systems = [
+                button_interact_events,
+                link_button_materials,
+                highlight_buttons
+            ]
         );
 
         // Collider
         register_entity!(app, collider);
 
+        // Door hinged
+        register_entity!(app, door_hinged);
+
         // Door linear
         register_entity!(
             app,
             door_linear,
-            events = [DoorFullyClosedEvent, DoorFullyOpenedEvent],
+            events = [DoorFullyClosedEvent, DoorFullyOpenedEvent, DoorBlockedEvent],
             resources = [NamedDoorStatuses],
-            systems = [door_sounds, update_door]
+            systems = [door_sounds, collect_named_door_statuses]
+        );
+
+        // Item target
+        register_entity!(
+            app,
+            item_target,
+            events = [UseItemSuccessEvent, UseItemFailureEvent],
+            systems = [use_items_on_targets, lock_item_targets]
         );
 
         // Phone
@@ -51,6 +84,7 @@ impl Plugin for EntityPlugin {
             ],
             resources = [PhoneUiEnabled, PhoneUiVisible],
             systems = [
+                close_phone,
                 number_not_availble,
                 press_phone_keys,
                 phone_interact_events,
@@ -60,6 +94,20 @@ impl Plugin for EntityPlugin {
             startup_systems = [setup_phone_ui]
         );
 
+        // Pickup
+        register_entity!(
+            app,
+            pickup,
+            resources = [CollectedPickups],
+            systems = [pick_up_items, despawn_collected_pickups]
+        );
+
+        // Prop
+        register_entity!(app, prop, systems = [carry_props]);
+
+        // Sound
+        register_entity!(app, sound, systems = [play_sounds]);
+
         // Teleport destination
         register_entity!(app, teleport_destination);
 
@@ -74,6 +122,32 @@ impl Plugin for EntityPlugin {
             resources = [NamedTriggerStatuses],
             systems = [trigger_collision_events]
         );
+
+        // Tween
+        register_entity!(app, tween);
+
+        // Doors and tweens move each controller tick, then update their global transforms so the
+        // controller and physics see where they are now rather than where they were last frame
+        app.stage(FpsControllerStage::Fixed, |schedule: &mut Schedule| {
+            schedule
+                .add_system_set_to_stage(
+                    FpsControllerStage::Move,
+                    ConditionSet::new()
+                        .run_in_state(GameState::RunLevel)
+                        .label(MoveEntitiesSystem)
+                        .before(FpsControllerSystems::Look)
+                        .with_system(door_hinged::update_hinged_doors)
+                        .with_system(door_linear::update_door)
+                        .with_system(tween::update_tweens)
+                        .into(),
+                )
+                .add_system_to_stage(
+                    FpsControllerStage::Move,
+                    transform_propagate_system
+                        .after(MoveEntitiesSystem)
+                        .before(FpsControllerSystems::Look),
+                )
+        });
     }
 }
 
@@ -97,7 +171,7 @@ impl_named!(A, B, C, D, E, F, G);
 
 pub struct NamedFilterMap<'a, I> {
     iter: I,
-    name: &'a str,
+    query: NameQuery<'a>,
 }
 
 impl<'a, I: Iterator> Iterator for NamedFilterMap<'a, I>
@@ -110,7 +184,7 @@ where
     fn next(&mut self) -> Option<I::Item> {
         self.iter.find_map(|item| {
             if let Some(item_name) = item.name() {
-                if item_name.contains(self.name) {
+                if self.query.matches(item_name) {
                     Some(item)
                 } else {
                     None
@@ -129,6 +203,10 @@ where
 }
 
 pub trait NamedIterator<I> {
+    /// Items whose name matches `query`, in the iterator's order.
+    fn filter_name(self, query: NameQuery) -> NamedFilterMap<I>;
+
+    /// Items whose name contains `name`, the same as `filter_name(NameQuery::contains(name))`.
     fn filter_name_contains(self, name: &str) -> NamedFilterMap<I>;
 }
 
@@ -137,7 +215,11 @@ where
     T: Iterator<Item = I>,
     I: Named,
 {
+    fn filter_name(self, query: NameQuery) -> NamedFilterMap<T> {
+        NamedFilterMap { iter: self, query }
+    }
+
     fn filter_name_contains(self, name: &str) -> NamedFilterMap<T> {
-        NamedFilterMap { iter: self, name }
+        self.filter_name(NameQuery::contains(name))
     }
 }
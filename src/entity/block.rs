@@ -13,4 +13,5 @@ pub struct Block {}
 
 spawn_from_scene!(block, Block, |cmds, _entity, _collider| {
     cmds.insert(Collider::cuboid(1.0, 1.0, 1.0));
+    Ok(())
 });
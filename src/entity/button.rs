@@ -1,8 +1,38 @@
-use bevy::{prelude::*, utils::hashbrown::HashMap};
+use bevy::{
+    ecs::system::{EntityCommands, SystemParam},
+    prelude::*,
+    utils::hashbrown::HashMap,
+};
 use bevy_rapier3d::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::{spawn_from_scene, PlayerCamera};
+use crate::{actions::Action, materials::general::GeneralMaterial, spawn_from_scene, PlayerCamera};
+
+use super::{
+    name::{EntityName, NameQuery},
+    NamedIterator,
+};
+
+/// How far away the player can reach buttons and props.
+pub const INTERACT_DISTANCE: f32 = 2.0;
+
+/// Highlight of a button's meshes while it's hovered.
+pub const HOVER_HIGHLIGHT: Color = Color::rgba(0.7, 0.7, 0.7, 1.0);
+
+/// What the player is looking at within reach, and how far away it is.
+pub fn interaction_ray(
+    camera: &Transform,
+    physics_context: &RapierContext,
+    filter: QueryFilter,
+) -> Option<(Entity, f32)> {
+    physics_context.cast_ray(
+        camera.translation,
+        camera.forward(),
+        INTERACT_DISTANCE,
+        false,
+        filter,
+    )
+}
 
 #[derive(Clone)]
 pub struct ButtonEvent {
@@ -18,66 +48,163 @@ pub struct ButtonEvent {
 #[serde(default)]
 pub struct Button {
     pub enabled: bool,
+    /// Shown under the crosshair while hovered
+    pub prompt: Option<String>,
+    /// Shown instead of `prompt` while the button is [locked](ButtonState::locked)
+    pub locked_prompt: Option<String>,
+    /// Meshes whose name contains this are highlighted while hovered, by default the ones with
+    /// the button's [id](EntityName)
+    pub mesh: Option<String>,
 }
 
 impl Default for Button {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            prompt: None,
+            locked_prompt: None,
+            mesh: None,
+        }
     }
 }
 
+impl Button {
+    /// Whether the mesh named `mesh_name` is highlighted with a button whose name has `id`.
+    fn highlights(&self, id: Option<&str>, mesh_name: &str) -> bool {
+        match &self.mesh {
+            Some(mesh) => mesh_name.contains(mesh.as_str()),
+            None => id.map_or(false, |id| {
+                !id.is_empty() && EntityName::parse(mesh_name).id == id
+            }),
+        }
+    }
+
+    /// The prompt to show for a button in `state`, if any.
+    pub fn prompt(&self, state: &ButtonState) -> Option<&str> {
+        if state.locked {
+            self.locked_prompt.as_deref().or(self.prompt.as_deref())
+        } else {
+            self.prompt.as_deref()
+        }
+    }
+}
+
+/// Named buttons hovered this frame, and for one frame after they stop being hovered. See
+/// [`ButtonStates`] for buttons' current state.
 #[derive(Default)]
-pub struct NamedButtonStatuses(HashMap<String, ButtonEvent>);
+pub struct NamedButtonStatuses(HashMap<Entity, ButtonEvent>);
 
 impl NamedButtonStatuses {
+    /// The first button in name order whose name contains `pat`.
     pub fn any(&self, pat: &str) -> Option<ButtonEvent> {
-        for (name, event) in &self.0 {
-            if name.contains(pat) {
-                return Some(event.clone());
-            }
+        self.find(NameQuery::contains(pat))
+    }
+
+    /// The first button in name order matching `query`.
+    pub fn find(&self, query: NameQuery) -> Option<ButtonEvent> {
+        query.first_in(self.named()).map(|(_, event)| event.clone())
+    }
+
+    /// Every button matching `query`, sorted by name.
+    pub fn all(&self, query: NameQuery) -> Vec<(&str, &ButtonEvent)> {
+        query.all_in(self.named())
+    }
+
+    fn named(&self) -> impl Iterator<Item = (&str, &ButtonEvent)> {
+        self.0
+            .values()
+            .filter_map(|event| Some((event.name.as_deref()?, event)))
+    }
+}
+
+/// Whether the player is looking at a button, kept up to date every frame.
+#[derive(Clone, Copy, Debug, Default, Component)]
+pub struct ButtonState {
+    pub hovered: bool,
+    /// Pressing it won't work yet, such as an
+    /// [`ItemTarget`](super::item_target::ItemTarget) without its item selected
+    pub locked: bool,
+}
+
+/// Highlight of a button's meshes while it isn't hovered, for showing what state it's in.
+#[derive(Clone, Debug, Component)]
+pub struct ButtonHighlight {
+    pub color: Color,
+    /// Materials of the meshes it's shown on, see [`Button::mesh`]
+    materials: Vec<Handle<GeneralMaterial>>,
+}
+
+impl Default for ButtonHighlight {
+    fn default() -> Self {
+        Self {
+            color: Color::BLACK,
+            materials: Vec::new(),
         }
-        None
+    }
+}
+
+/// Buttons' [`ButtonState`] by name.
+#[derive(SystemParam)]
+pub struct ButtonStates<'w, 's> {
+    buttons: Query<'w, 's, (&'static Name, &'static ButtonState)>,
+}
+
+impl<'w, 's> ButtonStates<'w, 's> {
+    /// Whether any button matching `query` is hovered.
+    pub fn is_hovered(&self, query: NameQuery) -> bool {
+        self.buttons
+            .iter()
+            .filter_name(query)
+            .any(|(_, state)| state.hovered)
     }
 }
 
 spawn_from_scene!(button, Button, |cmds, _entity, _button| {
-    cmds.insert(Collider::cuboid(1.0, 1.0, 1.0)).insert(Sensor);
+    insert_button_sensor(cmds);
+    Ok(())
 });
 
+/// The sensor [`interaction_ray`] hits, a cube sized by the entity's scale.
+pub(super) fn insert_button_sensor(cmds: &mut EntityCommands) {
+    cmds.insert(Collider::cuboid(1.0, 1.0, 1.0))
+        .insert(Sensor)
+        .insert(ButtonState::default())
+        .insert(ButtonHighlight::default());
+}
+
 pub(super) fn button_interact_events(
     player_camera: Query<&Transform, With<PlayerCamera>>,
     buttons: Query<(Option<&Name>, &Button)>,
+    mut states: Query<(Entity, &mut ButtonState)>,
     mut button_press_events: EventWriter<ButtonEvent>,
     physics_context: Res<RapierContext>,
     mut named_button_statuses: ResMut<NamedButtonStatuses>,
-    mouse_button: Res<Input<MouseButton>>,
+    actions: Res<Input<Action>>,
 ) {
     let mut new_named_button_statuses = HashMap::new();
+    let mut hovered = None;
 
     for transform in player_camera.iter() {
-        let max_dist = 2.0;
-
-        let ray = physics_context.cast_ray(
-            transform.translation,
-            transform.forward(),
-            max_dist,
-            false,
+        let ray = interaction_ray(
+            transform,
+            &physics_context,
             QueryFilter::default().exclude_solids(), // Only interact with sensors
         );
         if let Some((entity, _)) = ray {
             if let Ok((name, button)) = buttons.get(entity) {
                 if button.enabled {
+                    hovered = Some(entity);
                     let name = name.map(|name| name.to_string());
                     debug!(name = ?name, "Button pressed");
                     let event = ButtonEvent {
                         name: name.clone(),
-                        pressed: mouse_button.just_pressed(MouseButton::Left),
+                        pressed: actions.just_pressed(Action::Interact),
                         hovered: true,
                         entity,
                     };
                     button_press_events.send(event.clone());
-                    if let Some(name) = name {
-                        new_named_button_statuses.insert(name, event);
+                    if name.is_some() {
+                        new_named_button_statuses.insert(entity, event);
                     }
                 }
             }
@@ -85,8 +212,8 @@ pub(super) fn button_interact_events(
     }
 
     // Retain buttons that are loosing hover, but were hovered last frame
-    named_button_statuses.0.retain(|name, event| {
-        if new_named_button_statuses.get(name).is_none() {
+    named_button_statuses.0.retain(|entity, event| {
+        if new_named_button_statuses.get(entity).is_none() {
             if event.hovered {
                 event.hovered = false;
                 true
@@ -99,4 +226,119 @@ pub(super) fn button_interact_events(
     });
 
     named_button_statuses.0.extend(new_named_button_statuses);
+
+    for (entity, mut state) in &mut states {
+        let is_hovered = hovered == Some(entity);
+        // Only write on changes, so `Changed<ButtonState>` means something
+        if state.hovered != is_hovered {
+            state.hovered = is_hovered;
+        }
+    }
+}
+
+/// Find the materials of buttons' meshes as buttons and materials are added, for
+/// [`highlight_buttons`].
+pub(super) fn link_button_materials(
+    mut buttons: Query<(Entity, Option<&Name>, &Button, &mut ButtonHighlight)>,
+    new_buttons: Query<(), Added<ButtonHighlight>>,
+    meshes: Query<(&Name, &Handle<GeneralMaterial>)>,
+    new_meshes: Query<(&Name, &Handle<GeneralMaterial>), Added<Handle<GeneralMaterial>>>,
+) {
+    if new_buttons.is_empty() && new_meshes.is_empty() {
+        return;
+    }
+
+    for (entity, name, button, mut highlight) in &mut buttons {
+        let id = name.map(|name| EntityName::parse(name).id);
+        let mut link = |mesh_name: &str, mat_h: &Handle<GeneralMaterial>| {
+            if button.highlights(id, mesh_name) && !highlight.materials.contains(mat_h) {
+                highlight.materials.push(mat_h.clone());
+            }
+        };
+        // A new button looks through every mesh, the others only through new ones
+        if new_buttons.get(entity).is_ok() {
+            for (mesh_name, mat_h) in &meshes {
+                link(mesh_name, mat_h);
+            }
+        } else {
+            for (mesh_name, mat_h) in &new_meshes {
+                link(mesh_name, mat_h);
+            }
+        }
+    }
+}
+
+/// Set the highlight of buttons' meshes when they're hovered or their highlight changes.
+pub(super) fn highlight_buttons(
+    buttons: Query<
+        (&ButtonState, &ButtonHighlight),
+        Or<(Changed<ButtonState>, Changed<ButtonHighlight>)>,
+    >,
+    mut general_mats: ResMut<Assets<GeneralMaterial>>,
+) {
+    for (state, highlight) in &buttons {
+        let color = if state.hovered {
+            HOVER_HIGHLIGHT
+        } else {
+            highlight.color
+        };
+        for mat_h in &highlight.materials {
+            // Only write on changes, so the material isn't re-uploaded
+            if general_mats
+                .get(mat_h)
+                .map_or(false, |mat| mat.highlight != color)
+            {
+                if let Some(mut mat) = general_mats.get_mut(mat_h) {
+                    mat.highlight = color;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{levels::Level, test_harness::TestGame};
+
+    #[test]
+    fn hovering_highlights_and_prompts() {
+        let mut game = TestGame::new();
+        game.load_level(Level::Level2Lobby);
+        let button = game.find("BUTTON Garage Key").unwrap();
+
+        let position = game.position_of("BUTTON Garage Key");
+        game.set_camera(position);
+        game.run_frames(2);
+        let world = &mut game.app.world;
+        let state = *world.get::<ButtonState>(button).unwrap();
+        assert!(state.hovered && state.locked);
+        assert_eq!(
+            world.get::<Button>(button).unwrap().prompt(&state),
+            Some("Needs key")
+        );
+        let highlights: Vec<Color> = world
+            .query::<(&Name, &Handle<GeneralMaterial>)>()
+            .iter(world)
+            .filter_name_contains("Garage Key Cyl")
+            .map(|(_, mat_h)| {
+                world
+                    .resource::<Assets<GeneralMaterial>>()
+                    .get(mat_h)
+                    .unwrap()
+                    .highlight
+            })
+            .collect();
+        assert!(!highlights.is_empty());
+        assert!(highlights.iter().all(|color| *color == HOVER_HIGHLIGHT));
+
+        game.select_item("key");
+        game.update();
+        let state = *game.app.world.get::<ButtonState>(button).unwrap();
+        assert!(!state.locked);
+        assert_eq!(
+            game.app.world.get::<Button>(button).unwrap().prompt(&state),
+            Some("Open gate")
+        );
+    }
 }
@@ -2,6 +2,7 @@ use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::assets::get_verts_indices;
+use crate::entity::error::EntityError;
 use crate::spawn_from_scene;
 
 /// A physics collider.
@@ -13,16 +14,28 @@ spawn_from_scene!(
     collider,
     Collider,
     |_cmds, entity, _collider, world: &World, cmds: bevy::prelude::Commands| {
+        let meshes = world.resource::<Assets<Mesh>>();
+        // Keep going so the rest of the meshes still collide
+        let mut result = Ok(());
         if let Some(children) = entity.get::<Children>() {
             for child in children.iter() {
                 let child = world.entity(*child);
                 if let Some(mesh) = child.get::<Handle<Mesh>>() {
-                    let meshes = world.get_resource::<Assets<Mesh>>().unwrap();
-                    let (vertices, indices) = get_verts_indices(meshes.get(mesh).unwrap());
-                    cmds.entity(child.id())
-                        .insert(bevy_rapier3d::prelude::Collider::trimesh(vertices, indices));
+                    match meshes
+                        .get(mesh)
+                        .ok_or(EntityError::MissingMesh)
+                        .and_then(|mesh| Ok(get_verts_indices(mesh)?))
+                    {
+                        Ok((vertices, indices)) => {
+                            cmds.entity(child.id()).insert(
+                                bevy_rapier3d::prelude::Collider::trimesh(vertices, indices),
+                            );
+                        }
+                        Err(err) => result = Err(err),
+                    }
                 }
             }
         }
+        result
     }
 );
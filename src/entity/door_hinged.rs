@@ -0,0 +1,189 @@
+//! Doors which turn about their origin, such as swing doors, tilting gates and knobs.
+//!
+//! Put the object's origin on the hinge in Blender. The door turns `angle` degrees about `axis`,
+//! which is relative to the closed door:
+//!
+//! ```text
+//! COLLIDER DOOR_HINGED Office Door {"axis": [0.0, 1.0, 0.0], "angle": -90.0, "easing": "EaseInOut"}
+//! ```
+//!
+//! Hinged doors share [`DoorState`], [`WhenBlocked`] and the door events with
+//! [`DoorLinear`](super::door_linear::DoorLinear).
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_fps_controller::controller::TIMESTEP;
+use bevy_rapier3d::prelude::RigidBody;
+use serde::{Deserialize, Serialize};
+
+use crate::{entity::error::EntityError, spawn_from_scene};
+
+use super::{
+    door_linear::{
+        step_door_state, DoorBlockedEvent, DoorFullyClosedEvent, DoorFullyOpenedEvent,
+        DoorObstructions, DoorState, TweenResult, WhenBlocked,
+    },
+    tween::Easing,
+};
+
+#[derive(Clone, Copy, Debug, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+#[serde(default)]
+pub struct DoorHinged {
+    /// Fraction of the way it turns per second
+    pub speed: f32,
+    /// Axis it turns about, relative to the closed door
+    pub axis: [f32; 3],
+    /// Degrees it turns to open, negative to turn the other way
+    pub angle: f32,
+    pub easing: Easing,
+    /// Whether it plays the door sound as it opens, off for knobs and such
+    pub sound: bool,
+    pub when_blocked: WhenBlocked,
+    pub origin: Quat,
+    pub state: DoorState,
+}
+
+impl Default for DoorHinged {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            axis: [0.0, 1.0, 0.0],
+            angle: 90.0,
+            easing: Easing::default(),
+            sound: true,
+            when_blocked: WhenBlocked::default(),
+            origin: Quat::IDENTITY,
+            state: DoorState::default(),
+        }
+    }
+}
+
+impl DoorHinged {
+    /// The door's rotation when `openness` of the way open, from 0 for closed to 1 for open.
+    pub fn rotation(&self, openness: f32) -> Quat {
+        let axis = Vec3::from(self.axis).try_normalize().unwrap_or(Vec3::Y);
+        let angle = self.angle.to_radians() * self.easing.apply(openness);
+        self.origin * Quat::from_axis_angle(axis, angle)
+    }
+}
+
+spawn_from_scene!(door_hinged, DoorHinged, |cmds, entity, door_hinged| {
+    let trans = entity
+        .get::<Transform>()
+        .ok_or(EntityError::MissingTransform)?;
+    door_hinged.origin = trans.rotation;
+    // Moved by its transform, this lets rapier push the player and the controller carry them
+    cmds.insert(RigidBody::KinematicPositionBased);
+    Ok(())
+});
+
+/// Runs each controller tick, like [`super::door_linear::update_door`].
+pub(super) fn update_hinged_doors(
+    mut doors: ParamSet<(
+        Query<(Entity, &mut Transform, &mut DoorHinged, Option<&Name>)>,
+        DoorObstructions,
+    )>,
+    mut blocked: Local<HashSet<Entity>>,
+    mut door_fully_closed_events: EventWriter<DoorFullyClosedEvent>,
+    mut door_fully_opened_events: EventWriter<DoorFullyOpenedEvent>,
+    mut door_blocked_events: EventWriter<DoorBlockedEvent>,
+) {
+    // Where each door turns to, found before checking what's in the way as that reads the
+    // doors' colliders
+    let moves: Vec<_> = doors
+        .p0()
+        .iter()
+        .map(|(entity, trans, door, _)| {
+            let mut state = door.state;
+            let (result, _) = step_door_state(TIMESTEP as f32, door.speed, &mut state);
+            let moved = Transform {
+                rotation: door.rotation(state.openness()),
+                ..*trans
+            };
+            (entity, state, result, *trans, moved)
+        })
+        .collect();
+
+    for (entity, state, result, from, moved) in moves {
+        let idle = matches!(result, TweenResult::Idle);
+        let blocker = if idle {
+            None
+        } else {
+            doors.p1().find(entity, &from, &moved)
+        };
+        let mut query = doors.p0();
+        let (_, mut trans, mut door, name) = match query.get_mut(entity) {
+            Ok(door) => door,
+            Err(_) => continue,
+        };
+        if idle {
+            // Keeps the door in place when its state is set directly, such as when loading
+            if trans.rotation != moved.rotation {
+                trans.rotation = moved.rotation;
+            }
+            continue;
+        }
+
+        match blocker {
+            Some(blocker) => {
+                if blocked.insert(entity) {
+                    debug!(?name, ?blocker, "Hinged door blocked");
+                    door_blocked_events.send(DoorBlockedEvent {
+                        name: name.map(|name| name.to_string()),
+                        entity,
+                        blocker,
+                    });
+                }
+                let when_blocked = door.when_blocked;
+                if !when_blocked.apply(&mut door.state) {
+                    continue;
+                }
+            }
+            None => {
+                blocked.remove(&entity);
+            }
+        }
+
+        door.state = state;
+        trans.rotation = moved.rotation;
+        match result {
+            TweenResult::JustClosed => {
+                door_fully_closed_events.send(DoorFullyClosedEvent {
+                    name: name.map(|name| name.to_string()),
+                    entity,
+                });
+            }
+            TweenResult::JustOpened => {
+                door_fully_opened_events.send(DoorFullyOpenedEvent {
+                    name: name.map(|name| name.to_string()),
+                    entity,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_about_its_axis() {
+        let door = DoorHinged {
+            axis: [0.0, 2.0, 0.0],
+            angle: 90.0,
+            easing: Easing::EaseInOut,
+            ..default()
+        };
+        assert!(door.rotation(0.0).abs_diff_eq(Quat::IDENTITY, 1e-6));
+        let open = door.rotation(1.0) * Vec3::X;
+        assert!(open.abs_diff_eq(-Vec3::Z, 1e-6), "{open}");
+        // Eased halfway is still halfway
+        let half = door.rotation(0.5) * Vec3::X;
+        assert!(
+            half.abs_diff_eq(Vec3::new(1.0, 0.0, -1.0).normalize(), 1e-6),
+            "{half}"
+        );
+    }
+}
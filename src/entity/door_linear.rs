@@ -1,12 +1,22 @@
 use bevy::{
+    ecs::system::SystemParam,
     prelude::*,
     utils::{HashMap, HashSet},
 };
+use bevy_fps_controller::controller::{LogicalPlayer, TIMESTEP};
 use bevy_kira_audio::{AudioControl, DynamicAudioChannels};
+use bevy_rapier3d::prelude::{Collider, QueryFilter, RapierContext, RigidBody};
 use interpolation::lerp;
 use serde::{Deserialize, Serialize};
 
-use crate::{assets::SoundAssets, audio::AudioComponent, spawn_from_scene};
+use crate::{
+    assets::SoundAssets,
+    audio::AudioComponent,
+    entity::{
+        door_hinged::DoorHinged, error::EntityError, name::NameQuery, tween::Easing, NamedIterator,
+    },
+    spawn_from_scene,
+};
 
 pub struct DoorFullyClosedEvent {
     pub name: Option<String>,
@@ -18,14 +28,124 @@ pub struct DoorFullyOpenedEvent {
     pub entity: Entity,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Something got in a moving door's way, sent again only once it's been clear.
+pub struct DoorBlockedEvent {
+    pub name: Option<String>,
+    pub entity: Entity,
+    pub blocker: Entity,
+}
+
+#[derive(Clone, Debug)]
 pub struct NamedDoorStatus {
+    pub name: String,
     pub entity: Entity,
     pub is_open: bool,
 }
 
+/// Named doors which finished opening or closing this frame, see [`DoorStates`] for doors'
+/// current state.
 #[derive(Default)]
-pub struct NamedDoorStatuses(HashMap<String, NamedDoorStatus>);
+pub struct NamedDoorStatuses(HashMap<Entity, NamedDoorStatus>);
+
+impl NamedDoorStatuses {
+    /// The first door in name order matching `query`.
+    pub fn find(&self, query: NameQuery) -> Option<NamedDoorStatus> {
+        query
+            .first_in(self.named())
+            .map(|(_, status)| status.clone())
+    }
+
+    /// Every door matching `query`, sorted by name.
+    pub fn all(&self, query: NameQuery) -> Vec<(&str, &NamedDoorStatus)> {
+        query.all_in(self.named())
+    }
+
+    fn named(&self) -> impl Iterator<Item = (&str, &NamedDoorStatus)> {
+        self.0.values().map(|status| (status.name.as_str(), status))
+    }
+}
+
+/// Doors' [`DoorState`] by name, whichever kind of door they are.
+#[derive(SystemParam)]
+pub struct DoorStates<'w, 's> {
+    linear: Query<'w, 's, (&'static Name, &'static DoorLinear)>,
+    hinged: Query<'w, 's, (&'static Name, &'static DoorHinged)>,
+}
+
+impl<'w, 's> DoorStates<'w, 's> {
+    /// The state of the first door in name order matching `query`.
+    pub fn find(&self, query: NameQuery) -> Option<DoorState> {
+        query.first_in(self.states()).map(|(_, state)| state)
+    }
+
+    /// Whether there are doors matching `query` and they're all fully closed.
+    pub fn all_closed(&self, query: NameQuery) -> bool {
+        all_closed(self.states(), query)
+    }
+
+    /// Whether any door matching `query` is fully open.
+    pub fn any_open(&self, query: NameQuery) -> bool {
+        self.states()
+            .any(|(name, state)| query.matches(name) && state.is_open())
+    }
+
+    fn states(&self) -> impl Iterator<Item = (&str, DoorState)> {
+        let linear = self
+            .linear
+            .iter()
+            .map(|(name, door)| (name.as_str(), door.state));
+        let hinged = self
+            .hinged
+            .iter()
+            .map(|(name, door)| (name.as_str(), door.state));
+        linear.chain(hinged)
+    }
+}
+
+/// Open and close doors by name, whichever kind of door they are.
+#[derive(SystemParam)]
+pub struct DoorControls<'w, 's> {
+    linear: Query<'w, 's, (&'static Name, &'static mut DoorLinear)>,
+    hinged: Query<'w, 's, (&'static Name, &'static mut DoorHinged)>,
+}
+
+impl<'w, 's> DoorControls<'w, 's> {
+    /// The state of every door matching `query`.
+    pub fn states_mut<'a>(
+        &'a mut self,
+        query: NameQuery<'a>,
+    ) -> impl Iterator<Item = &'a mut DoorState> + 'a {
+        let linear = self
+            .linear
+            .iter_mut()
+            .filter_name(query)
+            .map(|(_, door)| &mut door.into_inner().state);
+        let hinged = self
+            .hinged
+            .iter_mut()
+            .filter_name(query)
+            .map(|(_, door)| &mut door.into_inner().state);
+        linear.chain(hinged)
+    }
+
+    /// Whether there are doors matching `query` and they're all fully closed.
+    pub fn all_closed(&self, query: NameQuery) -> bool {
+        let linear = self
+            .linear
+            .iter()
+            .map(|(name, door)| (name.as_str(), door.state));
+        let hinged = self
+            .hinged
+            .iter()
+            .map(|(name, door)| (name.as_str(), door.state));
+        all_closed(linear.chain(hinged), query)
+    }
+}
+
+fn all_closed<'a>(states: impl Iterator<Item = (&'a str, DoorState)>, query: NameQuery) -> bool {
+    let mut doors = states.filter(|(name, _)| query.matches(name)).peekable();
+    doors.peek().is_some() && doors.all(|(_, state)| state.is_closed())
+}
 
 /// A door which moves linearly based on [`move_to`].
 #[derive(Clone, Copy, Debug, Default, Component, Reflect, Serialize, Deserialize)]
@@ -34,10 +154,110 @@ pub struct NamedDoorStatuses(HashMap<String, NamedDoorStatus>);
 pub struct DoorLinear {
     pub speed: f32,
     pub move_to: [f32; 3],
+    pub easing: Easing,
+    pub when_blocked: WhenBlocked,
     pub origin: Vec3,
     pub state: DoorState,
 }
 
+impl DoorLinear {
+    /// Where the door is when `openness` of the way open, from 0 for closed to 1 for open.
+    pub fn translation(&self, openness: f32, trans: &Transform) -> Vec3 {
+        let progress = self.easing.apply(openness);
+        let local_dest = self.origin
+            + trans
+                .compute_matrix()
+                .transform_vector3(self.move_to.into());
+        lerp::<[f32; 3]>(&self.origin.into(), &local_dest.into(), &progress).into()
+    }
+}
+
+/// What a door does when the player or something loose is in its way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum WhenBlocked {
+    /// Opens again if it was closing, waits if it was opening
+    Reverse,
+    /// Stops until the way is clear
+    Wait,
+    /// Carries on, pushing whatever is in the way
+    Push,
+}
+
+impl WhenBlocked {
+    /// Change a blocked door's `state`, returns whether it still moves.
+    pub fn apply(self, state: &mut DoorState) -> bool {
+        match self {
+            WhenBlocked::Reverse => {
+                if state.is_closing() {
+                    state.open();
+                }
+                false
+            }
+            WhenBlocked::Wait => false,
+            WhenBlocked::Push => true,
+        }
+    }
+}
+
+impl Default for WhenBlocked {
+    fn default() -> Self {
+        WhenBlocked::Reverse
+    }
+}
+
+/// Finds what's in a moving door's way, which is the player or any dynamic body.
+#[derive(SystemParam)]
+pub struct DoorObstructions<'w, 's> {
+    physics_context: Res<'w, RapierContext>,
+    player: Query<'w, 's, Entity, With<LogicalPlayer>>,
+    bodies: Query<'w, 's, &'static RigidBody>,
+    hierarchy: Query<'w, 's, (Option<&'static Parent>, Option<&'static Children>)>,
+    parents: Query<'w, 's, &'static GlobalTransform>,
+    colliders: Query<'w, 's, (&'static Transform, &'static Collider)>,
+}
+
+impl<'w, 's> DoorObstructions<'w, 's> {
+    /// What `door` moving from `from` to `to` would hit. Its colliders turn to their new
+    /// rotation, then sweep from where they are to where they'd be.
+    ///
+    /// This reads the transforms of the door's children, which may be doors themselves, so
+    /// door systems take it in a [`ParamSet`] with their door query.
+    pub fn find(&self, door: Entity, from: &Transform, to: &Transform) -> Option<Entity> {
+        let (parent, children) = self.hierarchy.get(door).ok()?;
+        let player = self.player.get_single().ok();
+        let blocks = |hit: Entity| {
+            Some(hit) == player || matches!(self.bodies.get(hit), Ok(RigidBody::Dynamic))
+        };
+        let filter = QueryFilter::default().exclude_sensors().predicate(&blocks);
+        let parent = parent
+            .and_then(|parent| self.parents.get(parent.get()).ok())
+            .copied()
+            .unwrap_or_default();
+        let from = parent.mul_transform(*from);
+        let to = parent.mul_transform(*to);
+
+        // The door's colliders are on its mesh children, see `entity::collider`
+        children
+            .into_iter()
+            .flat_map(|children| children.iter())
+            .filter_map(|child| self.colliders.get(*child).ok())
+            .find_map(|(child_trans, collider)| {
+                let start = from.mul_transform(*child_trans).compute_transform();
+                let end = to.mul_transform(*child_trans).compute_transform();
+                self.physics_context
+                    .cast_shape(
+                        start.translation,
+                        end.rotation,
+                        end.translation - start.translation,
+                        collider,
+                        1.0,
+                        filter,
+                    )
+                    .map(|(hit, _)| hit)
+            })
+    }
+}
+
 #[derive(Clone, Copy, Debug, Reflect, Serialize, Deserialize)]
 pub enum DoorState {
     FullyOpen,
@@ -99,6 +319,16 @@ impl DoorState {
     pub fn is_closing(&self) -> bool {
         matches!(self, DoorState::Closing(_))
     }
+
+    /// How far open the door is, from 0 for closed to 1 for open.
+    pub fn openness(&self) -> f32 {
+        match *self {
+            DoorState::FullyOpen => 1.0,
+            DoorState::FullyClosed => 0.0,
+            DoorState::Opening(progress) => progress,
+            DoorState::Closing(progress) => 1.0 - progress,
+        }
+    }
 }
 
 impl Default for DoorState {
@@ -107,20 +337,33 @@ impl Default for DoorState {
     }
 }
 
-spawn_from_scene!(door_linear, DoorLinear, |_cmds, entity, door_linear| {
-    let trans = entity.get::<Transform>().unwrap();
+spawn_from_scene!(door_linear, DoorLinear, |cmds, entity, door_linear| {
+    let trans = entity
+        .get::<Transform>()
+        .ok_or(EntityError::MissingTransform)?;
     door_linear.origin = trans.translation;
+    // Moved by its transform, this lets rapier push the player and the controller carry them
+    cmds.insert(RigidBody::KinematicPositionBased);
+    Ok(())
 });
 
 pub(super) fn door_sounds(
     mut cmds: Commands,
-    doors: Query<(Entity, &DoorLinear, Option<&AudioComponent>), Changed<DoorLinear>>,
+    linear: Query<(Entity, &DoorLinear, Option<&AudioComponent>), Changed<DoorLinear>>,
+    hinged: Query<(Entity, &DoorHinged, Option<&AudioComponent>), Changed<DoorHinged>>,
     mut open_doors: Local<HashSet<Entity>>,
     sound_assets: Res<SoundAssets>,
     mut channels: ResMut<DynamicAudioChannels>,
 ) {
-    for (entity, door, audio_comp) in doors.iter() {
-        if door.state.is_opening() {
+    let linear = linear
+        .iter()
+        .map(|(entity, door, audio_comp)| (entity, door.state, audio_comp));
+    let hinged = hinged
+        .iter()
+        .filter(|(_, door, _)| door.sound)
+        .map(|(entity, door, audio_comp)| (entity, door.state, audio_comp));
+    for (entity, state, audio_comp) in linear.chain(hinged) {
+        if state.is_opening() {
             if open_doors.insert(entity) {
                 AudioComponent::get_or_insert(&mut cmds, entity, audio_comp, &mut channels)
                     .channel(&channels)
@@ -132,74 +375,117 @@ pub(super) fn door_sounds(
     }
 }
 
+/// Runs each controller tick so the player riding a door moves in step with it.
 pub(super) fn update_door(
-    time: Res<Time>,
-    mut doors: Query<(Entity, &mut Transform, &mut DoorLinear, Option<&Name>)>,
+    mut doors: ParamSet<(
+        Query<(Entity, &mut Transform, &mut DoorLinear, Option<&Name>)>,
+        DoorObstructions,
+    )>,
+    mut blocked: Local<HashSet<Entity>>,
     mut door_fully_closed_events: EventWriter<DoorFullyClosedEvent>,
     mut door_fully_opened_events: EventWriter<DoorFullyOpenedEvent>,
-    mut named_door_statuses: ResMut<NamedDoorStatuses>,
+    mut door_blocked_events: EventWriter<DoorBlockedEvent>,
 ) {
-    named_door_statuses.0 = HashMap::new();
+    // Where each door moves to, found before checking what's in the way as that reads the
+    // doors' colliders
+    let moves: Vec<_> = doors
+        .p0()
+        .iter()
+        .filter_map(|(entity, trans, door, _)| {
+            let mut state = door.state;
+            let (result, openness) = step_door_state(TIMESTEP as f32, door.speed, &mut state);
+            let moved = Transform {
+                translation: door.translation(openness?, trans),
+                ..*trans
+            };
+            Some((entity, state, result, *trans, moved))
+        })
+        .collect();
+
+    for (entity, state, result, from, moved) in moves {
+        let blocker = doors.p1().find(entity, &from, &moved);
+        let mut query = doors.p0();
+        let (_, mut trans, mut door, name) = match query.get_mut(entity) {
+            Ok(door) => door,
+            Err(_) => continue,
+        };
 
-    for (entity, mut trans, mut door, name) in doors.iter_mut() {
-        // TODO: player can get stuck in doors.
-        // Workaround is to make sure trigger to open doors overlaps doors.
-        let tween_result = tween_transform(
-            time.delta_seconds(),
-            door.speed,
-            door.origin,
-            door.move_to.into(),
-            &mut door.state,
-            &mut trans,
-        );
-        match tween_result {
+        match blocker {
+            Some(blocker) => {
+                if blocked.insert(entity) {
+                    debug!(?name, ?blocker, "Door blocked");
+                    door_blocked_events.send(DoorBlockedEvent {
+                        name: name.map(|name| name.to_string()),
+                        entity,
+                        blocker,
+                    });
+                }
+                let when_blocked = door.when_blocked;
+                if !when_blocked.apply(&mut door.state) {
+                    continue;
+                }
+            }
+            None => {
+                blocked.remove(&entity);
+            }
+        }
+
+        door.state = state;
+        trans.translation = moved.translation;
+        match result {
             TweenResult::JustClosed => {
                 door_fully_closed_events.send(DoorFullyClosedEvent {
                     name: name.map(|name| name.to_string()),
                     entity,
                 });
-                if let Some(name) = name {
-                    named_door_statuses.0.insert(
-                        name.to_string(),
-                        NamedDoorStatus {
-                            entity,
-                            is_open: false,
-                        },
-                    );
-                }
             }
             TweenResult::JustOpened => {
                 door_fully_opened_events.send(DoorFullyOpenedEvent {
                     name: name.map(|name| name.to_string()),
                     entity,
                 });
-                if let Some(name) = name {
-                    named_door_statuses.0.insert(
-                        name.to_string(),
-                        NamedDoorStatus {
-                            entity,
-                            is_open: true,
-                        },
-                    );
-                }
             }
             _ => {}
         }
     }
 }
 
-// TODO move elsewhere
-// Note: this teleports the transform,
-// can result in things getting stuck
-pub fn tween_transform(
+/// Fill [`NamedDoorStatuses`] from the events of every kind of door.
+pub(super) fn collect_named_door_statuses(
+    mut door_fully_closed_events: EventReader<DoorFullyClosedEvent>,
+    mut door_fully_opened_events: EventReader<DoorFullyOpenedEvent>,
+    mut named_door_statuses: ResMut<NamedDoorStatuses>,
+) {
+    named_door_statuses.0 = HashMap::new();
+
+    let closed = door_fully_closed_events
+        .iter()
+        .map(|event| (&event.name, event.entity, false));
+    let opened = door_fully_opened_events
+        .iter()
+        .map(|event| (&event.name, event.entity, true));
+    for (name, entity, is_open) in closed.chain(opened) {
+        if let Some(name) = name {
+            named_door_statuses.0.insert(
+                entity,
+                NamedDoorStatus {
+                    name: name.clone(),
+                    entity,
+                    is_open,
+                },
+            );
+        }
+    }
+}
+
+/// Move a door's state along at `speed`, returning how far open it is now if it moved, from 0
+/// for closed to 1 for open.
+pub fn step_door_state(
     delta_s: f32,
     speed: f32,
-    origin: Vec3,
-    destination: Vec3,
     state: &mut DoorState,
-    trans: &mut Transform,
-) -> TweenResult {
-    let (result, progress) = match *state {
+) -> (TweenResult, Option<f32>) {
+    match *state {
         DoorState::Opening(mut progress) => {
             progress = f32::min(progress + speed * delta_s, 1.0);
             if progress >= 1.0 {
@@ -221,14 +507,7 @@ pub fn tween_transform(
             }
         }
         _ => (TweenResult::Idle, None),
-    };
-
-    if let Some(progress) = progress {
-        let local_dest = origin + trans.compute_matrix().transform_vector3(destination);
-        trans.translation = lerp::<[f32; 3]>(&origin.into(), &local_dest.into(), &progress).into();
     }
-
-    result
 }
 
 pub enum TweenResult {
@@ -237,3 +516,71 @@ pub enum TweenResult {
     JustOpened,
     Progress,
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::{Events, ManualEventReader};
+
+    use crate::test_harness::TestGame;
+
+    use super::*;
+
+    #[test]
+    fn blocked_doors_reverse_only_when_closing() {
+        let mut closing = DoorState::Closing(0.4);
+        assert!(!WhenBlocked::Reverse.apply(&mut closing));
+        assert!(matches!(closing, DoorState::Opening(progress) if (progress - 0.6).abs() < 1e-6));
+
+        let mut opening = DoorState::Opening(0.4);
+        assert!(!WhenBlocked::Reverse.apply(&mut opening));
+        assert!(!WhenBlocked::Wait.apply(&mut opening));
+        assert!(matches!(opening, DoorState::Opening(progress) if (progress - 0.4).abs() < 1e-6));
+
+        assert!(WhenBlocked::Push.apply(&mut opening));
+    }
+
+    #[test]
+    fn elevator_doors_reopen_on_the_player() {
+        let mut game = TestGame::new();
+        game.teleport_into("TRIGGER Elevator Outside");
+        let opened = game.run_until(600, |world| {
+            world
+                .query::<(&Name, &DoorLinear)>()
+                .iter(world)
+                .filter_name_contains("Elevator Door")
+                .all(|(_, door)| door.state.is_open())
+        });
+        assert!(opened, "the doors didn't open");
+
+        // Stand between the doors and close them without the logic graph knowing
+        let left = game.position_of("DOOR_LINEAR Left Elevator Door");
+        let right = game.position_of("DOOR_LINEAR Right Elevator Door");
+        let doorway = (left + right) / 2.0;
+        let floor = game.position_of("TRIGGER Elevator Inside Near Door").y - 0.35;
+        game.teleport(Vec3::new(doorway.x, floor, doorway.z));
+        game.run_frames(5);
+        for (_, mut door) in game
+            .app
+            .world
+            .query::<(&Name, &mut DoorLinear)>()
+            .iter_mut(&mut game.app.world)
+            .filter_name_contains("Elevator Door")
+        {
+            door.state.close();
+        }
+
+        let mut reader = ManualEventReader::<DoorBlockedEvent>::default();
+        let mut blocked = None;
+        game.run_until(300, |world| {
+            blocked = reader
+                .iter(world.resource::<Events<DoorBlockedEvent>>())
+                .map(|event| (event.entity, event.blocker))
+                .next();
+            blocked.is_some()
+        });
+        let (door, blocker) = blocked.expect("the doors weren't blocked");
+        assert_eq!(blocker, game.player);
+        let state = game.app.world.get::<DoorLinear>(door).unwrap().state;
+        assert!(state.is_opening(), "{state:?}");
+    }
+}
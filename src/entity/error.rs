@@ -0,0 +1,109 @@
+//! Problems setting up entities from scenes.
+//!
+//! One bad export shouldn't crash the game, so the entity falls back to defaults and the problem
+//! is logged with the entity's name. Debug builds also list them on screen, see
+//! [`crate::diagnostics`].
+
+use std::fmt;
+
+use bevy::{ecs::system::Command, prelude::*};
+use serde::de::DeserializeOwned;
+
+use crate::assets::MeshError;
+
+/// Why an entity from a scene couldn't be set up as exported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntityError {
+    /// The glTF extras don't parse as the entity's properties
+    InvalidProperties {
+        /// Property the error is in, `.` for the properties as a whole
+        path: String,
+        message: String,
+    },
+    MissingTransform,
+    MissingMesh,
+    InvalidMesh(MeshError),
+}
+
+impl fmt::Display for EntityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityError::InvalidProperties { path, message } if path == "." => {
+                write!(f, "invalid properties: {message}")
+            }
+            EntityError::InvalidProperties { path, message } => {
+                write!(f, "invalid property `{path}`: {message}")
+            }
+            EntityError::MissingTransform => write!(f, "no transform"),
+            EntityError::MissingMesh => write!(f, "mesh isn't loaded"),
+            EntityError::InvalidMesh(err) => write!(f, "invalid mesh: {err}"),
+        }
+    }
+}
+
+impl From<MeshError> for EntityError {
+    fn from(err: MeshError) -> Self {
+        EntityError::InvalidMesh(err)
+    }
+}
+
+/// Parse an entity's properties from the JSON in its [`GltfExtras`](bevy::gltf::GltfExtras).
+pub fn parse_properties<T: DeserializeOwned>(json: &str) -> Result<T, EntityError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    serde_path_to_error::deserialize(deserializer).map_err(|err| EntityError::InvalidProperties {
+        path: err.path().to_string(),
+        message: err.inner().to_string(),
+    })
+}
+
+/// Entities which couldn't be set up as exported, by name.
+#[derive(Default)]
+pub struct EntityErrors(Vec<(String, EntityError)>);
+
+impl EntityErrors {
+    pub fn report(&mut self, name: &str, error: EntityError) {
+        warn!(name, %error, "Failed to set up entity");
+        let report = (name.to_string(), error);
+        // Levels spawn again on each visit
+        if !self.0.contains(&report) {
+            self.0.push(report);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(String, EntityError)> {
+        self.0.iter()
+    }
+}
+
+/// Reports an error to [`EntityErrors`], for systems which can't borrow it such as those
+/// reading the whole [`World`].
+pub struct ReportEntityError {
+    pub name: String,
+    pub error: EntityError,
+}
+
+impl Command for ReportEntityError {
+    fn write(self, world: &mut World) {
+        world
+            .get_resource_or_insert_with(EntityErrors::default)
+            .report(&self.name, self.error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::door_linear::DoorLinear;
+
+    #[test]
+    fn invalid_property_has_its_path() {
+        let err = parse_properties::<DoorLinear>(r#"{"speed": 1.0, "move_to": [0.0, "up", 0.0]}"#)
+            .unwrap_err();
+
+        assert!(
+            matches!(&err, EntityError::InvalidProperties { path, .. } if path == "move_to[1]"),
+            "{err:?}"
+        );
+        assert!(err.to_string().starts_with("invalid property `move_to[1]`"));
+    }
+}
@@ -0,0 +1,193 @@
+//! Buttons and doors locked until the player uses the right item on them.
+//!
+//! Any entity whose extras have a `requires_item` is an [`ItemTarget`], whatever it's named.
+//! Pressing interact on one uses the [`SelectedItem`](crate::item::SelectedItem) and sends a
+//! [`UseItemSuccessEvent`] or [`UseItemFailureEvent`], with a sound and notification either way.
+//! Doors open by themselves, buttons leave the rest to level code and logic graphs:
+//!
+//! ```text
+//! BUTTON vending 1 {"requires_item": "money", "locked_text": "Insufficient\nfunds"}
+//! ```
+
+use bevy::{gltf::GltfExtras, prelude::*};
+use bevy_fps_controller::controller::LogicalPlayer;
+use bevy_kira_audio::{prelude::Audio, AudioControl};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::Action,
+    assets::SoundAssets,
+    item::{Items, SelectedItem},
+    notification::{notify, NotificationText, NOTIFICATION_DURATION},
+    scene_hook::SceneLoaded,
+    PlayerCamera,
+};
+
+use super::{
+    button::{interaction_ray, ButtonEvent, ButtonState},
+    door_hinged::DoorHinged,
+    door_linear::DoorLinear,
+    error::{parse_properties, ReportEntityError},
+};
+
+#[derive(Clone, Debug, Default, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct ItemTarget {
+    /// Id of the item to use, the target isn't locked if empty
+    pub requires_item: String,
+    /// Whether using the item takes it from the player
+    #[serde(default)]
+    pub consumes: bool,
+    /// Notification when the item isn't selected, otherwise says which item is needed
+    #[serde(default)]
+    pub locked_text: Option<String>,
+}
+
+/// The player used the required item on an [`ItemTarget`].
+pub struct UseItemSuccessEvent {
+    pub name: Option<String>,
+    pub entity: Entity,
+    pub item: String,
+}
+
+/// The player pressed an [`ItemTarget`] without its item selected.
+pub struct UseItemFailureEvent {
+    pub name: Option<String>,
+    pub entity: Entity,
+    pub required: String,
+    pub selected: Option<String>,
+}
+
+/// Add [`ItemTarget`] to entities whose extras have a `requires_item`.
+pub(super) fn spawn_item_target_from_scene(mut cmds: Commands, mut scene_loaded: SceneLoaded) {
+    for entity in scene_loaded.iter() {
+        let extras = match entity.get::<GltfExtras>() {
+            Some(extras) if extras.value.contains("requires_item") => extras,
+            _ => continue,
+        };
+        match parse_properties::<ItemTarget>(&extras.value) {
+            Ok(target) => {
+                debug!(id = ?entity.id(), item = %target.requires_item, "Registered item target");
+                cmds.entity(entity.id()).insert(target);
+            }
+            Err(error) => cmds.add(ReportEntityError {
+                name: entity
+                    .get::<Name>()
+                    .map_or_else(String::new, |name| name.to_string()),
+                error,
+            }),
+        }
+    }
+}
+
+/// Show item target buttons as [locked](ButtonState::locked) until their item is selected.
+pub(super) fn lock_item_targets(
+    mut targets: Query<(&ItemTarget, &mut ButtonState)>,
+    selected: Res<SelectedItem>,
+) {
+    for (target, mut state) in &mut targets {
+        let locked = !target.requires_item.is_empty()
+            && selected.0.as_deref() != Some(target.requires_item.as_str());
+        if state.locked != locked {
+            state.locked = locked;
+        }
+    }
+}
+
+pub(super) fn use_items_on_targets(
+    mut button_events: EventReader<ButtonEvent>,
+    actions: Res<Input<Action>>,
+    player: Query<Entity, With<LogicalPlayer>>,
+    player_camera: Query<&Transform, With<PlayerCamera>>,
+    physics_context: Res<RapierContext>,
+    parents: Query<&Parent>,
+    mut targets: Query<(
+        Option<&Name>,
+        &ItemTarget,
+        Option<&mut DoorLinear>,
+        Option<&mut DoorHinged>,
+    )>,
+    mut items: Items,
+    mut success_events: EventWriter<UseItemSuccessEvent>,
+    mut failure_events: EventWriter<UseItemFailureEvent>,
+    mut texts: Query<(&mut Text, &mut NotificationText)>,
+    audio: Res<Audio>,
+    sound_assets: Res<SoundAssets>,
+) {
+    let mut used: Vec<Entity> = button_events
+        .iter()
+        .filter(|event| event.pressed && targets.contains(event.entity))
+        .map(|event| event.entity)
+        .collect();
+
+    // Doors aren't buttons, look for one on the way to the walls
+    if actions.just_pressed(Action::Interact) {
+        if let Ok(camera) = player_camera.get_single() {
+            let mut filter = QueryFilter::default().exclude_sensors();
+            if let Ok(player) = player.get_single() {
+                filter = filter.exclude_rigid_body(player);
+            }
+            if let Some((hit, _)) = interaction_ray(camera, &physics_context, filter) {
+                // Door colliders are on their mesh children
+                let door = [Some(hit), parents.get(hit).ok().map(|parent| **parent)]
+                    .into_iter()
+                    .flatten()
+                    .find(|entity| {
+                        matches!(
+                            targets.get(*entity),
+                            Ok((_, _, Some(_), _) | (_, _, _, Some(_)))
+                        )
+                    });
+                used.extend(door);
+            }
+        }
+    }
+
+    for entity in used {
+        let (name, target, door_linear, door_hinged) = match targets.get_mut(entity) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        let door = door_linear
+            .map(|door| &mut door.into_inner().state)
+            .or_else(|| door_hinged.map(|door| &mut door.into_inner().state));
+        if target.requires_item.is_empty()
+            || door.as_ref().map_or(false, |state| !state.is_closed())
+        {
+            continue;
+        }
+        let name = name.map(|name| name.to_string());
+        let required = target.requires_item.clone();
+
+        if items.selected() == Some(required.as_str()) && items.use_item(&required, target.consumes)
+        {
+            debug!(?name, item = %required, "Item used on target");
+            audio.play(sound_assets.click.clone()).with_volume(0.3);
+            if let Some(state) = door {
+                state.open();
+            }
+            success_events.send(UseItemSuccessEvent {
+                name,
+                entity,
+                item: required,
+            });
+        } else {
+            debug!(?name, item = %required, selected = ?items.selected(), "Target needs an item");
+            audio.play(sound_assets.bad_click.clone()).with_volume(0.2);
+            let text = target.locked_text.clone().unwrap_or_else(|| {
+                let item = items
+                    .definition(&required)
+                    .map_or(required.as_str(), |definition| definition.name.as_str());
+                format!("Needs {item}")
+            });
+            notify(&mut texts, &text, NOTIFICATION_DURATION);
+            failure_events.send(UseItemFailureEvent {
+                name,
+                entity,
+                required,
+                selected: items.selected().map(str::to_string),
+            });
+        }
+    }
+}
@@ -0,0 +1,346 @@
+//! Matching entities by the names they're given in Blender.
+//!
+//! Names are made of uppercase kind prefixes which decide what the entity is, a logical id
+//! which level code and logic graphs refer to it by, and optional `#tags`:
+//!
+//! ```text
+//! COLLIDER DOOR_LINEAR Door 1 Left #lobby
+//! ^^^^^^^^^^^^^^^^^^^^ ^^^^^^^^^^^ ^^^^^^
+//! kinds                id          tags
+//! ```
+//!
+//! Blender's copy suffix such as `.001` isn't part of the id, so copies share it.
+
+use serde::Deserialize;
+
+/// A name split into its parts, see the [module docs](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntityName<'a> {
+    pub kinds: Vec<&'a str>,
+    pub id: &'a str,
+    pub tags: Vec<&'a str>,
+}
+
+impl<'a> EntityName<'a> {
+    pub fn parse(name: &'a str) -> Self {
+        let mut rest = strip_copy_suffix(name).trim();
+
+        let mut tags = Vec::new();
+        while let Some((head, last)) = rest.rsplit_once(' ') {
+            match last.strip_prefix('#') {
+                Some(tag) => {
+                    tags.push(tag);
+                    rest = head.trim_end();
+                }
+                None => break,
+            }
+        }
+        tags.reverse();
+
+        let mut kinds = Vec::new();
+        // The last word is the id even if it's uppercase, such as "BLOCK TV"
+        while let Some((word, tail)) = rest.split_once(' ') {
+            if !is_kind(word) {
+                break;
+            }
+            kinds.push(word);
+            rest = tail.trim_start();
+        }
+        if kinds.is_empty() && is_kind(rest) {
+            kinds.push(rest);
+            rest = "";
+        }
+
+        EntityName {
+            kinds,
+            id: rest,
+            tags,
+        }
+    }
+
+    pub fn has_kind(&self, kind: &str) -> bool {
+        self.kinds.contains(&kind)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(&tag)
+    }
+}
+
+fn is_kind(word: &str) -> bool {
+    word.len() > 1
+        && word.starts_with(|c: char| c.is_ascii_uppercase())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Remove Blender's `.001` style suffix for copies of an object.
+fn strip_copy_suffix(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((head, suffix)) if suffix.len() == 3 && suffix.chars().all(|c| c.is_ascii_digit()) => {
+            head
+        }
+        _ => name,
+    }
+}
+
+/// How a [`NameQuery`] compares its pattern against names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NameMatch {
+    /// The pattern is anywhere in the name, how entities have always been looked up
+    Contains,
+    /// The whole name, ignoring a copy suffix
+    Exact,
+    /// The start of the name
+    Prefix,
+    /// The whole name, `*` matches any run of characters and `?` any one character
+    Glob,
+    /// The pattern is parsed as an [`EntityName`], the name must have all of its kinds and
+    /// tags and the same id
+    Id,
+}
+
+/// A way of picking out entities by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NameQuery<'a> {
+    pub pattern: &'a str,
+    pub mode: NameMatch,
+}
+
+impl<'a> NameQuery<'a> {
+    pub const fn new(pattern: &'a str, mode: NameMatch) -> Self {
+        NameQuery { pattern, mode }
+    }
+
+    pub const fn contains(pattern: &'a str) -> Self {
+        Self::new(pattern, NameMatch::Contains)
+    }
+
+    pub const fn exact(pattern: &'a str) -> Self {
+        Self::new(pattern, NameMatch::Exact)
+    }
+
+    pub const fn prefix(pattern: &'a str) -> Self {
+        Self::new(pattern, NameMatch::Prefix)
+    }
+
+    pub const fn glob(pattern: &'a str) -> Self {
+        Self::new(pattern, NameMatch::Glob)
+    }
+
+    pub const fn id(pattern: &'a str) -> Self {
+        Self::new(pattern, NameMatch::Id)
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self.mode {
+            NameMatch::Contains => name.contains(self.pattern),
+            NameMatch::Exact => name == self.pattern || strip_copy_suffix(name) == self.pattern,
+            NameMatch::Prefix => name.starts_with(self.pattern),
+            NameMatch::Glob => glob_matches(self.pattern, name),
+            NameMatch::Id => {
+                let pattern = EntityName::parse(self.pattern);
+                let name = EntityName::parse(name);
+                name.id == pattern.id
+                    && pattern.kinds.iter().all(|kind| name.has_kind(kind))
+                    && pattern.tags.iter().all(|tag| name.has_tag(tag))
+            }
+        }
+    }
+
+    /// Every named entry which matches, sorted by name so the result doesn't depend on the
+    /// order of a map or query.
+    pub fn all_in<'n, T>(
+        &self,
+        entries: impl IntoIterator<Item = (&'n str, T)>,
+    ) -> Vec<(&'n str, T)> {
+        let mut matches: Vec<_> = entries
+            .into_iter()
+            .filter(|(name, _)| self.matches(name))
+            .collect();
+        matches.sort_by(|(a, _), (b, _)| a.cmp(b));
+        matches
+    }
+
+    /// The matching entry with the first name in sort order.
+    pub fn first_in<'n, T>(
+        &self,
+        entries: impl IntoIterator<Item = (&'n str, T)>,
+    ) -> Option<(&'n str, T)> {
+        entries
+            .into_iter()
+            .filter(|(name, _)| self.matches(name))
+            .min_by(|(a, _), (b, _)| a.cmp(b))
+    }
+}
+
+/// An owned [`NameQuery`] read from a data file such as a logic graph.
+///
+/// A plain string matches names containing it, or the mode can be given as
+/// `{"Exact": "DOOR_LINEAR Garage Gate"}`, with any of the [`NameMatch`] modes.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(from = "PatternFormat")]
+pub struct NamePattern {
+    pub pattern: String,
+    pub mode: NameMatch,
+}
+
+impl NamePattern {
+    pub fn query(&self) -> NameQuery {
+        NameQuery::new(&self.pattern, self.mode)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PatternFormat {
+    Contains(String),
+    WithMode(PatternWithMode),
+}
+
+#[derive(Deserialize)]
+enum PatternWithMode {
+    Contains(String),
+    Exact(String),
+    Prefix(String),
+    Glob(String),
+    Id(String),
+}
+
+impl From<PatternFormat> for NamePattern {
+    fn from(format: PatternFormat) -> Self {
+        let (pattern, mode) = match format {
+            PatternFormat::Contains(pattern)
+            | PatternFormat::WithMode(PatternWithMode::Contains(pattern)) => {
+                (pattern, NameMatch::Contains)
+            }
+            PatternFormat::WithMode(PatternWithMode::Exact(pattern)) => (pattern, NameMatch::Exact),
+            PatternFormat::WithMode(PatternWithMode::Prefix(pattern)) => {
+                (pattern, NameMatch::Prefix)
+            }
+            PatternFormat::WithMode(PatternWithMode::Glob(pattern)) => (pattern, NameMatch::Glob),
+            PatternFormat::WithMode(PatternWithMode::Id(pattern)) => (pattern, NameMatch::Id),
+        };
+        NamePattern { pattern, mode }
+    }
+}
+
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*` if the rest stops matching
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn parses_names() {
+        assert_eq!(
+            EntityName::parse("COLLIDER DOOR_LINEAR Door 1 Left #lobby #metal"),
+            EntityName {
+                kinds: vec!["COLLIDER", "DOOR_LINEAR"],
+                id: "Door 1 Left",
+                tags: vec!["lobby", "metal"],
+            }
+        );
+        assert_eq!(EntityName::parse("BLOCK Bar Stool.004").id, "Bar Stool");
+        assert_eq!(EntityName::parse("BLOCK TV").id, "TV");
+        assert_eq!(EntityName::parse("BLOCK.001").kinds, ["BLOCK"]);
+        assert!(EntityName::parse("Text.012").kinds.is_empty());
+    }
+
+    #[test]
+    fn exact_doesnt_match_longer_names() {
+        let query = NameQuery::exact("BUTTON vending 1");
+        assert!(query.matches("BUTTON vending 1"));
+        assert!(query.matches("BUTTON vending 1.001"));
+        assert!(!query.matches("BUTTON vending 10"));
+        assert!(NameQuery::contains("BUTTON vending 1").matches("BUTTON vending 10"));
+    }
+
+    #[test]
+    fn globs() {
+        assert!(NameQuery::glob("BUTTON vending ?").matches("BUTTON vending 4"));
+        assert!(!NameQuery::glob("BUTTON vending ?").matches("BUTTON vending 10"));
+        assert!(
+            NameQuery::glob("*Elevator Door").matches("COLLIDER DOOR_LINEAR Left Elevator Door")
+        );
+        assert!(NameQuery::glob("TRIGGER *Inside*").matches("TRIGGER Elevator Inside Main"));
+        assert!(!NameQuery::glob("TRIGGER *Inside").matches("TRIGGER Elevator Inside Main"));
+    }
+
+    #[test]
+    fn ids_ignore_other_kinds() {
+        let query = NameQuery::id("DOOR_LINEAR Door 1 Left");
+        assert!(query.matches("COLLIDER DOOR_LINEAR Door 1 Left"));
+        assert!(!query.matches("COLLIDER DOOR_LINEAR Door 1 Left Frame"));
+        assert!(!query.matches("COLLIDER Door 1 Left"));
+        assert!(NameQuery::id("TRIGGER #lobby").matches("TRIGGER #lobby"));
+    }
+
+    #[test]
+    fn patterns_contain_unless_given_a_mode() {
+        let pattern: NamePattern = serde_json::from_str(r#""Elevator Door""#).unwrap();
+        assert_eq!(pattern.query(), NameQuery::contains("Elevator Door"));
+        let pattern: NamePattern =
+            serde_json::from_str(r#"{"Exact": "DOOR_LINEAR Garage Gate"}"#).unwrap();
+        assert_eq!(pattern.query(), NameQuery::exact("DOOR_LINEAR Garage Gate"));
+        let pattern: NamePattern = serde_json::from_str(r#"{"Glob": "BUTTON Phone ?"}"#).unwrap();
+        assert_eq!(pattern.query(), NameQuery::glob("BUTTON Phone ?"));
+    }
+
+    #[test]
+    fn lookups_are_sorted() {
+        let map: HashMap<String, u32> = (0..20)
+            .map(|i| (format!("BUTTON vending {i}"), i))
+            .collect();
+
+        let entries = || map.iter().map(|(name, i)| (name.as_str(), i));
+
+        let all = NameQuery::contains("vending 1").all_in(entries());
+        assert_eq!(
+            all.iter().map(|(_, i)| **i).collect::<Vec<_>>(),
+            [1, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19]
+        );
+        assert_eq!(
+            NameQuery::contains("vending 1").first_in(entries()),
+            Some(all[0])
+        );
+        assert_eq!(
+            NameQuery::exact("BUTTON vending 12").first_in(entries()),
+            Some(("BUTTON vending 12", &12))
+        );
+    }
+}
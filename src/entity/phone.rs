@@ -7,8 +7,10 @@ use bevy_rapier3d::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    actions::Action,
     assets::{FontAssets, ImageAssets, SoundAssets},
     get_display_scale,
+    menu::GameSettings,
     notification::NotificationText,
     spawn_from_scene, PlayerCamera,
 };
@@ -83,6 +85,14 @@ pub(super) enum PhoneKey {
 }
 
 impl PhoneKey {
+    /// Keypad layout, used to move the focused key with the menu actions.
+    const GRID: [[PhoneKey; 3]; 4] = [
+        [PhoneKey::Key1, PhoneKey::Key2, PhoneKey::Key3],
+        [PhoneKey::Key4, PhoneKey::Key5, PhoneKey::Key6],
+        [PhoneKey::Key7, PhoneKey::Key8, PhoneKey::Key9],
+        [PhoneKey::KeyAsterix, PhoneKey::Key0, PhoneKey::KeyHash],
+    ];
+
     fn char(&self) -> char {
         match self {
             PhoneKey::Key0 => '0',
@@ -134,18 +144,18 @@ impl PhoneKey {
         }
     }
 
-    fn matches_key(&self, keycode: KeyCode) -> bool {
-        match keycode {
-            KeyCode::Key0 | KeyCode::Numpad0 if self == &PhoneKey::Key0 => true,
-            KeyCode::Key1 | KeyCode::Numpad1 if self == &PhoneKey::Key1 => true,
-            KeyCode::Key2 | KeyCode::Numpad2 if self == &PhoneKey::Key2 => true,
-            KeyCode::Key3 | KeyCode::Numpad3 if self == &PhoneKey::Key3 => true,
-            KeyCode::Key4 | KeyCode::Numpad4 if self == &PhoneKey::Key4 => true,
-            KeyCode::Key5 | KeyCode::Numpad5 if self == &PhoneKey::Key5 => true,
-            KeyCode::Key6 | KeyCode::Numpad6 if self == &PhoneKey::Key6 => true,
-            KeyCode::Key7 | KeyCode::Numpad7 if self == &PhoneKey::Key7 => true,
-            KeyCode::Key8 | KeyCode::Numpad8 if self == &PhoneKey::Key8 => true,
-            KeyCode::Key9 | KeyCode::Numpad9 if self == &PhoneKey::Key9 => true,
+    fn matches_action(&self, action: Action) -> bool {
+        match action {
+            Action::Phone0 => self == &PhoneKey::Key0,
+            Action::Phone1 => self == &PhoneKey::Key1,
+            Action::Phone2 => self == &PhoneKey::Key2,
+            Action::Phone3 => self == &PhoneKey::Key3,
+            Action::Phone4 => self == &PhoneKey::Key4,
+            Action::Phone5 => self == &PhoneKey::Key5,
+            Action::Phone6 => self == &PhoneKey::Key6,
+            Action::Phone7 => self == &PhoneKey::Key7,
+            Action::Phone8 => self == &PhoneKey::Key8,
+            Action::Phone9 => self == &PhoneKey::Key9,
             _ => false,
         }
     }
@@ -156,9 +166,10 @@ pub(super) fn resize_phone_ui(
     mut ui_image: Query<&mut Style, (With<PhoneUiImage>, Without<PhoneUiContainer>)>,
     mut ui_text: Query<&mut Text, With<PhoneUiText>>,
     mut window_resized_events: EventReader<WindowResized>,
+    game_settings: Res<GameSettings>,
 ) {
     if let Some(event) = window_resized_events.iter().last() {
-        let scale = get_display_scale(event.width, event.height);
+        let scale = get_display_scale(game_settings.render_scale, event.width, event.height);
         // let scale = Vec3::new(event.width, event.height, 1.0);
 
         for mut style in ui_container.iter_mut() {
@@ -249,6 +260,25 @@ pub(super) fn sync_phone_visibility(
     }
 }
 
+/// Closes the phone when the player moves away or hangs up, it's opened by the level's logic
+/// graph.
+pub(super) fn close_phone(
+    mut phone_ui_visible: ResMut<PhoneUiVisible>,
+    actions: Res<Input<Action>>,
+) {
+    if **phone_ui_visible
+        && actions.any_just_pressed([
+            Action::MoveForward,
+            Action::MoveBack,
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::PhoneClose,
+        ])
+    {
+        **phone_ui_visible = false;
+    }
+}
+
 #[derive(Deref, DerefMut)]
 pub(super) struct NumberNotAvailableTimer(Timer);
 
@@ -316,18 +346,59 @@ pub(super) fn press_phone_keys(
         Option<&Interaction>,
         Option<ChangeTrackers<Interaction>>,
         &mut UiImage,
+        &mut UiColor,
         &PhoneKey,
     )>,
     mut phone_text: Query<&mut Text, With<PhoneUiText>>,
     mut phone_submit_events: EventWriter<PhoneSubmitEvent>,
-    keys: Res<Input<KeyCode>>,
+    actions: Res<Input<Action>>,
     image_assets: Res<ImageAssets>,
     audio: Res<Audio>,
     sound_assets: Res<SoundAssets>,
     mut current_pressed_key: Local<Option<PhoneKey>>,
+    mut focus: Local<(usize, usize)>,
 ) {
     if **phone_ui_visible && **phone_ui_enabled {
-        for (_interaction, _interaction_changes, mut img, phone_key) in &mut phone_keys {
+        // Keypad navigation for gamepads
+        let (mut column, mut row) = *focus;
+        if actions.just_pressed(Action::MenuLeft) {
+            column = column.saturating_sub(1);
+        }
+        if actions.just_pressed(Action::MenuRight) {
+            column = (column + 1).min(2);
+        }
+        if actions.just_pressed(Action::MenuUp) {
+            row = row.saturating_sub(1);
+        }
+        if actions.just_pressed(Action::MenuDown) {
+            row = (row + 1).min(PhoneKey::GRID.len() - 1);
+        }
+        *focus = (column, row);
+        let focused_key = PhoneKey::GRID[row][column];
+
+        let just_pressed = |phone_key: &PhoneKey| {
+            actions
+                .get_just_pressed()
+                .any(|action| phone_key.matches_action(*action))
+                || (*phone_key == focused_key && actions.just_pressed(Action::PhoneSelect))
+        };
+        let just_released = |phone_key: &PhoneKey| {
+            actions
+                .get_just_released()
+                .any(|action| phone_key.matches_action(*action))
+                || (*phone_key == focused_key && actions.just_released(Action::PhoneSelect))
+        };
+
+        for (_interaction, _interaction_changes, mut img, mut color, phone_key) in &mut phone_keys {
+            let tint = if *phone_key == focused_key {
+                Color::rgb(0.7, 0.7, 0.7)
+            } else {
+                Color::WHITE
+            };
+            if color.0 != tint {
+                color.0 = tint;
+            }
+
             // Mouse interaction
             // if interaction_changes
             //     .map(|changes| changes.is_changed())
@@ -353,56 +424,48 @@ pub(super) fn press_phone_keys(
             // }
 
             // Keys interaction
-            if current_pressed_key.is_none() {
-                for key in keys.get_just_pressed() {
-                    if phone_key.matches_key(*key) {
-                        img.0 = phone_key.image_pressed(&image_assets);
-                        for mut text in &mut phone_text {
-                            if let Some(section) = text.sections.iter_mut().next() {
-                                section.value.push(phone_key.char());
-
-                                if section.value.len() >= 7 {
-                                    phone_submit_events.send(PhoneSubmitEvent {
-                                        number: section.value.clone(),
-                                    });
-                                    **phone_ui_enabled = false;
-                                    img.0 = phone_key.image(&image_assets);
-                                }
-                            }
+            if current_pressed_key.is_none() && just_pressed(phone_key) {
+                img.0 = phone_key.image_pressed(&image_assets);
+                for mut text in &mut phone_text {
+                    if let Some(section) = text.sections.iter_mut().next() {
+                        section.value.push(phone_key.char());
+
+                        if section.value.len() >= 7 {
+                            phone_submit_events.send(PhoneSubmitEvent {
+                                number: section.value.clone(),
+                            });
+                            **phone_ui_enabled = false;
+                            img.0 = phone_key.image(&image_assets);
                         }
-                        let playback_rate = match phone_key {
-                            PhoneKey::Key0 => 0.875,
-                            PhoneKey::Key1 => 0.9,
-                            PhoneKey::Key2 => 0.925,
-                            PhoneKey::Key3 => 0.95,
-                            PhoneKey::Key4 => 0.975,
-                            PhoneKey::Key5 => 1.0,
-                            PhoneKey::Key6 => 1.025,
-                            PhoneKey::Key7 => 1.05,
-                            PhoneKey::Key8 => 1.075,
-                            PhoneKey::Key9 => 1.1,
-                            PhoneKey::KeyHash => 1.0,
-                            PhoneKey::KeyAsterix => 1.0,
-                        };
-                        audio
-                            .play(sound_assets.phone_key_press.clone())
-                            .with_playback_rate(playback_rate)
-                            .with_volume(0.1);
-                        *current_pressed_key = Some(*phone_key);
-                        break;
                     }
                 }
+                let playback_rate = match phone_key {
+                    PhoneKey::Key0 => 0.875,
+                    PhoneKey::Key1 => 0.9,
+                    PhoneKey::Key2 => 0.925,
+                    PhoneKey::Key3 => 0.95,
+                    PhoneKey::Key4 => 0.975,
+                    PhoneKey::Key5 => 1.0,
+                    PhoneKey::Key6 => 1.025,
+                    PhoneKey::Key7 => 1.05,
+                    PhoneKey::Key8 => 1.075,
+                    PhoneKey::Key9 => 1.1,
+                    PhoneKey::KeyHash => 1.0,
+                    PhoneKey::KeyAsterix => 1.0,
+                };
+                audio
+                    .play(sound_assets.phone_key_press.clone())
+                    .with_playback_rate(playback_rate)
+                    .with_volume(0.1);
+                *current_pressed_key = Some(*phone_key);
             }
-            for key in keys.get_just_released() {
-                if phone_key.matches_key(*key) {
-                    img.0 = phone_key.image(&image_assets);
-                    *current_pressed_key = None;
-                    break;
-                }
+            if just_released(phone_key) {
+                img.0 = phone_key.image(&image_assets);
+                *current_pressed_key = None;
             }
         }
 
-        if keys.just_pressed(KeyCode::Back) {
+        if actions.just_pressed(Action::PhoneBackspace) {
             for mut text in &mut phone_text {
                 if let Some(section) = text.sections.iter_mut().next() {
                     section.value.pop();
@@ -429,13 +492,17 @@ pub(super) fn setup_phone_ui(
     windows: Res<Windows>,
     font_assets: Res<FontAssets>,
     image_assets: Res<ImageAssets>,
+    game_settings: Res<GameSettings>,
 ) {
-    let window = windows.get_primary().unwrap();
-
-    let scale = get_display_scale(
-        window.physical_width() as f32,
-        window.physical_height() as f32,
-    );
+    // There's no window when running headless, lay out for the default size instead
+    let scale = match windows.get_primary() {
+        Some(window) => get_display_scale(
+            game_settings.render_scale,
+            window.physical_width() as f32,
+            window.physical_height() as f32,
+        ),
+        None => get_display_scale(game_settings.render_scale, 1280.0, 720.0),
+    };
     // let scale = Vec3::new(window.width(), window.height(), 1.0);
 
     cmds.spawn_bundle(NodeBundle {
@@ -631,9 +698,9 @@ pub(super) fn phone_interact_events(
     phones: Query<(Option<&Name>, &Phone)>,
     mut phone_menu_open_events: EventWriter<PhoneMenuOpenEvent>,
     physics_context: Res<RapierContext>,
-    mouse_button: Res<Input<MouseButton>>,
+    actions: Res<Input<Action>>,
 ) {
-    if mouse_button.just_pressed(MouseButton::Left) {
+    if actions.just_pressed(Action::Interact) {
         for transform in player_camera.iter() {
             let max_dist = 2.0;
 
@@ -0,0 +1,100 @@
+//! Items lying around the level, picked up by pressing interact on them.
+//!
+//! A `PICKUP` with an `item` in its extras is the one the player presses, it's a [`Button`]
+//! whether or not it's named one. Other `PICKUP`s with the same [id](EntityName) are parts of it,
+//! such as its mesh, and go with it when it's picked up:
+//!
+//! ```text
+//! BUTTON PICKUP money {"item": "money"}
+//! PICKUP MESH money
+//! ```
+
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{item::Items, levels::Level, spawn_from_scene};
+
+use super::{
+    button::{insert_button_sensor, Button, ButtonEvent},
+    name::EntityName,
+};
+
+#[derive(Clone, Debug, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+#[serde(default)]
+pub struct Pickup {
+    /// Id of the [`ItemDefinition`](crate::item::ItemDefinition), empty for parts of a pickup
+    pub item: String,
+    pub count: u32,
+}
+
+impl Default for Pickup {
+    fn default() -> Self {
+        Self {
+            item: String::new(),
+            count: 1,
+        }
+    }
+}
+
+/// Levels and ids of pickups which have been picked up, so they don't come back with the level.
+///
+/// Keyed by level as well as id, two levels can each have a pickup with the same id.
+#[derive(Clone, Debug, Default, Deref, DerefMut)]
+pub struct CollectedPickups(pub HashSet<(Level, String)>);
+
+spawn_from_scene!(pickup, Pickup, |cmds, entity, pickup| {
+    if pickup.item.is_empty() {
+        return Ok(());
+    }
+    let is_button = entity
+        .get::<Name>()
+        .map_or(false, |name| EntityName::parse(name).has_kind("BUTTON"));
+    if !is_button {
+        insert_button_sensor(cmds);
+        cmds.insert(Button::default());
+    }
+    Ok(())
+});
+
+pub(super) fn pick_up_items(
+    mut cmds: Commands,
+    mut button_events: EventReader<ButtonEvent>,
+    pickups: Query<(Entity, &Name, &Pickup)>,
+    mut items: Items,
+    mut collected: ResMut<CollectedPickups>,
+    level: Res<Level>,
+) {
+    for event in button_events.iter().filter(|event| event.pressed) {
+        let (name, pickup) = match pickups.get(event.entity) {
+            Ok((_, name, pickup)) if !pickup.item.is_empty() => (name, pickup),
+            _ => continue,
+        };
+        if !items.give(&pickup.item, pickup.count) {
+            warn!(%name, item = %pickup.item, "Pickup has an unknown item");
+            continue;
+        }
+
+        let id = EntityName::parse(name).id;
+        for (entity, _, _) in pickups
+            .iter()
+            .filter(|(_, name, _)| EntityName::parse(name).id == id)
+        {
+            cmds.entity(entity).despawn_recursive();
+        }
+        collected.insert((*level, id.to_string()));
+    }
+}
+
+pub(super) fn despawn_collected_pickups(
+    mut cmds: Commands,
+    pickups: Query<(Entity, &Name), Added<Pickup>>,
+    collected: Res<CollectedPickups>,
+    level: Res<Level>,
+) {
+    for (entity, name) in &pickups {
+        if collected.contains(&(*level, EntityName::parse(name).id.to_string())) {
+            cmds.entity(entity).despawn_recursive();
+        }
+    }
+}
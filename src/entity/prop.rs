@@ -0,0 +1,224 @@
+//! Loose objects with physics, which the player can pick up, carry and throw.
+//!
+//! A carried prop is steered towards where it's held by setting its velocity rather than moving
+//! it, so it still collides with the level on the way and stops against walls.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_fps_controller::controller::LogicalPlayer;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::Action,
+    assets::{get_verts_indices, MeshError},
+    entity::error::EntityError,
+    spawn_from_scene, PlayerCamera,
+};
+
+use super::button::interaction_ray;
+
+/// How far in front of the camera a carried prop is held.
+const HOLD_DISTANCE: f32 = 1.5;
+
+/// A carried prop is let go when it's this far from where it should be, such as when it's stuck
+/// behind a wall.
+const DROP_DISTANCE: f32 = 1.5;
+
+/// How quickly a carried prop catches up with where it's held, per second.
+const CARRY_STIFFNESS: f32 = 15.0;
+
+/// Enough to keep up with a sprinting player without outrunning collision detection.
+const MAX_CARRY_SPEED: f32 = 10.0;
+
+const THROW_SPEED: f32 = 8.0;
+
+/// Radians per second a carried prop turns while rotating it.
+const ROTATE_SPEED: f32 = PI;
+
+/// A dynamic body with a collider made from its mesh.
+#[derive(Clone, Debug, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+#[serde(default)]
+pub struct Prop {
+    /// Mass per cubic metre of the collider
+    pub density: f32,
+}
+
+impl Default for Prop {
+    fn default() -> Self {
+        Self { density: 100.0 }
+    }
+}
+
+/// On the prop the player is holding.
+#[derive(Clone, Debug, Component)]
+pub struct Carried {
+    /// Rotation relative to the way the player is facing
+    rotation: Quat,
+    /// Gravity scale to put back once let go
+    gravity: f32,
+}
+
+spawn_from_scene!(prop, Prop, |cmds, entity, prop, world: &World| {
+    let meshes = world.resource::<Assets<Mesh>>();
+    let own_mesh = entity
+        .get::<Handle<Mesh>>()
+        .map(|mesh| (mesh, Transform::identity()));
+    let child_meshes = entity
+        .get::<Children>()
+        .into_iter()
+        .flat_map(|children| children.iter())
+        .filter_map(|child| {
+            let child = world.get_entity(*child)?;
+            Some((
+                child.get::<Handle<Mesh>>()?,
+                child.get::<Transform>().copied().unwrap_or_default(),
+            ))
+        });
+
+    // Dynamic bodies need convex shapes, so each mesh becomes its hull
+    let mut shapes = Vec::new();
+    for (mesh, transform) in own_mesh.into_iter().chain(child_meshes) {
+        let mesh = meshes.get(mesh).ok_or(EntityError::MissingMesh)?;
+        let (vertices, _) = get_verts_indices(mesh)?;
+        let points: Vec<Vec3> = vertices
+            .iter()
+            .map(|vertex| *vertex * transform.scale)
+            .collect();
+        let hull = Collider::convex_hull(&points).ok_or(MeshError::DegenerateHull)?;
+        shapes.push((transform.translation, transform.rotation, hull));
+    }
+    if shapes.is_empty() {
+        return Err(EntityError::MissingMesh);
+    }
+
+    cmds.insert(RigidBody::Dynamic)
+        .insert(Collider::compound(shapes))
+        .insert(ColliderMassProperties::Density(prop.density))
+        .insert(Velocity::zero())
+        // Thrown props are fast enough to pass through thin walls otherwise
+        .insert(Ccd::enabled());
+    Ok(())
+});
+
+pub(super) fn carry_props(
+    mut cmds: Commands,
+    time: Res<Time>,
+    actions: Res<Input<Action>>,
+    physics_context: Res<RapierContext>,
+    player: Query<Entity, With<LogicalPlayer>>,
+    player_camera: Query<&Transform, With<PlayerCamera>>,
+    props: Query<(&GlobalTransform, Option<&GravityScale>), (With<Prop>, With<RigidBody>)>,
+    mut carried: Query<(Entity, &GlobalTransform, &mut Velocity, &mut Carried)>,
+) {
+    let camera = match player_camera.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    // Props turn with the player but don't tilt as they look up and down
+    let (yaw, _, _) = camera.rotation.to_euler(EulerRot::YXZ);
+    let facing = Quat::from_rotation_y(yaw);
+
+    if let Ok((entity, transform, mut velocity, mut held)) = carried.get_single_mut() {
+        let target = camera.translation + camera.forward() * HOLD_DISTANCE;
+        let position = transform.translation();
+        let thrown = actions.just_pressed(Action::Throw);
+
+        if thrown
+            || actions.just_pressed(Action::Interact)
+            || position.distance(target) > DROP_DISTANCE
+        {
+            if thrown {
+                velocity.linvel = camera.forward() * THROW_SPEED;
+                velocity.angvel = Vec3::ZERO;
+            }
+            debug!(?entity, thrown, "Prop let go");
+            cmds.entity(entity)
+                .remove::<Carried>()
+                .insert(GravityScale(held.gravity));
+            return;
+        }
+
+        if actions.pressed(Action::RotateProp) {
+            held.rotation =
+                Quat::from_rotation_y(ROTATE_SPEED * time.delta_seconds()) * held.rotation;
+        }
+
+        velocity.linvel = ((target - position) * CARRY_STIFFNESS).clamp_length_max(MAX_CARRY_SPEED);
+        let rotation = transform.compute_transform().rotation;
+        let (axis, angle) = (facing * held.rotation * rotation.inverse()).to_axis_angle();
+        // Turn the short way round
+        let angle = if angle > PI { angle - 2.0 * PI } else { angle };
+        velocity.angvel = axis * angle * CARRY_STIFFNESS;
+        return;
+    }
+
+    if !actions.just_pressed(Action::Interact) {
+        return;
+    }
+    // The camera is inside the player's collider
+    let mut filter = QueryFilter::default().exclude_sensors();
+    if let Ok(player) = player.get_single() {
+        filter = filter.exclude_rigid_body(player);
+    }
+    if let Some((entity, _)) = interaction_ray(camera, &physics_context, filter) {
+        if let Ok((transform, gravity)) = props.get(entity) {
+            debug!(?entity, "Prop picked up");
+            cmds.entity(entity)
+                .insert(Carried {
+                    rotation: facing.inverse() * transform.compute_transform().rotation,
+                    gravity: gravity.map_or(1.0, |gravity| gravity.0),
+                })
+                // Otherwise it sags below where it's held
+                .insert(GravityScale(0.0));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness::TestGame;
+
+    #[test]
+    fn carries_and_throws_props() {
+        let mut game = TestGame::new();
+        // Well away from the level, floating until it's thrown
+        let start = Vec3::new(0.0, 500.0, 0.0);
+        let prop = game
+            .app
+            .world
+            .spawn()
+            .insert_bundle(TransformBundle::from(Transform::from_translation(start)))
+            .insert(Prop::default())
+            .insert(RigidBody::Dynamic)
+            .insert(Collider::cuboid(0.2, 0.2, 0.2))
+            .insert(Velocity::zero())
+            .insert(GravityScale(0.0))
+            .id();
+        game.run_frames(2);
+
+        // The camera faces down -Z
+        game.set_camera(start + Vec3::Z);
+        game.press(Action::Interact);
+        assert!(game.app.world.get::<Carried>(prop).is_some());
+
+        game.set_camera(start + Vec3::Z + Vec3::X);
+        game.run_frames(60);
+        let held = start + Vec3::Z * (1.0 - HOLD_DISTANCE) + Vec3::X;
+        let position = game
+            .app
+            .world
+            .get::<GlobalTransform>(prop)
+            .unwrap()
+            .translation();
+        assert!(position.distance(held) < 0.1, "{position} != {held}");
+
+        game.press(Action::Throw);
+        assert!(game.app.world.get::<Carried>(prop).is_none());
+        let velocity = game.app.world.get::<Velocity>(prop).unwrap().linvel;
+        assert!(velocity.dot(-Vec3::Z) > THROW_SPEED * 0.5, "{velocity}");
+    }
+}
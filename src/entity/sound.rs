@@ -0,0 +1,126 @@
+//! Sounds placed in the level, such as hums, drips and machinery.
+//!
+//! A `SOUND` plays a file from `assets` where it's placed, through its own [`AudioComponent`]
+//! so it pans and fades as the player moves. It starts with the level, or each time something
+//! enters the trigger named by `trigger`:
+//!
+//! ```text
+//! SOUND Boiler Hum {"sound": "sounds/drone1.flac", "volume": 0.5, "looped": true, "radius": 15.0}
+//! SOUND Lobby Chime {"sound": "sounds/click.flac", "trigger": "TRIGGER Lobby Entrance"}
+//! ```
+
+use bevy::prelude::*;
+use bevy_kira_audio::{AudioControl, AudioSource, DynamicAudioChannels};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::{AudioComponent, DistanceModel, SpatialAudio},
+    entity::error::EntityError,
+    spawn_from_scene,
+};
+
+use super::{name::NameQuery, trigger::TriggerEnterEvent};
+
+#[derive(Clone, Debug, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+#[serde(default)]
+pub struct Sound {
+    /// Path relative to the assets folder
+    pub sound: String,
+    pub volume: f32,
+    pub looped: bool,
+    /// Heard up to this far, fading out from a tenth of the way
+    pub radius: f32,
+    /// Whether it starts with the level, ignored if there's a `trigger`
+    pub autoplay: bool,
+    /// Name of a trigger which starts it whenever something enters, a looped sound only starts
+    /// the first time
+    pub trigger: Option<String>,
+    /// Whether it's quieter with something solid in the way
+    pub occlusion: bool,
+    #[serde(skip)]
+    #[reflect(ignore)]
+    pub handle: Handle<AudioSource>,
+    #[serde(skip)]
+    pub started: bool,
+}
+
+impl Default for Sound {
+    fn default() -> Self {
+        Self {
+            sound: String::new(),
+            volume: 1.0,
+            looped: false,
+            radius: 20.0,
+            autoplay: true,
+            trigger: None,
+            occlusion: false,
+            handle: Handle::default(),
+            started: false,
+        }
+    }
+}
+
+impl Sound {
+    /// How the sound carries, from its volume and radius.
+    pub fn spatial_audio(&self) -> SpatialAudio {
+        SpatialAudio {
+            volume: self.volume,
+            distance_model: DistanceModel::Linear,
+            min_distance: self.radius * 0.1,
+            max_distance: self.radius,
+            occlusion: self.occlusion,
+            ..default()
+        }
+    }
+}
+
+spawn_from_scene!(
+    sound,
+    Sound,
+    |cmds, _entity, sound, asset_server: Res<AssetServer>| {
+        cmds.insert(sound.spatial_audio());
+        if sound.sound.is_empty() {
+            return Err(EntityError::InvalidProperties {
+                path: String::from("sound"),
+                message: String::from("no sound file"),
+            });
+        }
+        sound.handle = asset_server.load(sound.sound.as_str());
+        Ok(())
+    }
+);
+
+pub(super) fn play_sounds(
+    mut cmds: Commands,
+    mut sounds: Query<(Entity, &mut Sound, Option<&AudioComponent>, Option<&Name>)>,
+    mut trigger_enter_events: EventReader<TriggerEnterEvent>,
+    mut channels: ResMut<DynamicAudioChannels>,
+) {
+    let entered: Vec<&str> = trigger_enter_events
+        .iter()
+        .filter_map(|event| event.name.as_deref())
+        .collect();
+
+    for (entity, mut sound, audio_comp, name) in &mut sounds {
+        let start = match &sound.trigger {
+            Some(trigger) => entered
+                .iter()
+                .any(|entered| NameQuery::contains(trigger).matches(entered)),
+            None => sound.autoplay && !sound.started,
+        };
+        if !start || (sound.looped && sound.started) || sound.sound.is_empty() {
+            continue;
+        }
+        sound.started = true;
+
+        debug!(?name, sound = %sound.sound, "Playing sound");
+        let audio_comp =
+            AudioComponent::get_or_insert(&mut cmds, entity, audio_comp, &mut channels);
+        let channel = audio_comp.channel(&channels);
+        let mut play = channel.play(sound.handle.clone());
+        if sound.looped {
+            play.looped();
+        }
+    }
+}
@@ -27,6 +27,7 @@ impl Default for Teleport {
 
 spawn_from_scene!(teleport, Teleport, |cmds, _entity, _teleport| {
     cmds.insert(Collider::cuboid(1.0, 1.0, 1.0)).insert(Sensor);
+    Ok(())
 });
 
 /// Teleport a player if they are within the radius of any active teleport.
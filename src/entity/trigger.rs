@@ -1,15 +1,21 @@
-use bevy::{prelude::*, utils::hashbrown::HashMap};
+use bevy::{ecs::system::SystemParam, prelude::*, utils::hashbrown::HashMap};
 use bevy_fps_controller::controller::LogicalPlayer;
 use bevy_rapier3d::{prelude::*, rapier::prelude::CollisionEventFlags};
 use serde::{Deserialize, Serialize};
 
 use crate::spawn_from_scene;
 
-use super::Named;
+use super::{
+    name::{EntityName, NameQuery},
+    Named, NamedIterator,
+};
 
+/// The first body a trigger detects entered it.
 pub struct TriggerEnterEvent {
     pub name: Option<String>,
     pub entity: Entity,
+    /// The body which entered
+    pub body: Entity,
 }
 
 impl Named for TriggerEnterEvent {
@@ -18,9 +24,12 @@ impl Named for TriggerEnterEvent {
     }
 }
 
+/// The last body a trigger detects left it.
 pub struct TriggerExitEvent {
     pub name: Option<String>,
     pub entity: Entity,
+    /// The body which left
+    pub body: Entity,
 }
 
 impl Named for TriggerExitEvent {
@@ -29,131 +38,349 @@ impl Named for TriggerExitEvent {
     }
 }
 
-/// A trigger which emits [`TriggerEnterEvent`] and [`TriggerExitEvent`] events when the player enters the region.
+/// A trigger which emits [`TriggerEnterEvent`] when the first body it [`detects`] enters the
+/// region and [`TriggerExitEvent`] when the last one leaves.
+///
+/// [`detects`]: Trigger::detects
 #[derive(Clone, Debug, Component, Reflect, Serialize, Deserialize)]
 #[reflect(Component)]
 #[serde(default)]
 pub struct Trigger {
     pub enabled: bool,
+    pub detects: TriggerFilter,
 }
 
 impl Default for Trigger {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            detects: TriggerFilter::Player,
+        }
+    }
+}
+
+/// Which bodies a [`Trigger`] reacts to, such as `{"detects": {"Tag": "chair"}}` in the extras.
+#[derive(Clone, Debug, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+pub enum TriggerFilter {
+    Player,
+    /// The player or any dynamic rigid body, for pressure plates
+    Dynamic,
+    /// Bodies with the `#tag` in their name, see [`EntityName`]
+    Tag(String),
+}
+
+impl TriggerFilter {
+    fn detects(&self, entity: Entity, bodies: &Query<Body>) -> bool {
+        let (name, rigid_body, parent, player) = match bodies.get(entity) {
+            Ok(body) => body,
+            Err(_) => return false,
+        };
+        // Colliders can be children of their body, such as meshes of an object from Blender
+        self.matches(name, rigid_body, player)
+            || parent
+                .and_then(|parent| bodies.get(**parent).ok())
+                .map_or(false, |(name, rigid_body, _, player)| {
+                    self.matches(name, rigid_body, player)
+                })
+    }
+
+    fn matches(
+        &self,
+        name: Option<&Name>,
+        rigid_body: Option<&RigidBody>,
+        player: Option<&LogicalPlayer>,
+    ) -> bool {
+        match self {
+            TriggerFilter::Player => player.is_some(),
+            TriggerFilter::Dynamic => {
+                player.is_some() || matches!(rigid_body, Some(RigidBody::Dynamic))
+            }
+            TriggerFilter::Tag(tag) => {
+                name.map_or(false, |name| EntityName::parse(name).has_tag(tag))
+            }
+        }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+type Body = (
+    Option<&'static Name>,
+    Option<&'static RigidBody>,
+    Option<&'static Parent>,
+    Option<&'static LogicalPlayer>,
+);
+
+#[derive(Clone, Debug)]
 pub struct NamedTriggerStatus {
+    pub name: String,
     pub entity: Entity,
-    pub player_is_inside: bool,
+    /// Whether it became occupied rather than empty
+    pub is_occupied: bool,
 }
 
+/// Named triggers which became occupied or empty this frame, see [`TriggerStates`] for who is
+/// inside them now.
 #[derive(Default)]
-pub struct NamedTriggerStatuses(HashMap<String, NamedTriggerStatus>);
+pub struct NamedTriggerStatuses(HashMap<Entity, NamedTriggerStatus>);
 
 impl NamedTriggerStatuses {
+    /// The first trigger in name order whose name contains `pat`.
     pub fn any(&self, pat: &str) -> Option<NamedTriggerStatus> {
-        for (name, status) in &self.0 {
-            if name.contains(pat) {
-                return Some(*status);
-            }
+        self.find(NameQuery::contains(pat))
+    }
+
+    /// The first trigger in name order matching `query`.
+    pub fn find(&self, query: NameQuery) -> Option<NamedTriggerStatus> {
+        query
+            .first_in(self.named())
+            .map(|(_, status)| status.clone())
+    }
+
+    /// Every trigger matching `query`, sorted by name.
+    pub fn all(&self, query: NameQuery) -> Vec<(&str, &NamedTriggerStatus)> {
+        query.all_in(self.named())
+    }
+
+    fn named(&self) -> impl Iterator<Item = (&str, &NamedTriggerStatus)> {
+        self.0.values().map(|status| (status.name.as_str(), status))
+    }
+}
+
+/// Colliders inside a trigger which it detects, kept up to date as they enter and leave. The
+/// trigger stays occupied until the last one leaves.
+#[derive(Clone, Debug, Default, Component)]
+pub struct TriggerOccupancy {
+    occupants: Vec<Entity>,
+}
+
+impl TriggerOccupancy {
+    pub fn is_occupied(&self) -> bool {
+        !self.occupants.is_empty()
+    }
+
+    pub fn count(&self) -> usize {
+        self.occupants.len()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.occupants.contains(&entity)
+    }
+
+    pub fn occupants(&self) -> &[Entity] {
+        &self.occupants
+    }
+
+    fn enter(&mut self, entity: Entity) {
+        if !self.contains(entity) {
+            self.occupants.push(entity);
         }
-        None
+    }
+
+    fn exit(&mut self, entity: Entity) {
+        self.occupants.retain(|occupant| *occupant != entity);
+    }
+}
+
+/// Triggers' [`TriggerOccupancy`] by name.
+#[derive(SystemParam)]
+pub struct TriggerStates<'w, 's> {
+    triggers: Query<'w, 's, (&'static Name, &'static TriggerOccupancy)>,
+}
+
+impl<'w, 's> TriggerStates<'w, 's> {
+    /// Whether any trigger matching `query` has something inside.
+    pub fn is_occupied(&self, query: NameQuery) -> bool {
+        self.triggers
+            .iter()
+            .filter_name(query)
+            .any(|(_, occupancy)| occupancy.is_occupied())
+    }
+
+    /// Every trigger matching `query`, sorted by name.
+    pub fn all(&self, query: NameQuery) -> Vec<(&str, &TriggerOccupancy)> {
+        query.all_in(
+            self.triggers
+                .iter()
+                .map(|(name, occupancy)| (name.as_str(), occupancy)),
+        )
     }
 }
 
 spawn_from_scene!(trigger, Trigger, |cmds, _entity, _trigger| {
-    cmds.insert(Collider::cuboid(1.0, 1.0, 1.0)).insert(Sensor);
+    cmds.insert(Collider::cuboid(1.0, 1.0, 1.0))
+        .insert(Sensor)
+        // Bodies other than the player don't report collisions themselves
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(TriggerOccupancy::default());
+    Ok(())
 });
 
 pub(super) fn trigger_collision_events(
-    player: Query<Entity, With<LogicalPlayer>>,
     mut trigger_entered_events: EventWriter<TriggerEnterEvent>,
     mut trigger_exit_events: EventWriter<TriggerExitEvent>,
     mut collision_events: EventReader<CollisionEvent>,
-    triggers: Query<(Option<&Name>, &Trigger)>,
+    mut triggers: Query<(Option<&Name>, &Trigger, &mut TriggerOccupancy)>,
+    bodies: Query<Body>,
     mut named_trigger_statuses: ResMut<NamedTriggerStatuses>,
 ) {
     named_trigger_statuses.0 = HashMap::new();
-    if let Some(player) = player.iter().next() {
-        for event in collision_events.iter() {
-            match event {
-                CollisionEvent::Started(e1, e2, flags) => {
-                    if flags.contains(CollisionEventFlags::SENSOR) {
-                        let trigger = if *e1 == player {
-                            triggers
-                                .get(*e2)
-                                .map(|(name, trigger)| (name, trigger, *e2))
-                                .ok()
-                        } else if *e2 == player {
-                            triggers
-                                .get(*e1)
-                                .map(|(name, trigger)| (name, trigger, *e1))
-                                .ok()
-                        } else {
-                            None
-                        };
-
-                        if let Some((name, trigger, entity)) = trigger {
-                            if trigger.enabled {
-                                let name = name.map(|name| name.to_string());
-                                debug!(name = ?name, "Enter trigger");
-                                trigger_entered_events.send(TriggerEnterEvent {
-                                    name: name.clone(),
-                                    entity,
-                                });
-                                if let Some(name) = name {
-                                    named_trigger_statuses.0.insert(
-                                        name,
-                                        NamedTriggerStatus {
-                                            entity,
-                                            player_is_inside: true,
-                                        },
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
-                CollisionEvent::Stopped(e1, e2, flags) => {
-                    if flags.contains(CollisionEventFlags::SENSOR) {
-                        let trigger = if *e1 == player {
-                            triggers
-                                .get(*e2)
-                                .map(|(name, trigger)| (name, trigger, *e2))
-                                .ok()
-                        } else if *e2 == player {
-                            triggers
-                                .get(*e1)
-                                .map(|(name, trigger)| (name, trigger, *e1))
-                                .ok()
-                        } else {
-                            None
-                        };
-
-                        if let Some((name, trigger, entity)) = trigger {
-                            if trigger.enabled {
-                                let name = name.map(|name| name.to_string());
-                                debug!(name = ?name, "Exit trigger");
-                                trigger_exit_events.send(TriggerExitEvent {
-                                    name: name.clone(),
-                                    entity,
-                                });
-                                if let Some(name) = name {
-                                    named_trigger_statuses.0.insert(
-                                        name,
-                                        NamedTriggerStatus {
-                                            entity,
-                                            player_is_inside: false,
-                                        },
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
+    for event in collision_events.iter() {
+        let (e1, e2, flags, entered) = match event {
+            CollisionEvent::Started(e1, e2, flags) => (*e1, *e2, flags, true),
+            CollisionEvent::Stopped(e1, e2, flags) => (*e1, *e2, flags, false),
+        };
+        if !flags.contains(CollisionEventFlags::SENSOR) {
+            continue;
+        }
+
+        for (entity, body) in [(e1, e2), (e2, e1)] {
+            let (name, trigger, mut occupancy) = match triggers.get_mut(entity) {
+                Ok(trigger) => trigger,
+                Err(_) => continue,
+            };
+            // A despawned body can't be checked against the filter, but still has to leave
+            let tracked = if entered {
+                trigger.detects.detects(body, &bodies)
+            } else {
+                occupancy.contains(body)
+            };
+            if !tracked {
+                continue;
+            }
+
+            // Kept while disabled, so it's right once the trigger is enabled
+            let was_occupied = occupancy.is_occupied();
+            if entered {
+                occupancy.enter(body);
+            } else {
+                occupancy.exit(body);
+            }
+            if !trigger.enabled || occupancy.is_occupied() == was_occupied {
+                continue;
+            }
+
+            let name = name.map(|name| name.to_string());
+            if entered {
+                debug!(name = ?name, ?body, "Enter trigger");
+                trigger_entered_events.send(TriggerEnterEvent {
+                    name: name.clone(),
+                    entity,
+                    body,
+                });
+            } else {
+                debug!(name = ?name, ?body, "Exit trigger");
+                trigger_exit_events.send(TriggerExitEvent {
+                    name: name.clone(),
+                    entity,
+                    body,
+                });
+            }
+            if let Some(name) = name {
+                named_trigger_statuses.0.insert(
+                    entity,
+                    NamedTriggerStatus {
+                        name,
+                        entity,
+                        is_occupied: entered,
+                    },
+                );
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::ManualEventReader;
+
+    use crate::test_harness::TestGame;
+
+    use super::*;
+
+    /// A trigger floating above the garage, out of the player's way.
+    fn spawn_trigger(game: &mut TestGame, detects: TriggerFilter) -> (Entity, Vec3) {
+        let position = game.player_position() + Vec3::new(0.0, 50.0, 0.0);
+        let trigger = game
+            .app
+            .world
+            .spawn()
+            .insert_bundle(TransformBundle::from(Transform::from_translation(position)))
+            .insert(Name::new("TRIGGER Test Plate"))
+            .insert(Trigger {
+                enabled: true,
+                detects,
+            })
+            .insert(TriggerOccupancy::default())
+            .insert(Collider::cuboid(1.0, 1.0, 1.0))
+            .insert(Sensor)
+            .insert(ActiveEvents::COLLISION_EVENTS)
+            .id();
+        (trigger, position)
+    }
+
+    fn spawn_box(game: &mut TestGame, name: &str, position: Vec3) -> Entity {
+        game.app
+            .world
+            .spawn()
+            .insert_bundle(TransformBundle::from(Transform::from_translation(position)))
+            .insert(Name::new(name.to_string()))
+            .insert(RigidBody::Dynamic)
+            .insert(Collider::cuboid(0.2, 0.2, 0.2))
+            // Stays where it's put
+            .insert(GravityScale(0.0))
+            .id()
+    }
+
+    fn move_box(game: &mut TestGame, entity: Entity, position: Vec3) {
+        game.app
+            .world
+            .get_mut::<Transform>(entity)
+            .unwrap()
+            .translation = position;
+    }
+
+    fn occupancy(game: &TestGame, trigger: Entity) -> usize {
+        game.app
+            .world
+            .get::<TriggerOccupancy>(trigger)
+            .unwrap()
+            .count()
+    }
+
+    #[test]
+    fn stays_occupied_until_the_last_body_leaves() {
+        let mut game = TestGame::new();
+        let (trigger, position) = spawn_trigger(&mut game, TriggerFilter::Dynamic);
+        let mut exits = ManualEventReader::<TriggerExitEvent>::default();
+
+        let a = spawn_box(&mut game, "Chair A", position + Vec3::X * 0.5);
+        let b = spawn_box(&mut game, "Chair B", position - Vec3::X * 0.5);
+        game.run_frames(5);
+        assert_eq!(occupancy(&game, trigger), 2);
+
+        move_box(&mut game, a, position + Vec3::Y * 10.0);
+        game.run_frames(5);
+        assert_eq!(occupancy(&game, trigger), 1);
+        let events = game.resource::<Events<TriggerExitEvent>>();
+        assert_eq!(exits.iter(events).count(), 0, "exited with a body inside");
+
+        move_box(&mut game, b, position + Vec3::Y * 10.0);
+        game.run_frames(5);
+        assert_eq!(occupancy(&game, trigger), 0);
+        let events = game.resource::<Events<TriggerExitEvent>>();
+        assert_eq!(exits.iter(events).count(), 1);
+    }
+
+    #[test]
+    fn filters_bodies() {
+        let mut game = TestGame::new();
+        let (player_only, position) = spawn_trigger(&mut game, TriggerFilter::Player);
+        let (tagged, _) = spawn_trigger(&mut game, TriggerFilter::Tag(String::from("chair")));
+
+        spawn_box(&mut game, "PROP Box", position + Vec3::X * 0.5);
+        spawn_box(&mut game, "PROP Chair #chair", position - Vec3::X * 0.5);
+        game.run_frames(5);
+
+        assert_eq!(occupancy(&game, player_only), 0);
+        assert_eq!(occupancy(&game, tagged), 1);
+    }
+}
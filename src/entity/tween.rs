@@ -0,0 +1,288 @@
+//! Objects which move along a path by themselves, for ambient motion such as spinning rings.
+//!
+//! The path starts at the object's pose in the scene and goes through each waypoint in turn,
+//! reaching it `duration` seconds after the one before. Waypoints are offsets from the starting
+//! pose, with the rotation in degrees about x, y and z:
+//!
+//! ```text
+//! TWEEN Fan {"mode": "Loop", "waypoints": [{"rotation": [0.0, 360.0, 0.0], "duration": 2.0}]}
+//! TWEEN Sign {"mode": "PingPong", "waypoints": [{"translation": [0.0, 0.2, 0.0], "duration": 1.5, "easing": "EaseInOut"}]}
+//! ```
+//!
+//! A looping path jumps back to the start after the last waypoint, so end it where it started,
+//! which includes whole turns.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_fps_controller::controller::TIMESTEP;
+use serde::{Deserialize, Serialize};
+
+use crate::{entity::error::EntityError, spawn_from_scene};
+
+/// How something speeds up and slows down on the way between two points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+pub enum Easing {
+    Linear,
+    /// Starts slow
+    EaseIn,
+    /// Ends slow
+    EaseOut,
+    /// Starts and ends slow
+    EaseInOut,
+    /// Starts slower than [`Easing::EaseIn`]
+    CubicIn,
+    /// Ends slower than [`Easing::EaseOut`]
+    CubicOut,
+    /// Starts and ends slower than [`Easing::EaseInOut`]
+    CubicInOut,
+    /// Bounces to a stop at the end
+    Bounce,
+    /// Overshoots the end and settles back
+    Spring,
+}
+
+impl Easing {
+    /// Map linear progress from 0 to 1 onto the curve. [`Easing::Spring`] goes past 1 on the
+    /// way.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::CubicInOut => 1.0 - (2.0 - 2.0 * t).powi(3) / 2.0,
+            Easing::Bounce => bounce(t),
+            Easing::Spring if t >= 1.0 => 1.0,
+            Easing::Spring => 1.0 - (-6.0 * t).exp() * (3.0 * PI * t).cos() * (1.0 - t),
+        }
+    }
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+/// Three bounces, each a quarter the height of the last.
+fn bounce(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+/// What a [`Tween`] does once it reaches the last waypoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+pub enum TweenMode {
+    /// Stops there
+    Once,
+    /// Starts again from the beginning
+    Loop,
+    /// Goes back along the path to the start, then forwards again
+    PingPong,
+}
+
+impl Default for TweenMode {
+    fn default() -> Self {
+        TweenMode::Loop
+    }
+}
+
+/// One point on a [`Tween`]'s path, relative to where it started.
+#[derive(Clone, Copy, Debug, Default, Reflect, FromReflect, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Waypoint {
+    pub translation: Vec3,
+    /// Degrees about x, y then z
+    pub rotation: Vec3,
+    /// Seconds from the waypoint before
+    pub duration: f32,
+    /// Easing on the way here from the waypoint before
+    pub easing: Easing,
+}
+
+#[derive(Clone, Debug, Component, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+#[serde(default)]
+pub struct Tween {
+    pub waypoints: Vec<Waypoint>,
+    pub mode: TweenMode,
+    /// Multiplies how fast it moves along the path, negative to go backwards
+    pub speed: f32,
+    pub playing: bool,
+    /// Seconds along the path
+    pub time: f32,
+    #[serde(skip)]
+    pub origin: Transform,
+}
+
+impl Default for Tween {
+    fn default() -> Self {
+        Self {
+            waypoints: Vec::new(),
+            mode: TweenMode::default(),
+            speed: 1.0,
+            playing: true,
+            time: 0.0,
+            origin: Transform::identity(),
+        }
+    }
+}
+
+impl Tween {
+    /// Seconds from the start to the last waypoint.
+    pub fn duration(&self) -> f32 {
+        self.waypoints
+            .iter()
+            .map(|waypoint| waypoint.duration)
+            .sum()
+    }
+
+    /// Offset and rotation from the starting pose `time` seconds along the path, after applying
+    /// the [`TweenMode`].
+    pub fn sample(&self, time: f32) -> (Vec3, Quat) {
+        let duration = self.duration();
+        let mut time = match self.mode {
+            _ if duration <= 0.0 => duration,
+            TweenMode::Once => time.clamp(0.0, duration),
+            TweenMode::Loop => time.rem_euclid(duration),
+            TweenMode::PingPong => duration - (time.rem_euclid(2.0 * duration) - duration).abs(),
+        };
+
+        let mut from = Waypoint::default();
+        let mut to = from;
+        let mut t = 1.0;
+        for waypoint in &self.waypoints {
+            to = *waypoint;
+            if time <= waypoint.duration {
+                t = if waypoint.duration > 0.0 {
+                    time / waypoint.duration
+                } else {
+                    1.0
+                };
+                break;
+            }
+            time -= waypoint.duration;
+            from = *waypoint;
+        }
+
+        let t = to.easing.apply(t);
+        let translation = from.translation.lerp(to.translation, t);
+        let rotation = from.rotation.lerp(to.rotation, t);
+        let rotation = Quat::from_euler(
+            EulerRot::XYZ,
+            rotation.x.to_radians(),
+            rotation.y.to_radians(),
+            rotation.z.to_radians(),
+        );
+        (translation, rotation)
+    }
+}
+
+spawn_from_scene!(tween, Tween, |_cmds, entity, tween| {
+    let trans = entity
+        .get::<Transform>()
+        .ok_or(EntityError::MissingTransform)?;
+    tween.origin = *trans;
+    Ok(())
+});
+
+/// Runs each controller tick so the player riding a tween moves in step with it.
+pub(super) fn update_tweens(mut tweens: Query<(&mut Transform, &mut Tween)>) {
+    for (mut trans, mut tween) in &mut tweens {
+        // Mesh children are named after their parent, but only the parent has the path
+        if !tween.playing || tween.waypoints.is_empty() {
+            continue;
+        }
+        tween.time += tween.speed * TIMESTEP as f32;
+        if tween.mode == TweenMode::Once {
+            let duration = tween.duration();
+            tween.time = tween.time.clamp(0.0, duration);
+            if tween.time == duration || (tween.time == 0.0 && tween.speed < 0.0) {
+                tween.playing = false;
+            }
+        }
+
+        let (offset, rotation) = tween.sample(tween.time);
+        let origin = tween.origin;
+        trans.translation = origin.translation + origin.compute_matrix().transform_vector3(offset);
+        trans.rotation = origin.rotation * rotation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easings_start_and_end_in_place() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+            Easing::CubicIn,
+            Easing::CubicOut,
+            Easing::CubicInOut,
+            Easing::Bounce,
+            Easing::Spring,
+        ] {
+            assert!(easing.apply(0.0).abs() < 1e-6, "{easing:?}");
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{easing:?}");
+        }
+        assert!((Easing::CubicInOut.apply(0.5) - 0.5).abs() < 1e-6);
+        assert!(Easing::Spring.apply(0.3) > 1.0);
+    }
+
+    #[test]
+    fn follows_waypoints_in_each_mode() {
+        let mut tween = Tween {
+            waypoints: vec![
+                Waypoint {
+                    translation: Vec3::X,
+                    duration: 1.0,
+                    ..default()
+                },
+                Waypoint {
+                    translation: Vec3::X + Vec3::Y,
+                    rotation: Vec3::new(0.0, 90.0, 0.0),
+                    duration: 2.0,
+                    ..default()
+                },
+            ],
+            mode: TweenMode::Once,
+            ..default()
+        };
+        let at = |tween: &Tween, time: f32| tween.sample(time).0;
+
+        assert!(at(&tween, 0.5).abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-6));
+        assert!(at(&tween, 2.0).abs_diff_eq(Vec3::new(1.0, 0.5, 0.0), 1e-6));
+        let (_, rotation) = tween.sample(3.0);
+        assert!((rotation * Vec3::X).abs_diff_eq(-Vec3::Z, 1e-6));
+
+        tween.mode = TweenMode::Loop;
+        assert!(at(&tween, 3.5).abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-6));
+        tween.mode = TweenMode::PingPong;
+        assert!(at(&tween, 4.0).abs_diff_eq(Vec3::new(1.0, 0.5, 0.0), 1e-6));
+        assert!(at(&tween, -0.5).abs_diff_eq(Vec3::new(0.5, 0.0, 0.0), 1e-6));
+        tween.mode = TweenMode::Once;
+        assert!(at(&tween, -1.0).abs_diff_eq(Vec3::ZERO, 1e-6));
+        assert!(at(&tween, 10.0).abs_diff_eq(Vec3::X + Vec3::Y, 1e-6));
+    }
+}
@@ -1,48 +1,118 @@
 use bevy::{prelude::*, ui::FocusPolicy, window::WindowResized};
 use bevy_kira_audio::{prelude::Audio, AudioControl};
 use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    assets::{GameState, ImageAssets, SoundAssets},
+    assets::{FontAssets, GameState, SoundAssets},
     get_display_scale,
+    item::{ItemAddedEvent, ItemDefinition, Items},
+    menu::GameSettings,
 };
 
 pub struct InventoryPlugin;
 
 impl Plugin for InventoryPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.init_resource::<Inventory>();
         app.add_enter_system(GameState::RunLevel, create_inventory_toolbar_ui);
         app.add_system_set(
             ConditionSet::new()
                 .run_in_state(GameState::RunLevel)
                 .with_system(update_inventory_toolbar_ui)
                 .with_system(resize_inventory_toolbar_ui)
+                .with_system(item_added_sound)
                 .into(),
         );
     }
 }
 
-#[derive(Default, PartialEq, Eq)]
+/// The items the player holds, in the order they were picked up. Change it through
+/// [`Items`] so the change is announced.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Inventory {
-    pub key: bool,
-    pub money: bool,
+    pub slots: Vec<ItemStack>,
+}
+
+/// One toolbar slot.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+impl Inventory {
+    /// How many of an item are held across all slots.
+    pub fn count(&self, item: &str) -> u32 {
+        self.slots
+            .iter()
+            .filter(|stack| stack.item == item)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    pub fn has(&self, item: &str) -> bool {
+        self.count(item) > 0
+    }
+
+    /// Add `count` of an item, stackable items share a slot and others take one each.
+    pub fn add(&mut self, item: &ItemDefinition, count: u32) {
+        if item.stackable {
+            match self.slots.iter_mut().find(|stack| stack.item == item.id) {
+                Some(stack) => stack.count += count,
+                None => self.slots.push(ItemStack {
+                    item: item.id.clone(),
+                    count,
+                }),
+            }
+        } else {
+            self.slots.extend((0..count).map(|_| ItemStack {
+                item: item.id.clone(),
+                count: 1,
+            }));
+        }
+    }
+
+    /// Remove `count` of an item starting from the last slot, returns `false` without removing
+    /// anything if there aren't that many.
+    pub fn remove(&mut self, item: &str, mut count: u32) -> bool {
+        if self.count(item) < count {
+            return false;
+        }
+        for stack in self.slots.iter_mut().rev() {
+            if count == 0 {
+                break;
+            }
+            if stack.item == item {
+                let taken = stack.count.min(count);
+                stack.count -= taken;
+                count -= taken;
+            }
+        }
+        self.slots.retain(|stack| stack.count > 0);
+        true
+    }
 }
 
 #[derive(Component)]
 struct InventoryUiContainer;
 
+/// Holds a slot for each [`ItemStack`], rebuilt whenever the inventory changes.
 #[derive(Component)]
-pub struct Icon(&'static str);
+struct InventoryToolbar;
+
+/// Part of an item's slot in the toolbar, its icon or count.
+#[derive(Component)]
+pub struct Icon(pub String);
 
 fn create_inventory_toolbar_ui(
     mut commands: Commands,
     windows: Res<Windows>,
-    image_assets: Res<ImageAssets>,
+    game_settings: Res<GameSettings>,
 ) {
     let window = windows.get_primary().unwrap();
 
     let scale = get_display_scale(
+        game_settings.render_scale,
         window.physical_width() as f32,
         window.physical_height() as f32,
     );
@@ -74,30 +144,60 @@ fn create_inventory_toolbar_ui(
                             Val::Px(20.0),
                             Val::Px(20.0),
                         ),
+                        flex_wrap: FlexWrap::Wrap,
                         ..default()
                     },
                     color: Color::NONE.into(),
                     focus_policy: FocusPolicy::Pass,
                     ..default()
                 })
-                .with_children(|parent| {
-                    parent
-                        .spawn_bundle(ImageBundle {
-                            style: Style {
-                                size: Size::new(Val::Px(42.0), Val::Px(42.0)),
-                                margin: UiRect::new(
-                                    Val::Px(10.0),
-                                    Val::Px(10.0),
-                                    Val::Px(10.0),
-                                    Val::Px(10.0),
-                                ),
-                                ..default()
-                            },
-                            image: image_assets.key.clone().into(),
-                            visibility: Visibility { is_visible: false },
-                            ..default()
-                        })
-                        .insert(Icon("key"));
+                .insert(InventoryToolbar);
+        })
+        .insert(InventoryUiContainer);
+}
+
+fn resize_inventory_toolbar_ui(
+    mut ui: Query<&mut Style, With<InventoryUiContainer>>,
+    mut window_resized_events: EventReader<WindowResized>,
+    game_settings: Res<GameSettings>,
+) {
+    if let Some(event) = window_resized_events.iter().last() {
+        for mut style in ui.iter_mut() {
+            let scale = get_display_scale(game_settings.render_scale, event.width, event.height);
+            style.size = Size::new(Val::Px(scale.x), Val::Px(scale.y));
+        }
+    }
+}
+
+fn update_inventory_toolbar_ui(
+    mut cmds: Commands,
+    items: Items,
+    toolbar: Query<Entity, With<InventoryToolbar>>,
+    asset_server: Res<AssetServer>,
+    font_assets: Res<FontAssets>,
+) {
+    if !items.is_changed() {
+        return;
+    }
+
+    for toolbar in &toolbar {
+        cmds.entity(toolbar)
+            .despawn_descendants()
+            .with_children(|parent| {
+                for stack in &items.inventory().slots {
+                    let icon: Handle<Image> = match items.definition(&stack.item) {
+                        Some(definition) => asset_server.load(definition.icon.as_str()),
+                        None => {
+                            warn!(item = %stack.item, "No definition for held item");
+                            continue;
+                        }
+                    };
+                    // Dim everything but the item the player would use
+                    let color = if items.selected() == Some(stack.item.as_str()) {
+                        Color::WHITE
+                    } else {
+                        Color::rgba(1.0, 1.0, 1.0, 0.4)
+                    };
                     parent
                         .spawn_bundle(ImageBundle {
                             style: Style {
@@ -110,58 +210,91 @@ fn create_inventory_toolbar_ui(
                                 ),
                                 ..default()
                             },
-                            image: image_assets.money.clone().into(),
-                            visibility: Visibility { is_visible: false },
+                            image: icon.into(),
+                            color: color.into(),
                             ..default()
                         })
-                        .insert(Icon("money"));
-                });
-        })
-        .insert(InventoryUiContainer);
-}
-
-fn resize_inventory_toolbar_ui(
-    mut ui: Query<&mut Style, With<InventoryUiContainer>>,
-    mut window_resized_events: EventReader<WindowResized>,
-) {
-    if let Some(event) = window_resized_events.iter().last() {
-        for mut style in ui.iter_mut() {
-            let scale = get_display_scale(event.width, event.height);
-            style.size = Size::new(Val::Px(scale.x), Val::Px(scale.y));
-        }
+                        .insert(Icon(stack.item.clone()))
+                        .with_children(|parent| {
+                            if stack.count > 1 {
+                                parent
+                                    .spawn_bundle(
+                                        TextBundle::from_section(
+                                            stack.count.to_string(),
+                                            TextStyle {
+                                                font: font_assets.fira_mono_medium.clone(),
+                                                font_size: 16.0,
+                                                color: Color::WHITE,
+                                            },
+                                        )
+                                        .with_style(
+                                            Style {
+                                                position_type: PositionType::Absolute,
+                                                position: UiRect {
+                                                    bottom: Val::Px(-4.0),
+                                                    right: Val::Px(-4.0),
+                                                    ..default()
+                                                },
+                                                ..default()
+                                            },
+                                        ),
+                                    )
+                                    .insert(Icon(stack.item.clone()));
+                            }
+                        });
+                }
+            });
     }
 }
 
-fn update_inventory_toolbar_ui(
-    inventory: Res<Inventory>,
-    mut icons: Query<(&Icon, &mut Visibility)>,
+fn item_added_sound(
+    mut item_added_events: EventReader<ItemAddedEvent>,
     audio: Res<Audio>,
     sound_assets: Res<SoundAssets>,
 ) {
-    if inventory.is_changed() {
-        let mut item_picked_up = false;
-
-        macro_rules! set_icon_visible {
-            ($name:literal, $value:expr) => {{
-                let mut visibility = icons.iter_mut().find(|icon| icon.0 .0 == $name).unwrap().1;
-                let is_visible = visibility.is_visible;
-                visibility.is_visible = $value;
-                let changed = is_visible != $value;
-                if is_visible != $value && $value {
-                    item_picked_up = true
-                }
-                changed
-            }};
-        }
+    // TODO only make key sound for keys
+    if item_added_events.iter().last().is_some() {
+        audio
+            .play(sound_assets.keys_pickup.clone())
+            .with_volume(0.2);
+    }
+}
 
-        set_icon_visible!("key", inventory.key);
-        set_icon_visible!("money", inventory.money);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // TODO only make key sound for keys
-        if item_picked_up {
-            audio
-                .play(sound_assets.keys_pickup.clone())
-                .with_volume(0.2);
+    fn item(id: &str, stackable: bool) -> ItemDefinition {
+        ItemDefinition {
+            id: id.to_string(),
+            name: id.to_string(),
+            icon: String::new(),
+            stackable,
+            description: String::new(),
         }
     }
+
+    #[test]
+    fn stacks_stackable_items() {
+        let mut inventory = Inventory::default();
+        inventory.add(&item("coin", true), 2);
+        inventory.add(&item("key", false), 2);
+        inventory.add(&item("coin", true), 3);
+
+        assert_eq!(inventory.slots.len(), 3);
+        assert_eq!(inventory.count("coin"), 5);
+        assert_eq!(inventory.count("key"), 2);
+
+        assert!(!inventory.remove("coin", 6));
+        assert_eq!(inventory.count("coin"), 5);
+        assert!(inventory.remove("key", 1));
+        assert!(inventory.remove("coin", 5));
+        assert_eq!(
+            inventory.slots,
+            [ItemStack {
+                item: "key".to_string(),
+                count: 1
+            }]
+        );
+    }
 }
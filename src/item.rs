@@ -0,0 +1,241 @@
+//! Items the player can hold, defined in `assets/data/game.items` rather than in code.
+//!
+//! Everything which changes the [`Inventory`] goes through [`Items`], so the change is announced
+//! with an [`ItemAddedEvent`], [`ItemRemovedEvent`] or [`ItemUsedEvent`].
+//!
+//! The player picks one held item as the [`SelectedItem`] to use on
+//! [`ItemTarget`](crate::entity::item_target::ItemTarget)s.
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use iyes_loopless::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    actions::Action,
+    assets::{DataAssets, GameState},
+    inventory::Inventory,
+};
+
+pub struct ItemPlugin;
+
+impl Plugin for ItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ItemDefinitions>()
+            .init_asset_loader::<ItemDefinitionsLoader>()
+            .init_resource::<Inventory>()
+            .init_resource::<SelectedItem>()
+            .add_event::<ItemAddedEvent>()
+            .add_event::<ItemRemovedEvent>()
+            .add_event::<ItemUsedEvent>()
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::RunLevel)
+                    .with_system(cycle_selected_item)
+                    .into(),
+            );
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ItemDefinition {
+    /// What logic graphs, pickups and saves call the item
+    pub id: String,
+    /// Shown to the player
+    pub name: String,
+    /// Image path relative to the assets folder
+    pub icon: String,
+    /// Whether copies of the item share an inventory slot
+    #[serde(default)]
+    pub stackable: bool,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "5d3f3c1e-52a8-4c0b-9a8e-2a47c1f0b6d4"]
+pub struct ItemDefinitions {
+    pub items: Vec<ItemDefinition>,
+}
+
+impl ItemDefinitions {
+    pub fn get(&self, id: &str) -> Option<&ItemDefinition> {
+        self.items.iter().find(|item| item.id == id)
+    }
+}
+
+#[derive(Default)]
+pub struct ItemDefinitionsLoader;
+
+impl AssetLoader for ItemDefinitionsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let definitions = serde_json::from_slice::<ItemDefinitions>(bytes)?;
+            // Load the icons along with the definitions so the toolbar doesn't pop in
+            let icons = definitions
+                .items
+                .iter()
+                .map(|item| AssetPath::from(item.icon.as_str()).to_owned())
+                .collect();
+            load_context.set_default_asset(LoadedAsset::new(definitions).with_dependencies(icons));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["items"]
+    }
+}
+
+pub struct ItemAddedEvent {
+    pub item: String,
+    pub count: u32,
+}
+
+pub struct ItemRemovedEvent {
+    pub item: String,
+    pub count: u32,
+}
+
+/// An item was used on something, it's removed first if it was used up.
+pub struct ItemUsedEvent {
+    pub item: String,
+}
+
+/// Id of the held item the player would use, not saved.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SelectedItem(pub Option<String>);
+
+/// Read and change the [`Inventory`], sending events for every change.
+#[derive(SystemParam)]
+pub struct Items<'w, 's> {
+    inventory: ResMut<'w, Inventory>,
+    selected: ResMut<'w, SelectedItem>,
+    definitions: Res<'w, Assets<ItemDefinitions>>,
+    data_assets: Res<'w, DataAssets>,
+    added_events: EventWriter<'w, 's, ItemAddedEvent>,
+    removed_events: EventWriter<'w, 's, ItemRemovedEvent>,
+    used_events: EventWriter<'w, 's, ItemUsedEvent>,
+}
+
+impl<'w, 's> Items<'w, 's> {
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    /// Whether the inventory or selection changed since the system last ran.
+    pub fn is_changed(&self) -> bool {
+        self.inventory.is_changed() || self.selected.is_changed()
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.0.as_deref()
+    }
+
+    pub fn definition(&self, id: &str) -> Option<&ItemDefinition> {
+        self.definitions.get(&self.data_assets.items)?.get(id)
+    }
+
+    /// Add `count` of an item, selecting it if nothing is. Returns `false` if there's no such
+    /// item.
+    pub fn give(&mut self, id: &str, count: u32) -> bool {
+        let definition = match self
+            .definitions
+            .get(&self.data_assets.items)
+            .and_then(|definitions| definitions.get(id))
+        {
+            Some(definition) => definition,
+            None => return false,
+        };
+        self.inventory.add(definition, count);
+        if self.selected.0.is_none() {
+            self.selected.0 = Some(id.to_string());
+        }
+        debug!(item = id, count, "Item added");
+        self.added_events.send(ItemAddedEvent {
+            item: id.to_string(),
+            count,
+        });
+        true
+    }
+
+    /// Remove `count` of an item, returns `false` and leaves the inventory as it was if there
+    /// aren't that many.
+    pub fn take(&mut self, id: &str, count: u32) -> bool {
+        if !self.inventory.remove(id, count) {
+            return false;
+        }
+        if !self.inventory.has(id) && self.selected() == Some(id) {
+            self.selected.0 = None;
+        }
+        debug!(item = id, count, "Item removed");
+        self.removed_events.send(ItemRemovedEvent {
+            item: id.to_string(),
+            count,
+        });
+        true
+    }
+
+    /// Use an item the player holds, removing one if `consume`. Returns `false` if they don't
+    /// hold it.
+    pub fn use_item(&mut self, id: &str, consume: bool) -> bool {
+        let held = if consume {
+            self.take(id, 1)
+        } else {
+            self.inventory.has(id)
+        };
+        if held {
+            debug!(item = id, consume, "Item used");
+            self.used_events.send(ItemUsedEvent {
+                item: id.to_string(),
+            });
+        }
+        held
+    }
+}
+
+/// Step the [`SelectedItem`] through the held items in toolbar order.
+fn cycle_selected_item(
+    actions: Res<Input<Action>>,
+    inventory: Res<Inventory>,
+    mut selected: ResMut<SelectedItem>,
+) {
+    let step: isize = match (
+        actions.just_pressed(Action::NextItem),
+        actions.just_pressed(Action::PreviousItem),
+    ) {
+        (true, false) => 1,
+        (false, true) => -1,
+        _ => return,
+    };
+
+    let mut held: Vec<&str> = Vec::new();
+    for stack in &inventory.slots {
+        if !held.contains(&stack.item.as_str()) {
+            held.push(&stack.item);
+        }
+    }
+    if held.is_empty() {
+        return;
+    }
+
+    let current = selected
+        .0
+        .as_deref()
+        .and_then(|item| held.iter().position(|held| *held == item));
+    let next = match current {
+        Some(current) => (current as isize + step).rem_euclid(held.len() as isize) as usize,
+        None if step > 0 => 0,
+        None => held.len() - 1,
+    };
+    selected.0 = Some(held[next].to_string());
+}
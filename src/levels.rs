@@ -3,7 +3,10 @@ use std::time::Duration;
 use bevy::{prelude::*, utils::HashSet};
 use iyes_loopless::prelude::*;
 
-use crate::assets::{GameState, SoundAssets};
+use crate::{
+    assets::{GameState, SoundAssets},
+    audio::MusicChannel,
+};
 
 use self::{
     elevator::{ElevatorPlugin, ElevatorScene},
@@ -14,7 +17,8 @@ use self::{
     level5_garage_lobby::Level5GarageLobbyPlugin,
 };
 
-use bevy_kira_audio::{prelude::Audio, AudioControl, AudioInstance, AudioTween};
+use bevy_kira_audio::{prelude::AudioChannel, AudioControl, AudioInstance, AudioTween};
+use serde::{Deserialize, Serialize};
 
 pub mod elevator;
 pub mod level1_garage;
@@ -46,7 +50,7 @@ impl Plugin for LevelsPlugin {
     }
 }
 
-#[derive(Component, Clone, Copy, Eq, PartialEq, Debug, Hash)]
+#[derive(Component, Clone, Copy, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
 pub enum Level {
     None,
     Level1Garage,
@@ -60,7 +64,7 @@ pub enum Level {
 #[derive(Component)]
 struct LevelEntity;
 
-struct SelectedLevel(Level);
+pub struct SelectedLevel(pub Level);
 
 pub struct UnlockedLevels(pub HashSet<Level>);
 
@@ -77,7 +81,7 @@ fn change_level(
     mut cmds: Commands,
     level: Res<Level>,
     scenes: Query<Entity, (With<Handle<Scene>>, Without<ElevatorScene>)>,
-    audio: Res<Audio>,
+    audio: Res<AudioChannel<MusicChannel>>,
     sound_assets: Res<SoundAssets>,
     mut drones: Local<Vec<Handle<AudioInstance>>>,
     mut audio_instances: ResMut<Assets<AudioInstance>>,
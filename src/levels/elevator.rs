@@ -1,28 +1,15 @@
 #![allow(clippy::type_complexity)]
-use crate::assets::SoundAssets;
-use crate::entity::NamedIterator;
-use crate::materials::general::GeneralMaterial;
-use crate::{
-    assets::{GameState, ModelAssets},
-    entity::{button::NamedButtonStatuses, door_linear::DoorLinear, trigger::NamedTriggerStatuses},
-};
+use crate::assets::{GameState, ModelAssets};
 use bevy::prelude::*;
-use bevy_kira_audio::{prelude::Audio, AudioControl};
 use iyes_loopless::prelude::*;
 
-use super::{Level, SelectedLevel};
+use super::Level;
 
+/// Spawns the elevator, its doors and button are handled by the elevator logic graph.
 pub struct ElevatorPlugin;
 impl Plugin for ElevatorPlugin {
     fn build(&self, app: &mut App) {
         app.add_enter_system(GameState::RunLevel, setup);
-        app.add_system_set(
-            ConditionSet::new()
-                .run_in_state(GameState::RunLevel)
-                .with_system(doors)
-                //.with_system(buttons)
-                .into(),
-        );
     }
 }
 
@@ -38,78 +25,45 @@ fn setup(mut cmds: Commands, model_assets: Res<ModelAssets>) {
     cmds.insert_resource(NextState(Level::Level1Garage));
 }
 
-fn doors(
-    mut materials: Query<(&Name, &Handle<GeneralMaterial>)>,
-    mut general_mats: ResMut<Assets<GeneralMaterial>>,
-    mut doors: Query<(&Name, &mut DoorLinear)>,
-    triggers: Res<NamedTriggerStatuses>,
-    buttons: Res<NamedButtonStatuses>,
-    //mut door_fully_closed_events: EventReader<DoorFullyClosedEvent>,
-    mut inside_elevator: Local<bool>,
-    mut outside_elevator: Local<bool>,
-    mut inside_near_door: Local<bool>,
-    mut level: ResMut<Level>,
-    selected_level: Res<SelectedLevel>,
-    audio: Res<Audio>,
-    sound_assets: Res<SoundAssets>,
-) {
-    if triggers.is_changed() {
-        if let Some(status) = triggers.any("Elevator Inside Main") {
-            *inside_elevator = status.player_is_inside;
-        }
+#[cfg(test)]
+mod tests {
+    use crate::{
+        entity::{door_linear::DoorLinear, NamedIterator},
+        test_harness::TestGame,
+    };
 
-        if let Some(status) = triggers.any("Elevator Inside Near Door") {
-            *inside_near_door = status.player_is_inside;
-        }
+    use super::*;
 
-        if let Some(status) = triggers.any("Elevator Outside") {
-            *outside_elevator = status.player_is_inside;
-        }
+    #[test]
+    fn doors_open_when_walking_up() {
+        let mut game = TestGame::new();
+        assert!(game.door_state("Elevator Door").is_closed());
 
-        for (_, mut door) in doors.iter_mut().filter_name_contains("Elevator Door") {
-            if *outside_elevator || *inside_near_door {
-                door.state.open();
-            } else {
-                door.state.close();
-            }
-        }
+        game.teleport_into("TRIGGER Elevator Outside");
+        assert!(game.wait_for_trigger("Elevator Outside", 30));
+        game.run_frames(5);
+
+        assert!(game.trigger_occupied("TRIGGER Elevator Outside"));
+        let state = game.door_state("Elevator Door");
+        assert!(state.is_opening() || state.is_open(), "{state:?}");
     }
 
-    if *inside_elevator {
-        if let Some(event) = buttons.any("BUTTON Elevator Inside") {
-            for (_, mat_h) in materials
-                .iter_mut()
-                .filter_name_contains("Elevator Buttons")
-            {
-                if let Some(mut mat) = general_mats.get_mut(mat_h) {
-                    if event.hovered {
-                        mat.highlight = Color::rgba(0.7, 0.7, 0.7, 1.0);
-                    } else {
-                        mat.highlight = Color::BLACK;
-                    }
-                }
-            }
-            if event.pressed
-                && doors
-                    .iter()
-                    .filter_name_contains("Elevator Door")
-                    .next()
-                    .unwrap()
-                    .1
-                    .state
-                    .is_closed()
-            {
-                audio
-                    .play(sound_assets.elevator_transport.clone())
-                    .with_volume(0.7);
-                audio.play(sound_assets.click.clone()).with_volume(0.3);
-                if *level == Level::Level1Garage {
-                    *level = selected_level.0;
-                } else {
-                    *level = Level::Level1Garage;
-                }
-                debug!(?level, "Change level");
-            }
-        }
+    #[test]
+    fn button_goes_to_the_selected_level() {
+        let mut game = TestGame::new();
+
+        game.teleport_into("TRIGGER Elevator Inside Main");
+        assert!(game.wait_for_trigger("Elevator Inside Main", 30));
+        let closed = game.run_until(600, |world| {
+            world
+                .query::<(&Name, &DoorLinear)>()
+                .iter(world)
+                .filter_name_contains("Elevator Door")
+                .all(|(_, door)| door.state.is_closed())
+        });
+        assert!(closed, "the doors didn't close");
+
+        game.click("BUTTON Elevator Inside");
+        assert_eq!(*game.resource::<Level>(), Level::Level2Lobby);
     }
 }
@@ -1,21 +1,20 @@
 use crate::{
-    assets::{ModelAssets, SoundAssets},
+    assets::ModelAssets,
     entity::{
-        button::NamedButtonStatuses, door_linear::DoorLinear, trigger::Trigger, NamedIterator,
+        button::{Button, ButtonHighlight},
+        name::NameQuery,
+        trigger::{Trigger, TriggerOccupancy},
+        NamedIterator,
     },
-    inventory::Inventory,
-    materials::general::GeneralMaterial,
-    notification::NotificationText,
+    logic_graph::LogicFlags,
     scene_hook::{HookedSceneBundle, SceneHook},
+    scene_lint::NameLookup,
 };
 use bevy::prelude::*;
-use bevy_kira_audio::{prelude::Audio, AudioControl};
 use bevy_rapier3d::prelude::{Collider, Sensor};
 use iyes_loopless::prelude::*;
 
-use super::{
-    level2_lobby::GarageOpened, level3_chair::RingsSetup, Level, SelectedLevel, UnlockedLevels,
-};
+use super::{level2_lobby::GARAGE_OPENED_FLAG, Level, SelectedLevel, UnlockedLevels};
 
 pub struct Level1GaragePlugin;
 impl Plugin for Level1GaragePlugin {
@@ -25,13 +24,37 @@ impl Plugin for Level1GaragePlugin {
             ConditionSet::new()
                 .run_in_state(Level::Level1Garage)
                 .with_system(vending_machine)
-                .with_system(ring_switches)
-                .with_system(open_garage_door)
+                .with_system(open_garage_exit)
                 .into(),
         );
     }
 }
 
+/// Vending machine buttons and the levels they select.
+const VENDING_BUTTONS: [(NameQuery<'static>, Level); 4] = [
+    (NameQuery::exact("BUTTON vending 1"), Level::Level2Lobby),
+    (NameQuery::exact("BUTTON vending 2"), Level::Level3Chair),
+    (
+        NameQuery::exact("BUTTON vending 3"),
+        Level::Level4ChairsPile,
+    ),
+    (
+        NameQuery::exact("BUTTON vending 4"),
+        Level::Level5GarageLobby,
+    ),
+];
+
+const GARAGE_EXIT: NameQuery<'static> = NameQuery::contains("BLOCK Garage Exit");
+
+/// Names looked up by this level's systems, checked by [`crate::scene_lint`].
+pub const NAME_LOOKUPS: &[NameLookup] = &[
+    NameLookup::First(VENDING_BUTTONS[0].0),
+    NameLookup::First(VENDING_BUTTONS[1].0),
+    NameLookup::First(VENDING_BUTTONS[2].0),
+    NameLookup::First(VENDING_BUTTONS[3].0),
+    NameLookup::All(GARAGE_EXIT),
+];
+
 fn setup(mut cmds: Commands, model_assets: Res<ModelAssets>) {
     cmds.spawn_bundle(HookedSceneBundle {
         scene: SceneBundle {
@@ -53,162 +76,149 @@ fn setup(mut cmds: Commands, model_assets: Res<ModelAssets>) {
     });
 }
 
+// Selecting a level is handled by the garage logic graph, this shows which levels can be
+// selected and disables the buttons of locked ones, so they don't react to money at all
 fn vending_machine(
-    mut materials: Query<(&Name, &Handle<GeneralMaterial>)>,
-    mut general_mats: ResMut<Assets<GeneralMaterial>>,
-    buttons: Res<NamedButtonStatuses>,
-    mut selected_level: ResMut<SelectedLevel>,
-    inventory: Res<Inventory>,
+    mut buttons: Query<(&Name, &mut Button, &mut ButtonHighlight)>,
+    selected_level: Res<SelectedLevel>,
     unlocked_levels: Res<UnlockedLevels>,
-    mut texts: Query<(&mut Text, &mut NotificationText)>,
-    audio: Res<Audio>,
-    sound_assets: Res<SoundAssets>,
 ) {
-    for (btn_name, btn_obj_name, level) in [
-        (
-            "BUTTON vending 1",
-            "vending machine button 1",
-            Level::Level2Lobby,
-        ),
-        (
-            "BUTTON vending 2",
-            "vending machine button 2",
-            Level::Level3Chair,
-        ),
-        (
-            "BUTTON vending 3",
-            "vending machine button 3",
-            Level::Level4ChairsPile,
-        ),
-        (
-            "BUTTON vending 4",
-            "vending machine button 4",
-            Level::Level5GarageLobby,
-        ),
-    ] {
-        let mut highlight_color = Color::BLACK;
-        if unlocked_levels.0.contains(&level) {
-            let mut hovered = false;
-            if let Some(event) = buttons.any(btn_name) {
-                if event.pressed {
-                    if inventory.money {
-                        audio.play(sound_assets.click.clone()).with_volume(0.3);
-                        selected_level.0 = level;
-                    } else {
-                        for (mut text, mut note) in &mut texts {
-                            note.0 = 8.0;
-                            if let Some(section) = text.sections.iter_mut().next() {
-                                audio.play(sound_assets.bad_click.clone()).with_volume(0.2);
-                                section.value = String::from("Insufficient\nfunds");
-                            }
-                        }
-                    }
-                }
-                hovered = true;
-            }
-            if hovered {
-                highlight_color = Color::rgba(0.5, 0.5, 0.5, 1.0);
-            } else if selected_level.0 == level {
-                highlight_color = Color::rgba(0.0, 0.3, 0.0, 1.0);
-            }
+    for (query, level) in VENDING_BUTTONS {
+        let unlocked = unlocked_levels.0.contains(&level);
+        let highlight_color = if !unlocked {
+            Color::rgba(0.9, 0.0, 0.0, 1.0)
+        } else if selected_level.0 == level {
+            Color::rgba(0.0, 0.3, 0.0, 1.0)
         } else {
-            highlight_color = Color::rgba(0.9, 0.0, 0.0, 1.0);
-        }
-        for (_, mat_h) in materials.iter_mut().filter_name_contains(btn_obj_name) {
-            if let Some(mut mat) = general_mats.get_mut(mat_h) {
-                if mat.highlight != highlight_color {
-                    mat.highlight = highlight_color;
-                }
+            Color::BLACK
+        };
+        for (_, mut button, mut highlight) in buttons.iter_mut().filter_name(query) {
+            if button.enabled != unlocked {
+                button.enabled = unlocked;
             }
-        }
-    }
-}
-
-fn ring_switches(
-    mut mat_trans: Query<(&Name, &mut Transform, &Handle<GeneralMaterial>)>,
-    mut general_mats: ResMut<Assets<GeneralMaterial>>,
-    buttons: Res<NamedButtonStatuses>,
-    mut rings_setup: ResMut<RingsSetup>,
-    audio: Res<Audio>,
-    sound_assets: Res<SoundAssets>,
-) {
-    for (btn_name, btn_obj_name) in [
-        ("BUTTON ring knob 1", "KNOB ring direction knob 1"),
-        ("BUTTON ring knob 2", "KNOB ring direction knob 2"),
-        ("BUTTON ring knob 3", "KNOB ring direction knob 3"),
-    ] {
-        let mut highlight_color = Color::rgba(0.0, 0.0, 0.0, 1.0);
-        let mut pressed = false;
-        if let Some(event) = buttons.any(btn_name) {
-            if event.hovered {
-                highlight_color = Color::rgba(0.35, 0.35, 0.35, 1.0);
-            }
-            if event.pressed {
-                pressed = true;
-            }
-        }
-        if pressed {
-            audio.play(sound_assets.click.clone()).with_volume(0.3);
-            if btn_obj_name.contains("knob 1") {
-                rings_setup.direction = !rings_setup.direction;
-            } else if btn_obj_name.contains("knob 2") {
-                rings_setup.speed = !rings_setup.speed;
-            } else if btn_obj_name.contains("knob 3") {
-                rings_setup.color = !rings_setup.color;
-            }
-        }
-        for (_, mut trans, mat_h) in mat_trans.iter_mut().filter_name_contains(btn_obj_name) {
-            // TODO don't set every frame, but handle the level being reloaded
-            if btn_obj_name.contains("knob 1") {
-                if rings_setup.direction {
-                    trans.rotation = Quat::from_euler(EulerRot::XYZ, 90.0, 0.0, 0.0)
-                } else {
-                    trans.rotation = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, 0.0)
-                }
-            } else if btn_obj_name.contains("knob 2") {
-                if rings_setup.speed {
-                    trans.rotation = Quat::from_euler(EulerRot::XYZ, 90.0, 0.0, 0.0)
-                } else {
-                    trans.rotation = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, 0.0)
-                }
-            } else if btn_obj_name.contains("knob 3") {
-                if rings_setup.color {
-                    trans.rotation = Quat::from_euler(EulerRot::XYZ, 90.0, 0.0, 0.0)
-                } else {
-                    trans.rotation = Quat::from_euler(EulerRot::XYZ, 0.0, 0.0, 0.0)
-                }
-            }
-
-            if let Some(mut mat) = general_mats.get_mut(mat_h) {
-                if mat.highlight != highlight_color {
-                    mat.highlight = highlight_color;
-                }
+            if highlight.color != highlight_color {
+                highlight.color = highlight_color;
             }
         }
     }
 }
 
-fn open_garage_door(
+// The garage gate and the ring knobs are handled by the garage logic graph. Logic graphs can't
+// rename entities or change their components, so the exit is turned into the ending's trigger
+// here once the garage is opened
+fn open_garage_exit(
     mut cmds: Commands,
-    mut named: Query<(&mut Name, Entity), Without<DoorLinear>>,
-    garage_opened: Option<Res<GarageOpened>>,
-    mut doors: Query<(&Name, &mut DoorLinear)>,
+    mut named: Query<(&mut Name, Entity)>,
+    flags: Res<LogicFlags>,
 ) {
-    if garage_opened.is_some() {
+    if flags.contains(GARAGE_OPENED_FLAG) {
         for (mut name, entity) in &mut named {
-            if name.contains("BLOCK Garage Exit") {
+            if GARAGE_EXIT.matches(name.as_str()) {
                 *name = String::from("TRIGGER End Win Area").into();
                 cmds.entity(entity)
-                    .insert(Trigger { enabled: true })
+                    .insert(Trigger::default())
+                    .insert(TriggerOccupancy::default())
                     .insert(Sensor)
                     .remove::<Collider>();
             }
         }
-        for (_, mut door) in doors
-            .iter_mut()
-            .filter_name_contains("DOOR_LINEAR Garage Gate")
-        {
-            door.state.open();
-        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        inventory::Inventory,
+        levels::level3_chair::{RING_COLOR_FLAG, RING_DIRECTION_FLAG, RING_SPEED_FLAG},
+        notification::NotificationText,
+        test_harness::TestGame,
+    };
+
+    use super::*;
+
+    #[test]
+    fn vending_machine_needs_money() {
+        let mut game = TestGame::new();
+        let notification = |game: &mut TestGame| -> String {
+            let world = &mut game.app.world;
+            world
+                .query::<(&Text, &NotificationText)>()
+                .iter(world)
+                .flat_map(|(text, _)| text.sections.first())
+                .map(|section| section.value.clone())
+                .collect()
+        };
+
+        // Levels which aren't unlocked can't be picked, with or without money
+        let enabled = game
+            .app
+            .world
+            .query::<(&Name, &Button)>()
+            .iter(&game.app.world)
+            .filter_name(VENDING_BUTTONS[2].0)
+            .all(|(_, button)| button.enabled);
+        assert!(!enabled);
+        game.click("BUTTON vending 3");
+        assert_eq!(notification(&mut game), "");
+
+        game.click("BUTTON vending 2");
+        assert_eq!(game.resource::<SelectedLevel>().0, Level::Level2Lobby);
+        assert_eq!(notification(&mut game), "Insufficient\nfunds");
+
+        game.give_item("money");
+        game.select_item("money");
+        game.click("BUTTON vending 3");
+        assert_eq!(game.resource::<SelectedLevel>().0, Level::Level2Lobby);
+        game.click("BUTTON vending 2");
+        assert_eq!(game.resource::<SelectedLevel>().0, Level::Level3Chair);
+    }
+
+    #[test]
+    fn ring_knobs_unlock_chairs_pile() {
+        let mut game = TestGame::new();
+
+        game.click("BUTTON ring knob 1");
+        game.click("BUTTON ring knob 3");
+        let flags = game.resource::<LogicFlags>();
+        assert!(flags.contains(RING_DIRECTION_FLAG));
+        assert!(!flags.contains(RING_SPEED_FLAG));
+        assert!(flags.contains(RING_COLOR_FLAG));
+        game.run_frames(30);
+        assert!(game.door_state("ring direction knob 1").is_open());
+        assert!(game.door_state("ring direction knob 2").is_closed());
+        assert!(!game
+            .resource::<UnlockedLevels>()
+            .0
+            .contains(&Level::Level4ChairsPile));
+
+        game.load_level(Level::Level3Chair);
+        assert!(game
+            .resource::<UnlockedLevels>()
+            .0
+            .contains(&Level::Level4ChairsPile));
+
+        // The knobs are turned back to the setup when coming back
+        game.load_level(Level::Level1Garage);
+        game.run_frames(30);
+        assert!(game.door_state("ring direction knob 1").is_open());
+        assert!(game.door_state("ring direction knob 2").is_closed());
+    }
+
+    #[test]
+    fn garage_gate_opens_with_key() {
+        let mut game = TestGame::new();
+        assert!(game.door_state("DOOR_LINEAR Garage Gate").is_closed());
+
+        game.load_level(Level::Level5GarageLobby);
+        game.click("BUTTON PICKUP Keys");
+        assert!(game.resource::<Inventory>().has("key"));
+
+        game.load_level(Level::Level2Lobby);
+        game.click("BUTTON Garage Key");
+
+        game.load_level(Level::Level1Garage);
+        let state = game.door_state("DOOR_LINEAR Garage Gate");
+        assert!(state.is_opening() || state.is_open(), "{state:?}");
+        assert!(game.find("TRIGGER End Win Area").is_some());
     }
 }
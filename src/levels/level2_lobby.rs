@@ -1,49 +1,32 @@
 use crate::{
-    assets::{ModelAssets, SoundAssets},
-    entity::{button::NamedButtonStatuses, phone::PhoneUiVisible, NamedIterator},
-    inventory::Inventory,
-    materials::general::GeneralMaterial,
+    assets::ModelAssets,
     scene_hook::{HookedSceneBundle, SceneHook},
 };
 use bevy::prelude::*;
-use bevy_kira_audio::{prelude::Audio, AudioControl};
 use iyes_loopless::prelude::*;
 
 use super::Level;
 
+/// Spawns the lobby, its phones and garage key button are handled by the lobby logic graph.
 pub struct Level2LobbyPlugin;
 impl Plugin for Level2LobbyPlugin {
     fn build(&self, app: &mut App) {
         app.add_enter_system(Level::Level2Lobby, setup);
-        app.add_system_set(
-            ConditionSet::new()
-                .run_in_state(Level::Level2Lobby)
-                .with_system(money)
-                .with_system(phone)
-                .with_system(garage_key)
-                .into(),
-        );
     }
 }
 
+/// Logic flag set when the key's been used on the garage key button, which opens the garage
+/// gate in [`Level::Level1Garage`].
+pub const GARAGE_OPENED_FLAG: &str = "garage opened";
+
 fn setup(mut cmds: Commands, model_assets: Res<ModelAssets>) {
     cmds.spawn_bundle(HookedSceneBundle {
         scene: SceneBundle {
             scene: model_assets.level2_lobby.clone(),
             ..default()
         },
-        hook: SceneHook::new(move |entity, world, cmds| {
+        hook: SceneHook::new(move |_entity, _world, cmds| {
             cmds.insert(Level::Level2Lobby);
-            if let Some(name) = entity.get::<Name>() {
-                if name.contains("PICKUP MESH money") {
-                    if let Some(inventory) = world.get_resource::<Inventory>() {
-                        if inventory.money {
-                            //Despawn money if we already have it
-                            cmds.despawn();
-                        }
-                    }
-                }
-            }
         }),
     });
     cmds.spawn_bundle(HookedSceneBundle {
@@ -66,107 +49,60 @@ fn setup(mut cmds: Commands, model_assets: Res<ModelAssets>) {
     });
 }
 
-fn money(
-    mut materials: Query<(&Name, &Handle<GeneralMaterial>)>,
-    mut general_mats: ResMut<Assets<GeneralMaterial>>,
-    mut cmds: Commands,
-    buttons: Res<NamedButtonStatuses>,
-    mut inventory: ResMut<Inventory>,
-    mut items: Query<(&Name, Entity), With<Handle<Mesh>>>,
-) {
-    if let Some(event) = buttons.any("BUTTON PICKUP money") {
-        for (_, mat_h) in materials
-            .iter_mut()
-            .filter_name_contains("PICKUP MESH money")
-        {
-            if let Some(mut mat) = general_mats.get_mut(mat_h) {
-                let mut highlight_color = Color::rgba(0.0, 0.0, 0.0, 1.0);
-                if event.hovered {
-                    highlight_color = Color::rgba(0.7, 0.7, 0.7, 1.0);
-                }
-                if mat.highlight != highlight_color {
-                    mat.highlight = highlight_color;
-                }
-            }
-        }
-        if event.pressed {
-            inventory.money = true;
-            for (_, entity) in items.iter_mut().filter_name_contains("PICKUP MESH money") {
-                cmds.entity(entity).despawn();
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use crate::{
+        actions::Action, entity::phone::PhoneUiVisible, inventory::Inventory,
+        logic_graph::LogicFlags, test_harness::TestGame,
+    };
+
+    use super::*;
+
+    #[test]
+    fn picks_up_money_once() {
+        let mut game = TestGame::new();
+        game.load_level(Level::Level2Lobby);
+        assert!(!game.resource::<Inventory>().has("money"));
+
+        game.click("BUTTON PICKUP money");
+        assert!(game.resource::<Inventory>().has("money"));
+        assert!(game.find("PICKUP MESH money").is_none());
+
+        // Still gone when coming back
+        game.load_level(Level::Level1Garage);
+        game.load_level(Level::Level2Lobby);
+        assert!(game.find("PICKUP MESH money").is_none());
     }
-}
 
-fn phone(
-    mut materials: Query<(&Name, &Handle<GeneralMaterial>)>,
-    mut general_mats: ResMut<Assets<GeneralMaterial>>,
-    buttons: Res<NamedButtonStatuses>,
-    mut phone_ui_visible: ResMut<PhoneUiVisible>,
-    keys: Res<Input<KeyCode>>,
-) {
-    if !**phone_ui_visible {
-        for (btn_name, mesh_name) in [
-            ("BUTTON Phone 1", "Phone Mesh 1"),
-            ("BUTTON Phone 2", "Phone Mesh 2"),
-        ] {
-            if let Some(event) = buttons.any(btn_name) {
-                for (_, mat_h) in materials.iter_mut().filter_name_contains(mesh_name) {
-                    if let Some(mut mat) = general_mats.get_mut(mat_h) {
-                        let mut highlight_color = Color::rgba(0.0, 0.0, 0.0, 1.0);
-                        if event.hovered {
-                            highlight_color = Color::rgba(0.7, 0.7, 0.7, 1.0);
-                        }
-                        if mat.highlight != highlight_color {
-                            mat.highlight = highlight_color;
-                        }
-                    }
-                }
-                if event.pressed {
-                    **phone_ui_visible = true;
-                }
-            }
-        }
-    } else if keys.just_pressed(KeyCode::W)
-        || keys.just_pressed(KeyCode::S)
-        || keys.just_pressed(KeyCode::A)
-        || keys.just_pressed(KeyCode::D)
-    {
-        **phone_ui_visible = false;
+    #[test]
+    fn garage_key_needs_key() {
+        let mut game = TestGame::new();
+        game.load_level(Level::Level2Lobby);
+
+        game.click("BUTTON Garage Key");
+        assert!(!game.resource::<LogicFlags>().contains(GARAGE_OPENED_FLAG));
+
+        // Holding the key isn't enough, it has to be the selected item
+        game.give_item("key");
+        game.click("BUTTON Garage Key");
+        assert!(!game.resource::<LogicFlags>().contains(GARAGE_OPENED_FLAG));
+
+        game.select_item("key");
+        game.click("BUTTON Garage Key");
+        assert!(game.resource::<LogicFlags>().contains(GARAGE_OPENED_FLAG));
+        assert!(game.resource::<Inventory>().has("key"));
     }
-}
 
-pub struct GarageOpened;
-
-fn garage_key(
-    mut cmds: Commands,
-    mut materials: Query<(&Name, &Handle<GeneralMaterial>)>,
-    mut general_mats: ResMut<Assets<GeneralMaterial>>,
-    buttons: Res<NamedButtonStatuses>,
-    inventory: Res<Inventory>,
-    audio: Res<Audio>,
-    sound_assets: Res<SoundAssets>,
-) {
-    if let Some(event) = buttons.any("BUTTON Garage Key") {
-        for (_, mat_h) in materials.iter_mut().filter_name_contains("Garage Key Cyl") {
-            if let Some(mut mat) = general_mats.get_mut(mat_h) {
-                let mut highlight_color = Color::rgba(0.0, 0.0, 0.0, 1.0);
-                if event.hovered {
-                    highlight_color = Color::rgba(0.7, 0.7, 0.7, 1.0);
-                }
-                if mat.highlight != highlight_color {
-                    mat.highlight = highlight_color;
-                }
-            }
-        }
-        if event.pressed {
-            if inventory.key {
-                audio.play(sound_assets.click.clone()).with_volume(0.3);
-                audio.play(sound_assets.gate.clone()).with_volume(1.0);
-                cmds.insert_resource(GarageOpened);
-            } else {
-                audio.play(sound_assets.bad_click.clone()).with_volume(0.2);
-            }
-        }
+    #[test]
+    fn phone_opens_and_closes() {
+        let mut game = TestGame::new();
+        game.load_level(Level::Level2Lobby);
+        assert!(!**game.resource::<PhoneUiVisible>());
+
+        game.click("BUTTON Phone 1");
+        assert!(**game.resource::<PhoneUiVisible>());
+
+        game.press(Action::PhoneClose);
+        assert!(!**game.resource::<PhoneUiVisible>());
     }
 }
@@ -3,11 +3,14 @@ use iyes_loopless::prelude::*;
 
 use crate::{
     assets::ModelAssets,
+    entity::{name::NameQuery, tween::Tween, NamedIterator},
+    logic_graph::LogicFlags,
     materials::rings::RingsMaterial,
     scene_hook::{HookedSceneBundle, SceneHook},
+    scene_lint::NameLookup,
 };
 
-use super::{Level, UnlockedLevels};
+use super::Level;
 
 pub struct Level3ChairPlugin;
 impl Plugin for Level3ChairPlugin {
@@ -34,59 +37,97 @@ fn setup(mut cmds: Commands, model_assets: Res<ModelAssets>) {
     });
 }
 
-#[derive(Default)]
-pub struct RingsSetup {
-    pub direction: bool,
-    pub speed: bool,
-    pub color: bool,
-}
+/// Names looked up by [`update_rings`], checked by [`crate::scene_lint`].
+pub const NAME_LOOKUPS: &[NameLookup] = &[NameLookup::All(RING1)];
 
-impl RingsSetup {
-    #[allow(unused)]
-    fn is_correct_a(&self) -> bool {
-        self.direction && !self.speed && self.color
-    }
-    fn is_correct_b(&self) -> bool {
-        !self.direction && !self.speed && self.color
-    }
-}
+const RING1: NameQuery<'static> = NameQuery::contains("Ring1");
+
+/// Logic flags for the rings' setup, toggled by the knobs in [`Level::Level1Garage`]. Which
+/// setups unlock the next levels is in this level's logic graph.
+pub const RING_DIRECTION_FLAG: &str = "ring direction";
+pub const RING_SPEED_FLAG: &str = "ring speed";
+pub const RING_COLOR_FLAG: &str = "ring color";
 
+/// Sets which way and how fast the first ring spins along its `TWEEN` path, and its colour.
 fn update_rings(
-    time: Res<Time>,
-    rings_setup: Res<RingsSetup>,
-    mut rings: Query<(&mut Transform, &Name, &Handle<RingsMaterial>)>,
+    flags: Res<LogicFlags>,
+    mut rings: Query<(&Name, &mut Tween)>,
+    ring_materials: Query<(&Name, &Handle<RingsMaterial>)>,
     mut rings_mats: ResMut<Assets<RingsMaterial>>,
-    mut unlocked_levels: ResMut<UnlockedLevels>,
 ) {
-    for (mut transform, name, material) in &mut rings {
-        let speed = if name.contains("Ring1") {
-            if let Some(mat) = rings_mats.get_mut(material) {
-                let color = if rings_setup.color {
-                    Color::rgba(1.0, 0.0, 1.0, 1.0)
-                } else {
-                    Color::rgba(1.0, 0.4, 0.03, 1.0)
-                };
-                if mat.base_color != color {
-                    mat.base_color = color;
-                }
-            }
-            let mut speed = if rings_setup.speed { 1.0 } else { 1.5 };
-            if !rings_setup.direction {
-                speed = -speed;
-            }
-            speed
-        } else {
-            1.5
-        };
-        //transform.rotation =
-        //    Quat::from_xyzw(0.6, speed * time.seconds_since_startup() as f32, 1.1, 0.0);
-        transform.rotate_x(speed * time.delta_seconds());
-        transform.rotate_z(0.8 * speed * time.delta_seconds());
+    let mut speed = if flags.contains(RING_SPEED_FLAG) {
+        1.0
+    } else {
+        1.5
+    };
+    if !flags.contains(RING_DIRECTION_FLAG) {
+        speed = -speed;
     }
-    if rings_setup.is_correct_a() && !unlocked_levels.0.contains(&Level::Level4ChairsPile) {
-        unlocked_levels.0.insert(Level::Level4ChairsPile);
+    for (_, mut tween) in rings.iter_mut().filter_name(RING1) {
+        if tween.speed != speed {
+            tween.speed = speed;
+        }
+    }
+
+    let color = if flags.contains(RING_COLOR_FLAG) {
+        Color::rgba(1.0, 0.0, 1.0, 1.0)
+    } else {
+        Color::rgba(1.0, 0.4, 0.03, 1.0)
+    };
+    for (_, material) in ring_materials.iter().filter_name(RING1) {
+        if let Some(mat) = rings_mats.get_mut(material) {
+            if mat.base_color != color {
+                mat.base_color = color;
+            }
+        }
     }
-    if rings_setup.is_correct_b() && unlocked_levels.0.contains(&Level::Level4ChairsPile) {
-        unlocked_levels.0.insert(Level::Level5GarageLobby);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_harness::TestGame;
+
+    use super::*;
+
+    #[test]
+    fn rings_spin_as_set_up() {
+        let mut game = TestGame::new();
+        game.resource_mut::<LogicFlags>()
+            .insert(RING_DIRECTION_FLAG.to_string());
+        game.load_level(Level::Level3Chair);
+        game.run_frames(10);
+
+        let mut rings = game.app.world.query::<(&Name, &Tween)>();
+        let mut times = |game: &TestGame, ring: &str| -> Vec<f32> {
+            rings
+                .iter(&game.app.world)
+                .filter(|(name, tween)| name.contains(ring) && !tween.waypoints.is_empty())
+                .map(|(_, tween)| tween.time)
+                .collect()
+        };
+        let ring1 = times(&game, "Ring1");
+        assert!(!ring1.is_empty() && ring1.iter().all(|time| *time > 0.0));
+
+        game.resource_mut::<LogicFlags>()
+            .remove(RING_DIRECTION_FLAG);
+        game.run_frames(30);
+        let reversed = times(&game, "Ring1");
+        assert!(reversed
+            .iter()
+            .zip(&ring1)
+            .all(|(now, before)| now < before));
+        assert!(times(&game, "Ring2").iter().all(|time| *time > 0.0));
+
+        // The path ends on whole turns, so the rings don't snap back as they loop
+        for (name, tween) in rings.iter(&game.app.world) {
+            if !name.contains("ROTATE Ring") || tween.waypoints.is_empty() {
+                continue;
+            }
+            let duration = tween.duration();
+            let (before_offset, before) = tween.sample(duration - 0.01);
+            let (after_offset, after) = tween.sample(duration + 0.01);
+            assert!(before_offset.distance(after_offset) < 0.001, "{name}");
+            assert!(before.angle_between(after) < 0.05, "{name}");
+        }
     }
 }
@@ -1,27 +1,15 @@
 use bevy::prelude::*;
 use iyes_loopless::prelude::*;
 
-use crate::{
-    assets::ModelAssets,
-    entity::{
-        button::NamedButtonStatuses, door_linear::DoorLinear, trigger::NamedTriggerStatuses,
-        NamedIterator,
-    },
-    Sun,
-};
+use crate::{assets::ModelAssets, Sun};
 
 use super::Level;
 
 pub struct TestAreaLevelPlugin;
 impl Plugin for TestAreaLevelPlugin {
     fn build(&self, app: &mut App) {
+        // Doors are handled by the test area logic graph
         app.add_enter_system(Level::TestAreaLevel, setup);
-        app.add_system_set(
-            ConditionSet::new()
-                .run_in_state(Level::TestAreaLevel)
-                .with_system(doors)
-                .into(),
-        );
     }
 }
 
@@ -59,37 +47,3 @@ fn setup(mut cmds: Commands, model_assets: Res<ModelAssets>) {
         ..default()
     });
 }
-
-fn doors(
-    mut doors: Query<(&Name, &mut DoorLinear)>,
-    triggers: Res<NamedTriggerStatuses>,
-    buttons: Res<NamedButtonStatuses>,
-) {
-    if triggers.is_changed() {
-        if let Some(status) = triggers.any("TRIGGER Door trigger 1") {
-            for (_, mut door) in doors.iter_mut().filter_name_contains("DOOR_LINEAR Door 1") {
-                if status.player_is_inside {
-                    door.state.open();
-                } else {
-                    door.state.close();
-                }
-            }
-        }
-
-        if let Some(status) = triggers.any("TRIGGER Door trigger 2") {
-            for (_, mut door) in doors.iter_mut().filter_name_contains("DOOR_LINEAR Door 2") {
-                if status.player_is_inside {
-                    door.state.open();
-                } else {
-                    door.state.close();
-                }
-            }
-        }
-
-        if buttons.any("BUTTON Door trigger 3").is_some() {
-            for (_, mut door) in doors.iter_mut().filter_name_contains("DOOR_LINEAR Door 3") {
-                door.state.close();
-            }
-        }
-    }
-}
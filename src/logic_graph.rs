@@ -0,0 +1,481 @@
+//! Declarative puzzle logic loaded from `.logic` files next to the level glTFs.
+//!
+//! A [`LogicGraph`] is a list of [`LogicRule`]s. Each rule listens for one or more
+//! [`LogicEvent`]s, checks its [`LogicCondition`]s and runs its [`LogicAction`]s,
+//! so rooms can be wired up without touching Rust code.
+//!
+//! Progress the rules keep track of, such as how the rings are set up, is kept in
+//! [`LogicFlags`], which are saved with the game.
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashSet},
+};
+use bevy_kira_audio::{prelude::Audio, AudioControl};
+use iyes_loopless::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    assets::{GameState, SoundAssets},
+    entity::{
+        button::ButtonEvent,
+        door_linear::{DoorControls, DoorFullyClosedEvent, DoorFullyOpenedEvent},
+        item_target::{UseItemFailureEvent, UseItemSuccessEvent},
+        name::NamePattern,
+        phone::PhoneUiVisible,
+        trigger::{TriggerEnterEvent, TriggerExitEvent, TriggerStates},
+        NamedIterator,
+    },
+    inventory::Inventory,
+    item::Items,
+    levels::{Level, SelectedLevel, UnlockedLevels},
+    notification::{notify, NotificationText, NOTIFICATION_DURATION},
+    scene_hook::{SceneHook, SceneHooked},
+};
+
+/// Something that happened in the level which rules can react to.
+///
+/// Rules give a [`NamePattern`] for the names they react to, which is a plain string matched
+/// with [`str::contains`] unless it says otherwise, such as `{"Exact": "BUTTON Phone 1"}`. The
+/// events which happened carry the entity's name.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub enum LogicEvent<N = NamePattern> {
+    /// The level's scenes have spawned
+    LevelEntered,
+    TriggerEnter(N),
+    TriggerExit(N),
+    ButtonPressed(N),
+    DoorFullyOpened(N),
+    DoorFullyClosed(N),
+    /// The player used the required item on an item target
+    UseItemSuccess(N),
+    /// The player pressed an item target without its item selected
+    UseItemFailure(N),
+}
+
+impl LogicEvent {
+    fn name_pattern(&self) -> Option<&NamePattern> {
+        match self {
+            LogicEvent::LevelEntered => None,
+            LogicEvent::TriggerEnter(pat)
+            | LogicEvent::TriggerExit(pat)
+            | LogicEvent::ButtonPressed(pat)
+            | LogicEvent::DoorFullyOpened(pat)
+            | LogicEvent::DoorFullyClosed(pat)
+            | LogicEvent::UseItemSuccess(pat)
+            | LogicEvent::UseItemFailure(pat) => Some(pat),
+        }
+    }
+
+    fn matches(&self, other: &LogicEvent<String>) -> bool {
+        match (self, other) {
+            (LogicEvent::LevelEntered, LogicEvent::LevelEntered) => true,
+            (LogicEvent::TriggerEnter(pat), LogicEvent::TriggerEnter(name))
+            | (LogicEvent::TriggerExit(pat), LogicEvent::TriggerExit(name))
+            | (LogicEvent::ButtonPressed(pat), LogicEvent::ButtonPressed(name))
+            | (LogicEvent::DoorFullyOpened(pat), LogicEvent::DoorFullyOpened(name))
+            | (LogicEvent::DoorFullyClosed(pat), LogicEvent::DoorFullyClosed(name))
+            | (LogicEvent::UseItemSuccess(pat), LogicEvent::UseItemSuccess(name))
+            | (LogicEvent::UseItemFailure(pat), LogicEvent::UseItemFailure(name)) => {
+                pat.query().matches(name)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum LogicCondition {
+    /// A [`LogicFlags`] flag is set.
+    Flag(String),
+    /// The inventory contains the item, see [`Inventory::has`].
+    Inventory(String),
+    LevelUnlocked(Level),
+    LevelSelected(Level),
+    /// The current level is this one, for graphs which are active in every level.
+    InLevel(Level),
+    /// Something is inside a trigger matching the pattern, the player unless the trigger
+    /// detects other bodies. See [`TriggerStates::is_occupied`].
+    TriggerOccupied(NamePattern),
+    /// There are doors matching the pattern and they're all fully closed.
+    DoorsClosed(NamePattern),
+    Not(Box<LogicCondition>),
+    Any(Vec<LogicCondition>),
+    All(Vec<LogicCondition>),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub enum LogicAction {
+    OpenDoor(NamePattern),
+    CloseDoor(NamePattern),
+    ToggleDoor(NamePattern),
+    SetFlag(String),
+    ClearFlag(String),
+    ToggleFlag(String),
+    UnlockLevel(Level),
+    SelectLevel(Level),
+    /// Go to the level, leaving this one.
+    ChangeLevel(Level),
+    /// Go to the level selected with [`LogicAction::SelectLevel`].
+    GoToSelectedLevel,
+    /// Show the phone's keypad, see [`PhoneUiVisible`].
+    OpenPhone,
+    /// Give the player one of an item, see [`Items::give`].
+    GiveItem(String),
+    /// Take one of an item from the player if they hold it, see [`Items::take`].
+    TakeItem(String),
+    /// Despawn all meshes matching the pattern.
+    Despawn(NamePattern),
+    PlaySound {
+        sound: String,
+        #[serde(default = "default_volume")]
+        volume: f64,
+    },
+    Notify {
+        text: String,
+        #[serde(default = "default_notification_duration")]
+        duration: f32,
+    },
+}
+
+impl LogicCondition {
+    fn name_patterns<'a>(&'a self, patterns: &mut Vec<&'a NamePattern>) {
+        match self {
+            LogicCondition::TriggerOccupied(pat) | LogicCondition::DoorsClosed(pat) => {
+                patterns.push(pat)
+            }
+            LogicCondition::Not(condition) => condition.name_patterns(patterns),
+            LogicCondition::Any(conditions) | LogicCondition::All(conditions) => {
+                for condition in conditions {
+                    condition.name_patterns(patterns);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl LogicAction {
+    fn name_pattern(&self) -> Option<&NamePattern> {
+        match self {
+            LogicAction::OpenDoor(pat)
+            | LogicAction::CloseDoor(pat)
+            | LogicAction::ToggleDoor(pat)
+            | LogicAction::Despawn(pat) => Some(pat),
+            _ => None,
+        }
+    }
+}
+
+fn default_volume() -> f64 {
+    1.0
+}
+
+fn default_notification_duration() -> f32 {
+    NOTIFICATION_DURATION
+}
+
+/// Runs [`then`] when any of the [`on`] events happen and all [`when`] conditions pass,
+/// otherwise runs [`otherwise`].
+#[derive(Clone, Debug, Deserialize)]
+pub struct LogicRule {
+    pub on: Vec<LogicEvent>,
+    #[serde(default)]
+    pub when: Vec<LogicCondition>,
+    #[serde(default)]
+    pub then: Vec<LogicAction>,
+    #[serde(default)]
+    pub otherwise: Vec<LogicAction>,
+}
+
+/// A set of rules, only active while [`level`] is the current level.
+///
+/// Graphs without a level (such as the elevator) are always active.
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "3b0b8a43-7d8c-4c8e-9c55-0f0d3a1c6e2a"]
+pub struct LogicGraph {
+    #[serde(default)]
+    pub level: Option<Level>,
+    pub rules: Vec<LogicRule>,
+}
+
+impl LogicGraph {
+    pub fn is_active(&self, level: Level) -> bool {
+        self.level.map(|l| l == level).unwrap_or(true)
+    }
+
+    /// Name patterns the rules react to, check and act on, see [`crate::scene_lint`].
+    pub fn name_patterns(&self) -> Vec<&NamePattern> {
+        let mut patterns = Vec::new();
+        for rule in &self.rules {
+            patterns.extend(rule.on.iter().filter_map(LogicEvent::name_pattern));
+            for condition in &rule.when {
+                condition.name_patterns(&mut patterns);
+            }
+            patterns.extend(
+                rule.then
+                    .iter()
+                    .chain(&rule.otherwise)
+                    .filter_map(LogicAction::name_pattern),
+            );
+        }
+        patterns
+    }
+}
+
+/// Named flags set by [`LogicAction::SetFlag`], kept across levels.
+#[derive(Default, Deref, DerefMut)]
+pub struct LogicFlags(pub HashSet<String>);
+
+#[derive(Default)]
+pub struct LogicGraphLoader;
+
+impl AssetLoader for LogicGraphLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let graph = serde_json::from_slice::<LogicGraph>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(graph));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["logic"]
+    }
+}
+
+pub struct LogicGraphPlugin;
+
+impl Plugin for LogicGraphPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<LogicGraph>()
+            .init_asset_loader::<LogicGraphLoader>()
+            .init_resource::<LogicFlags>()
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::RunLevel)
+                    .with_system(run_logic_graphs)
+                    .into(),
+            );
+    }
+}
+
+struct LogicContext<'a> {
+    level: Level,
+    flags: &'a LogicFlags,
+    triggers: &'a TriggerStates<'a, 'a>,
+    doors: &'a DoorControls<'a, 'a>,
+    inventory: &'a Inventory,
+    unlocked_levels: &'a UnlockedLevels,
+    selected_level: &'a SelectedLevel,
+}
+
+impl LogicCondition {
+    fn check(&self, cx: &LogicContext) -> bool {
+        match self {
+            LogicCondition::Flag(flag) => cx.flags.contains(flag),
+            LogicCondition::Inventory(item) => cx.inventory.has(item),
+            LogicCondition::LevelUnlocked(level) => cx.unlocked_levels.0.contains(level),
+            LogicCondition::LevelSelected(level) => cx.selected_level.0 == *level,
+            LogicCondition::InLevel(level) => cx.level == *level,
+            LogicCondition::TriggerOccupied(pat) => cx.triggers.is_occupied(pat.query()),
+            LogicCondition::DoorsClosed(pat) => cx.doors.all_closed(pat.query()),
+            LogicCondition::Not(condition) => !condition.check(cx),
+            LogicCondition::Any(conditions) => conditions.iter().any(|c| c.check(cx)),
+            LogicCondition::All(conditions) => conditions.iter().all(|c| c.check(cx)),
+        }
+    }
+}
+
+/// The entity events rules can react to.
+#[derive(SystemParam)]
+struct EntityEvents<'w, 's> {
+    trigger_enter: EventReader<'w, 's, TriggerEnterEvent>,
+    trigger_exit: EventReader<'w, 's, TriggerExitEvent>,
+    button: EventReader<'w, 's, ButtonEvent>,
+    door_opened: EventReader<'w, 's, DoorFullyOpenedEvent>,
+    door_closed: EventReader<'w, 's, DoorFullyClosedEvent>,
+    use_item_success: EventReader<'w, 's, UseItemSuccessEvent>,
+    use_item_failure: EventReader<'w, 's, UseItemFailureEvent>,
+}
+
+/// Which level the game is in and which it can go to.
+#[derive(SystemParam)]
+struct LevelProgress<'w, 's> {
+    current: Res<'w, CurrentState<Level>>,
+    next: ResMut<'w, Level>,
+    selected: ResMut<'w, SelectedLevel>,
+    unlocked: ResMut<'w, UnlockedLevels>,
+    loading_scenes: Query<'w, 's, (), (With<SceneHook>, Without<SceneHooked>)>,
+}
+
+fn run_logic_graphs(
+    mut cmds: Commands,
+    graphs: Res<Assets<LogicGraph>>,
+    mut progress: LevelProgress,
+    mut entered_level: Local<Option<Level>>,
+    mut was_loaded: Local<bool>,
+    mut entity_events: EntityEvents,
+    mut flags: ResMut<LogicFlags>,
+    mut items: Items,
+    triggers: TriggerStates,
+    mut doors: DoorControls,
+    mut meshes: Query<(&Name, Entity), With<Handle<Mesh>>>,
+    mut texts: Query<(&mut Text, &mut NotificationText)>,
+    mut phone_ui_visible: ResMut<PhoneUiVisible>,
+    audio: Res<Audio>,
+    sound_assets: Res<SoundAssets>,
+) {
+    let mut events: Vec<LogicEvent<String>> = Vec::new();
+    let level = progress.current.0;
+
+    // Scene entities get their components the frame after their scene is hooked
+    let loaded = progress.loading_scenes.is_empty();
+    if *entered_level != Some(level) && loaded && *was_loaded {
+        *entered_level = Some(level);
+        events.push(LogicEvent::LevelEntered);
+    }
+    *was_loaded = loaded;
+
+    macro_rules! collect_named {
+        ($reader:ident, $variant:ident) => {
+            for event in entity_events.$reader.iter() {
+                if let Some(name) = &event.name {
+                    events.push(LogicEvent::$variant(name.clone()));
+                }
+            }
+        };
+    }
+
+    collect_named!(trigger_enter, TriggerEnter);
+    collect_named!(trigger_exit, TriggerExit);
+    collect_named!(door_opened, DoorFullyOpened);
+    collect_named!(door_closed, DoorFullyClosed);
+    collect_named!(use_item_success, UseItemSuccess);
+    collect_named!(use_item_failure, UseItemFailure);
+
+    for event in entity_events.button.iter() {
+        if event.pressed {
+            if let Some(name) = &event.name {
+                events.push(LogicEvent::ButtonPressed(name.clone()));
+            }
+        }
+    }
+
+    if events.is_empty() {
+        return;
+    }
+
+    let active_graphs = graphs
+        .iter()
+        .filter(|(_, graph)| graph.is_active(level))
+        .map(|(_, graph)| graph);
+
+    for graph in active_graphs {
+        for rule in &graph.rules {
+            for event in &events {
+                if !rule.on.iter().any(|on| on.matches(event)) {
+                    continue;
+                }
+
+                let passed = {
+                    let cx = LogicContext {
+                        level,
+                        flags: &flags,
+                        triggers: &triggers,
+                        doors: &doors,
+                        inventory: items.inventory(),
+                        unlocked_levels: &progress.unlocked,
+                        selected_level: &progress.selected,
+                    };
+                    rule.when.iter().all(|condition| condition.check(&cx))
+                };
+                let actions = if passed { &rule.then } else { &rule.otherwise };
+
+                for action in actions {
+                    debug!(?event, ?action, "Logic action");
+                    match action {
+                        LogicAction::OpenDoor(pat) => {
+                            for state in doors.states_mut(pat.query()) {
+                                state.open();
+                            }
+                        }
+                        LogicAction::CloseDoor(pat) => {
+                            for state in doors.states_mut(pat.query()) {
+                                state.close();
+                            }
+                        }
+                        LogicAction::ToggleDoor(pat) => {
+                            for state in doors.states_mut(pat.query()) {
+                                state.toggle();
+                            }
+                        }
+                        LogicAction::SetFlag(flag) => {
+                            flags.insert(flag.clone());
+                        }
+                        LogicAction::ClearFlag(flag) => {
+                            flags.remove(flag);
+                        }
+                        LogicAction::ToggleFlag(flag) => {
+                            if !flags.remove(flag) {
+                                flags.insert(flag.clone());
+                            }
+                        }
+                        LogicAction::UnlockLevel(level) => {
+                            if !progress.unlocked.0.contains(level) {
+                                progress.unlocked.0.insert(*level);
+                            }
+                        }
+                        LogicAction::SelectLevel(level) => {
+                            progress.selected.0 = *level;
+                        }
+                        LogicAction::ChangeLevel(level) => {
+                            *progress.next = *level;
+                        }
+                        LogicAction::GoToSelectedLevel => {
+                            *progress.next = progress.selected.0;
+                        }
+                        LogicAction::OpenPhone => {
+                            if !**phone_ui_visible {
+                                **phone_ui_visible = true;
+                            }
+                        }
+                        LogicAction::GiveItem(item) => {
+                            if !items.give(item, 1) {
+                                warn!(item = %item, "Attempted to give unknown item");
+                            }
+                        }
+                        LogicAction::TakeItem(item) => {
+                            items.take(item, 1);
+                        }
+                        LogicAction::Despawn(pat) => {
+                            for (_, entity) in meshes.iter_mut().filter_name(pat.query()) {
+                                cmds.entity(entity).despawn();
+                            }
+                        }
+                        LogicAction::PlaySound { sound, volume } => {
+                            match sound_assets.by_name(sound) {
+                                Some(handle) => {
+                                    audio.play(handle).with_volume(*volume);
+                                }
+                                None => {
+                                    warn!(sound = %sound, "Attempted to play unknown sound");
+                                }
+                            }
+                        }
+                        LogicAction::Notify { text, duration } => {
+                            notify(&mut texts, text, *duration);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,8 @@
 /// Helper macro to assign a component based on new entities name.
+///
+/// The optional body sets up the entity and returns a `Result<(), EntityError>`. The component
+/// is added either way, falling back to its defaults if the properties don't parse, and any
+/// error is reported to [`EntityErrors`](crate::entity::error::EntityErrors).
 #[macro_export]
 macro_rules! spawn_from_scene {
     (
@@ -7,7 +11,7 @@ macro_rules! spawn_from_scene {
         $(, |$cmds:ident, $entity:ident, $comp:ident $(, $( $args:ident : $arg_ty:ty ),* )? | { $( $body:tt )* } )?
     ) => {
         paste::paste! {
-            #[doc = "Add [`" $component "`] component to entities containing uppercase \"" $id "\"."]
+            #[doc = "Add [`" $component "`] component to entities with the uppercase \"" $id "\" kind, see [`EntityName`](crate::entity::name::EntityName)."]
             pub(super) fn [<spawn_ $id _from_scene>](
                 mut cmds: bevy::prelude::Commands,
                 mut scene_loaded: $crate::scene_hook::SceneLoaded,
@@ -15,28 +19,40 @@ macro_rules! spawn_from_scene {
             ) {
                 for entity in scene_loaded.iter() {
                     if let Some(name) = entity.get::<bevy::prelude::Name>() {
-                        if name.contains(&stringify!($id).to_uppercase()) {
+                        let entity_name = $crate::entity::name::EntityName::parse(name);
+                        if entity_name.has_kind(&stringify!($id).to_uppercase()) {
                             #[allow(unused_mut)]
                             let mut component: $component = entity
                                 .get::<bevy::gltf::GltfExtras>()
                                 .map(|extras| {
-                                    serde_json::from_str(&extras.value).expect(concat!(
-                                        "invalid ",
-                                        stringify!($id),
-                                        " properties"
-                                    ))
+                                    $crate::entity::error::parse_properties(&extras.value)
+                                        .unwrap_or_else(|error| {
+                                            cmds.add($crate::entity::error::ReportEntityError {
+                                                name: name.to_string(),
+                                                error,
+                                            });
+                                            Default::default()
+                                        })
                                 })
                                 .unwrap_or_default();
 
-                            bevy::prelude::debug!(id = ?entity.id(), name = %&name[stringify!($id).len()..].trim(), properties = ?component, concat!("Registered ", stringify!($id)));
+                            bevy::prelude::debug!(id = ?entity.id(), name = %entity_name.id, properties = ?component, concat!("Registered ", stringify!($id)));
 
                             let mut entity_cmd = cmds.entity(entity.id());
                             $({
-                                let $entity = entity;
-                                let $cmds = &mut entity_cmd;
-                                let $comp = &mut component;
+                                let result = (|| -> Result<(), $crate::entity::error::EntityError> {
+                                    let $entity = entity;
+                                    let $cmds = &mut entity_cmd;
+                                    let $comp = &mut component;
 
-                                $( $body )*
+                                    $( $body )*
+                                })();
+                                if let Err(error) = result {
+                                    entity_cmd.commands().add($crate::entity::error::ReportEntityError {
+                                        name: name.to_string(),
+                                        error,
+                                    });
+                                }
                             })?
                             entity_cmd.insert(component);
                         }
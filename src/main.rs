@@ -3,8 +3,8 @@
 
 #[cfg(debug_assertions)]
 use bevy_editor_pls::EditorState;
-use bevy_kira_audio::{prelude::Audio, AudioControl, AudioTween};
-use std::{f32::consts::PI, time::Duration};
+use bevy_kira_audio::{AudioControl, AudioTween};
+use std::{f32::consts::PI, path::Path, time::Duration};
 
 use assets::{abs_transform, copy_names};
 use bevy::{
@@ -28,52 +28,74 @@ use bevy_fps_controller::controller::*;
 use bevy_kira_audio::prelude::*;
 use bevy_rapier3d::prelude::*;
 use iyes_loopless::prelude::*;
-use levels::level3_chair::RingsSetup;
-use menu::MenuPlugin;
+use menu::{GameSettings, MenuPlugin};
 use notification::NotificationPlugin;
 
-use crate::assets::{FontAssets, GameState, ImageAssets, ModelAssets, SoundAssets};
-use crate::audio::AudioComponentPlugin;
+use crate::actions::{Action, ActionsPlugin};
+use crate::assets::{
+    DataAssets, FontAssets, GameState, ImageAssets, LogicAssets, ModelAssets, SoundAssets,
+};
+use crate::audio::{AudioComponentPlugin, MusicChannel};
+#[cfg(debug_assertions)]
+use crate::diagnostics::DiagnosticsOverlayPlugin;
 #[cfg(debug_assertions)]
 use crate::editor::GameEditorPlugin;
 use crate::entity::EntityPlugin;
 use crate::inventory::InventoryPlugin;
+use crate::item::ItemPlugin;
 use crate::levels::{Level, LevelsPlugin};
+use crate::logic_graph::LogicGraphPlugin;
 use crate::materials::{
     general::GeneralMaterial, post_process::PostProcessingMaterial, rings::RingsMaterial,
     swap_materials,
 };
+use crate::prompt::PromptPlugin;
+use crate::replay::ReplayPlugin;
+use crate::save::SavePlugin;
 use crate::scene_hook::HookPlugin;
+use crate::scene_lint::SceneLintPlugin;
 use crate::sidecar_asset::SidecarAssetPlugin;
 
+mod actions;
 mod assets;
 mod audio;
 #[cfg(debug_assertions)]
+mod diagnostics;
+#[cfg(debug_assertions)]
 mod editor;
 mod entity;
 mod inventory;
+mod item;
 mod levels;
+mod logic_graph;
 mod macros;
 mod materials;
 mod menu;
 mod notification;
+mod prompt;
+mod replay;
+mod save;
 mod scene_hook;
+mod scene_lint;
 mod sidecar_asset;
+#[cfg(test)]
+mod test_harness;
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--lint-scenes") {
+        std::process::exit(match scene_lint::lint_asset_files(Path::new("assets")) {
+            Ok(0) => 0,
+            Ok(_) => 1,
+            Err(err) => {
+                eprintln!("Failed to lint scenes: {err}");
+                2
+            }
+        });
+    }
+
     let mut app = App::new();
 
-    app.add_loopless_state(GameState::AssetLoading)
-        .add_loopless_state(Level::None)
-        .add_loading_state(
-            LoadingState::new(GameState::AssetLoading)
-                .continue_to_state(GameState::RunLevel)
-                .with_collection::<FontAssets>()
-                .with_collection::<ImageAssets>()
-                .with_collection::<ModelAssets>()
-                .with_collection::<SoundAssets>(),
-        )
-        .insert_resource(ImageSettings::default_nearest())
+    app.insert_resource(ImageSettings::default_nearest())
         .insert_resource(AssetServerSettings {
             watch_for_changes: true,
             ..default()
@@ -100,21 +122,14 @@ fn main() {
             fit_canvas_to_parent: true,
         })
         .add_plugins(DefaultPlugins)
-        .add_plugin(LogDiagnosticsPlugin::default())
-        //.add_plugin(FrameTimeDiagnosticsPlugin::default())
-        .add_plugin(AudioPlugin)
-        .add_plugin(AudioComponentPlugin)
-        .add_plugin(HookPlugin)
-        .add_plugin(Material2dPlugin::<PostProcessingMaterial>::default())
-        .add_plugin(MaterialPlugin::<GeneralMaterial>::default())
-        .add_plugin(MaterialPlugin::<RingsMaterial>::default())
+        .add_plugin(LogDiagnosticsPlugin::default());
+    //.add_plugin(FrameTimeDiagnosticsPlugin::default())
+
+    add_game_logic(&mut app);
+
+    app.add_plugin(Material2dPlugin::<PostProcessingMaterial>::default())
         .insert_resource(ClearColor(Color::BLACK))
-        .insert_resource(RapierConfiguration::default())
-        .add_plugin(RapierDebugRenderPlugin::default())
-        .add_plugin(SidecarAssetPlugin)
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugin(FpsControllerPlugin)
-        .insert_resource(RingsSetup::default());
+        .add_plugin(RapierDebugRenderPlugin::default());
 
     #[cfg(target_arch = "wasm32")]
     {
@@ -122,16 +137,60 @@ fn main() {
     }
 
     #[cfg(debug_assertions)]
-    app.add_plugin(GameEditorPlugin);
+    app.add_plugin(GameEditorPlugin)
+        .add_plugin(DiagnosticsOverlayPlugin);
 
-    app.add_plugin(LevelsPlugin)
-        .add_plugin(EntityPlugin)
-        .add_plugin(NotificationPlugin)
+    app.add_plugin(NotificationPlugin)
         .add_plugin(InventoryPlugin)
+        .add_plugin(PromptPlugin)
         .add_plugin(MenuPlugin)
         .add_system(window_resized)
         .add_enter_system(GameState::RunLevel, hide_mouse)
         .add_enter_system(GameState::RunLevel, setup_player)
+        .add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::RunLevel)
+                .after("pre_process")
+                .with_system(sun_follow_camera)
+                .with_system(toggle_mouse)
+                .into(),
+        )
+        .run();
+}
+
+/// Asset loading, physics, levels, entities and their logic.
+///
+/// Everything except windows, rendering and UI, so the test harness can run it headless.
+fn add_game_logic(app: &mut App) {
+    app.add_loopless_state(GameState::AssetLoading)
+        .add_loopless_state(Level::None)
+        .add_loading_state(
+            LoadingState::new(GameState::AssetLoading)
+                .continue_to_state(GameState::RunLevel)
+                .with_collection::<DataAssets>()
+                .with_collection::<FontAssets>()
+                .with_collection::<ImageAssets>()
+                .with_collection::<ModelAssets>()
+                .with_collection::<LogicAssets>()
+                .with_collection::<SoundAssets>(),
+        )
+        .add_plugin(AudioPlugin)
+        .add_plugin(AudioComponentPlugin)
+        .add_plugin(HookPlugin)
+        .add_plugin(MaterialPlugin::<GeneralMaterial>::default())
+        .add_plugin(MaterialPlugin::<RingsMaterial>::default())
+        .insert_resource(RapierConfiguration::default())
+        .add_plugin(SidecarAssetPlugin)
+        .add_plugin(ItemPlugin)
+        .add_plugin(LogicGraphPlugin)
+        .add_plugin(FpsControllerPlugin)
+        .add_plugin(FpsControllerPhysicsPlugin::<NoUserData>::default())
+        .add_plugin(ActionsPlugin)
+        .add_plugin(LevelsPlugin)
+        .add_plugin(EntityPlugin)
+        .add_plugin(SceneLintPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(ReplayPlugin)
         .add_system_set(
             ConditionSet::new()
                 .run_in_state(GameState::RunLevel)
@@ -144,19 +203,17 @@ fn main() {
             ConditionSet::new()
                 .run_in_state(GameState::RunLevel)
                 .after("pre_process")
-                .with_system(sun_follow_camera)
-                .with_system(toggle_mouse)
                 .with_system(swap_materials)
                 .into(),
-        )
-        .run();
+        );
 }
 
-pub fn get_display_scale(window_width: f32, window_height: f32) -> Vec3 {
+/// Render size and pixel scale for a window, `render_scale` is [`GameSettings::render_scale`].
+pub fn get_display_scale(render_scale: Option<u32>, window_width: f32, window_height: f32) -> Vec3 {
     let window_width = window_width as u32;
     let window_height = window_height as u32;
 
-    let scale = (window_height / 512).max(2);
+    let scale = render_scale.unwrap_or_else(|| (window_height / 512).max(2));
 
     let width = (window_width / scale).max(256);
     let height = (window_height / scale).max(256);
@@ -170,10 +227,11 @@ fn setup_player(
     mut post_processing_materials: ResMut<Assets<PostProcessingMaterial>>,
     mut windows: ResMut<Windows>,
     mut rapier_debug: ResMut<DebugRenderContext>,
-    audio: Res<Audio>,
+    music: Res<AudioChannel<MusicChannel>>,
     sound_assets: Res<SoundAssets>,
+    game_settings: Res<GameSettings>,
 ) {
-    audio
+    music
         .play(sound_assets.drone1.clone())
         .looped()
         .fade_in(AudioTween::linear(Duration::from_secs(5)))
@@ -190,6 +248,7 @@ fn setup_player(
     );
 
     let scale = get_display_scale(
+        game_settings.render_scale,
         window.physical_width() as f32,
         window.physical_height() as f32,
     );
@@ -219,44 +278,7 @@ fn setup_player(
     image.resize(size);
     let image_handle = images.add(image);
 
-    // Note that we have two entities for the player
-    // One is a "logical" player that handles the physics computation and collision
-    // The other is a "render" player that is what is displayed to the user
-    // This distinction is useful for later on if you want to add multiplayer,
-    // where often time these two ideas are not exactly synced up
-    cmds.spawn()
-        //TODO why so short?
-        .insert(Collider::capsule(Vec3::Y * 0.0, Vec3::Y * 0.7, 0.5))
-        .insert(ActiveEvents::COLLISION_EVENTS)
-        .insert(Velocity::zero())
-        .insert(RigidBody::Dynamic)
-        .insert(Sleeping::disabled())
-        .insert(LockedAxes::ROTATION_LOCKED)
-        .insert(AdditionalMassProperties::Mass(1.0))
-        .insert(GravityScale(0.0))
-        .insert(Ccd { enabled: true }) // Prevent clipping when going fast
-        .insert(LogicalPlayer(0))
-        .insert(FpsControllerInput {
-            pitch: 0.0,
-            yaw: 110.0f32.to_radians(),
-            ..default()
-        })
-        .insert(FpsController {
-            run_speed: 8.0,
-            forward_speed: 8.0,
-            max_air_speed: 8.0,
-            walk_speed: 4.0,
-            air_acceleration: 800.0, // bhop :D
-            jump_speed: 6.0,
-            key_jump: None,
-            key_fly: None,
-            ..default()
-        })
-        .insert_bundle(SpatialBundle {
-            visibility: Visibility { is_visible: false },
-            transform: Transform::from_translation(vec3(-36.0, 3.0, 67.0)),
-            ..default()
-        });
+    spawn_logical_player(&mut cmds);
 
     cmds.spawn_bundle(Camera3dBundle {
         camera: Camera {
@@ -325,6 +347,52 @@ fn setup_player(
     .insert(post_processing_pass_layer);
 }
 
+/// The physics side of the player, also used by the test harness.
+fn spawn_logical_player(cmds: &mut Commands) -> Entity {
+    // Note that we have two entities for the player
+    // One is a "logical" player that handles the physics computation and collision
+    // The other is a "render" player that is what is displayed to the user
+    // This distinction is useful for later on if you want to add multiplayer,
+    // where often time these two ideas are not exactly synced up
+    cmds.spawn()
+        //TODO why so short?
+        .insert(Collider::capsule(Vec3::Y * 0.0, Vec3::Y * 0.7, 0.5))
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(Velocity::zero())
+        .insert(RigidBody::Dynamic)
+        .insert(Sleeping::disabled())
+        .insert(LockedAxes::ROTATION_LOCKED)
+        .insert(AdditionalMassProperties::Mass(1.0))
+        .insert(GravityScale(0.0))
+        .insert(Ccd { enabled: true }) // Prevent clipping when going fast
+        .insert(LogicalPlayer(0))
+        .insert(FpsControllerInput {
+            pitch: 0.0,
+            yaw: 110.0f32.to_radians(),
+            ..default()
+        })
+        .insert(FpsController {
+            run_speed: 8.0,
+            forward_speed: 8.0,
+            max_air_speed: 8.0,
+            walk_speed: 4.0,
+            air_acceleration: 800.0, // bhop :D
+            jump_speed: 6.0,
+            crouch_speed: 2.0,
+            // Low enough to crawl under the chair piles
+            crouch_height: 0.0,
+            // Input comes from the action bindings, see `actions::controller_input_from_actions`
+            device_input: false,
+            ..default()
+        })
+        .insert_bundle(SpatialBundle {
+            visibility: Visibility { is_visible: false },
+            transform: Transform::from_translation(vec3(-36.0, 3.0, 67.0)),
+            ..default()
+        })
+        .id()
+}
+
 fn hide_mouse(mut windows: ResMut<Windows>) {
     let primary_win = windows.primary_mut();
     primary_win.set_cursor_visibility(false);
@@ -334,14 +402,13 @@ fn hide_mouse(mut windows: ResMut<Windows>) {
 
 fn toggle_mouse(
     mut windows: ResMut<Windows>,
-    keys: Res<Input<KeyCode>>,
+    actions: Res<Input<Action>>,
     mut fps_controller: Query<&mut FpsController>,
-    btn: Res<Input<MouseButton>>,
     #[cfg(debug_assertions)] editor_state: Res<EditorState>,
 ) {
     let window = windows.primary_mut();
     let mut fps_controller = fps_controller.single_mut();
-    if keys.just_pressed(KeyCode::Tab) {
+    if actions.just_pressed(Action::ToggleCursor) {
         let is_locked = window.cursor_locked();
         if is_locked {
             // Unlock
@@ -356,7 +423,7 @@ fn toggle_mouse(
             window.set_cursor_position(vec2(window.width() / 2.0, window.height() / 2.0));
         }
     }
-    if keys.just_pressed(KeyCode::Escape)
+    if actions.just_pressed(Action::ReleaseCursor)
         || (!window.cursor_locked() && fps_controller.enable_input)
     {
         // Unlock
@@ -373,7 +440,7 @@ fn toggle_mouse(
         editor_active = editor_state.active;
     }
 
-    if btn.just_pressed(MouseButton::Left)
+    if actions.just_pressed(Action::Interact)
         && (!fps_controller.enable_input || window.cursor_visible() || !window.cursor_locked())
         && !editor_active
     {
@@ -395,9 +462,10 @@ fn window_resized(
     mut meshes: ResMut<Assets<Mesh>>,
     post_process_mesh: Query<&Mesh2dHandle, With<PostProcessMesh>>,
     mut image_events: EventWriter<AssetEvent<Image>>,
+    game_settings: Res<GameSettings>,
 ) {
     if let Some(event) = window_resized_events.iter().last() {
-        let scale = get_display_scale(event.width, event.height);
+        let scale = get_display_scale(game_settings.render_scale, event.width, event.height);
 
         if let Some((_, mat)) = post_processing_materials.iter_mut().next() {
             let image = images.get_mut(&mat.source_image).unwrap();
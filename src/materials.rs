@@ -1,6 +1,6 @@
 use bevy::{prelude::*, render::view::NoFrustumCulling};
 
-use crate::scene_hook::SceneLoaded;
+use crate::{entity::name::NameQuery, scene_hook::SceneLoaded, scene_lint::NameLookup};
 
 use self::{general::GeneralMaterial, rings::RingsMaterial};
 
@@ -8,6 +8,9 @@ pub mod general;
 pub mod post_process;
 pub mod rings;
 
+/// Names looked up by [`swap_materials`] in any level, checked by [`crate::scene_lint`].
+pub const NAME_LOOKUPS: &[NameLookup] = &[NameLookup::All(NameQuery::contains("ROTATE Ring"))];
+
 pub fn swap_materials(
     mut cmds: Commands,
     mut scene_loaded: SceneLoaded,
@@ -1,19 +1,34 @@
-use bevy::{prelude::*, ui::FocusPolicy, window::WindowResized};
+use std::fs;
+
+use bevy::{
+    prelude::*,
+    render::camera::Projection,
+    ui::FocusPolicy,
+    window::{PresentMode, WindowResized},
+};
 
 use bevy_fps_controller::controller::FpsController;
+use bevy_kira_audio::prelude::{Audio, AudioChannel, AudioControl};
 use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    actions::{Action, Binding, InputBindings},
     assets::{FontAssets, GameState},
+    audio::MusicChannel,
     get_display_scale,
     materials::post_process::PostProcessingMaterial,
+    save::{LoadGameEvent, SaveExists},
+    PlayerCamera,
 };
 
 pub struct MenuPlugin;
 
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.insert_resource(GameSettings::default());
+        app.insert_resource(GameSettings::load())
+            .init_resource::<MenuSelection>()
+            .init_resource::<Rebinding>();
         app.add_enter_system(GameState::RunLevel, create_menu_ui);
         app.add_system_set(
             ConditionSet::new()
@@ -21,6 +36,7 @@ impl Plugin for MenuPlugin {
                 .with_system(resize_menu_ui)
                 .with_system(render_menu)
                 .with_system(update_menu)
+                .with_system(apply_settings)
                 .into(),
         );
     }
@@ -32,10 +48,16 @@ struct MenuUiContainer;
 #[derive(Component)]
 pub struct MenuText(pub String);
 
-fn create_menu_ui(mut cmds: Commands, windows: Res<Windows>, font_assets: Res<FontAssets>) {
+fn create_menu_ui(
+    mut cmds: Commands,
+    windows: Res<Windows>,
+    font_assets: Res<FontAssets>,
+    game_settings: Res<GameSettings>,
+) {
     let window = windows.get_primary().unwrap();
 
     let scale = get_display_scale(
+        game_settings.render_scale,
         window.physical_width() as f32,
         window.physical_height() as f32,
     );
@@ -90,33 +112,99 @@ fn create_menu_ui(mut cmds: Commands, windows: Res<Windows>, font_assets: Res<Fo
 fn resize_menu_ui(
     mut ui: Query<&mut Style, With<MenuUiContainer>>,
     mut window_resized_events: EventReader<WindowResized>,
+    game_settings: Res<GameSettings>,
 ) {
     if let Some(event) = window_resized_events.iter().last() {
         for mut style in ui.iter_mut() {
-            let scale = get_display_scale(event.width, event.height);
+            let scale = get_display_scale(game_settings.render_scale, event.width, event.height);
             style.size = Size::new(Val::Px(scale.x), Val::Px(scale.y));
         }
     }
 }
 
+pub const SETTINGS_PATH: &str = "settings.json";
+
+/// Player settings, persisted to [`SETTINGS_PATH`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GameSettings {
-    sensitivity: f32,
-    monitor_fx: bool,
-    sel: i32,
-    max: i32,
+    pub sensitivity: f32,
+    pub invert_y: bool,
+    pub stick_sensitivity: f32,
+    pub stick_deadzone: f32,
+    /// Vertical field of view in degrees
+    pub fov: f32,
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    /// Overrides the pixel scale from [`get_display_scale`], `None` picks it from the window height
+    pub render_scale: Option<u32>,
+    pub vsync: bool,
+    pub monitor_fx: bool,
 }
 
 impl Default for GameSettings {
     fn default() -> Self {
         GameSettings {
             sensitivity: 1.0,
+            invert_y: false,
+            stick_sensitivity: 1.0,
+            stick_deadzone: 0.15,
+            fov: 60.0,
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            render_scale: None,
+            vsync: true,
             monitor_fx: true,
-            sel: 0,
-            max: 1,
         }
     }
 }
 
+impl GameSettings {
+    /// Load the settings file, falling back to defaults if it is missing or invalid.
+    pub fn load() -> GameSettings {
+        match fs::read(SETTINGS_PATH) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+                warn!(%err, "Invalid settings file, using defaults");
+                GameSettings::default()
+            }),
+            Err(_) => GameSettings::default(),
+        }
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_vec_pretty(self)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| fs::write(SETTINGS_PATH, bytes).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            warn!(%err, "Failed to save settings");
+        }
+    }
+
+    /// Total music volume, including the master volume.
+    pub fn music(&self) -> f64 {
+        (self.master_volume * self.music_volume) as f64
+    }
+
+    /// Total sound effects volume, including the master volume.
+    pub fn sfx(&self) -> f64 {
+        (self.master_volume * self.sfx_volume) as f64
+    }
+}
+
+/// Currently selected menu item.
+#[derive(Default)]
+struct MenuSelection(i32);
+
+/// Action waiting for a new input, set by pressing right on a binding item.
+#[derive(Default)]
+struct Rebinding(Option<Action>);
+
+/// Number of settings items, the bindings for [`Action::ALL`] are listed after these.
+const SETTINGS_ITEMS: i32 = 12;
+const MENU_ITEMS: i32 = SETTINGS_ITEMS + Action::ALL.len() as i32;
+
 fn add_item(s: &mut String, pos: i32, sel: i32, text: &str, value: &str) {
     if sel == pos {
         s.push_str("> ")
@@ -130,85 +218,241 @@ fn add_item(s: &mut String, pos: i32, sel: i32, text: &str, value: &str) {
 }
 
 impl GameSettings {
-    fn render(&self) -> String {
-        let mut s = String::from("Select with arrow\nkeys or WASD\n");
+    fn render(&self, sel: i32, save_exists: bool) -> String {
+        let mut s = String::from("Select with arrow\nkeys, WASD or D-pad\n");
+        let percent = |value: f32| format!("{:.0}%", value * 100.0);
         add_item(
             &mut s,
             0,
-            self.sel,
+            sel,
             "Mouse Speed",
             &format!("{:.2}", self.sensitivity),
         );
+        add_item(&mut s, 1, sel, "Invert Y", &format!("{}", self.invert_y));
+        add_item(&mut s, 2, sel, "FOV", &format!("{:.0}", self.fov));
+        add_item(&mut s, 3, sel, "Volume", &percent(self.master_volume));
+        add_item(&mut s, 4, sel, "Music", &percent(self.music_volume));
+        add_item(&mut s, 5, sel, "Effects", &percent(self.sfx_volume));
         add_item(
             &mut s,
-            1,
-            self.sel,
+            6,
+            sel,
+            "Pixel Size",
+            &self
+                .render_scale
+                .map(|scale| scale.to_string())
+                .unwrap_or_else(|| String::from("auto")),
+        );
+        add_item(&mut s, 7, sel, "VSync", &format!("{}", self.vsync));
+        add_item(
+            &mut s,
+            8,
+            sel,
             "Monitor FX",
             &format!("{}", self.monitor_fx),
         );
+        add_item(
+            &mut s,
+            9,
+            sel,
+            "Stick Speed",
+            &format!("{:.2}", self.stick_sensitivity),
+        );
+        add_item(
+            &mut s,
+            10,
+            sel,
+            "Stick Deadzone",
+            &percent(self.stick_deadzone),
+        );
+        add_item(
+            &mut s,
+            11,
+            sel,
+            "Continue",
+            if save_exists {
+                "press right"
+            } else {
+                "no save"
+            },
+        );
         s
     }
 }
 
+fn render_bindings(s: &mut String, bindings: &InputBindings, sel: i32, rebinding: &Rebinding) {
+    s.push_str("\nControls (right to rebind)\n");
+    for (i, action) in Action::ALL.into_iter().enumerate() {
+        let value = if rebinding.0 == Some(action) {
+            String::from("press input, Esc to cancel")
+        } else {
+            bindings.label(action)
+        };
+        add_item(s, SETTINGS_ITEMS + i as i32, sel, action.label(), &value);
+    }
+}
+
 fn update_menu(
     mut windows: ResMut<Windows>,
     mut game_settings: ResMut<GameSettings>,
+    mut selection: ResMut<MenuSelection>,
+    mut bindings: ResMut<InputBindings>,
+    mut rebinding: ResMut<Rebinding>,
+    actions: Res<Input<Action>>,
     keys: Res<Input<KeyCode>>,
-    mut controllers: Query<&mut FpsController>,
-    mut post_processing_materials: ResMut<Assets<PostProcessingMaterial>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut load_game_events: EventWriter<LoadGameEvent>,
+    save_exists: Res<SaveExists>,
 ) {
     let window = windows.primary_mut();
-    if window.cursor_visible() {
-        let next = keys.just_pressed(KeyCode::Down) || keys.just_pressed(KeyCode::S);
-        let prev = keys.just_pressed(KeyCode::Up) || keys.just_pressed(KeyCode::W);
-        let inc = keys.just_pressed(KeyCode::Right) || keys.just_pressed(KeyCode::D);
-        let dec = keys.just_pressed(KeyCode::Left) || keys.just_pressed(KeyCode::A);
-        if next {
-            game_settings.sel += 1;
+    if !window.cursor_visible() {
+        rebinding.0 = None;
+        return;
+    }
+
+    if let Some(action) = rebinding.0 {
+        if keys.just_pressed(KeyCode::Escape) {
+            rebinding.0 = None;
+        } else if let Some(binding) = Binding::just_pressed(&keys, &mouse_buttons, &gamepad_buttons)
+        {
+            debug!(?action, ?binding, "Rebound action");
+            bindings.rebind(action, binding);
+            bindings.save();
+            rebinding.0 = None;
         }
-        if prev {
-            game_settings.sel -= 1;
+        return;
+    }
+
+    let next = actions.just_pressed(Action::MenuDown);
+    let prev = actions.just_pressed(Action::MenuUp);
+    let inc = actions.just_pressed(Action::MenuRight);
+    let dec = actions.just_pressed(Action::MenuLeft);
+    if next {
+        selection.0 += 1;
+    }
+    if prev {
+        selection.0 -= 1;
+    }
+    selection.0 = selection.0.clamp(0, MENU_ITEMS - 1);
+
+    if !inc && !dec {
+        return;
+    }
+    if selection.0 >= SETTINGS_ITEMS {
+        if inc {
+            rebinding.0 = Some(Action::ALL[(selection.0 - SETTINGS_ITEMS) as usize]);
         }
-        game_settings.sel = game_settings.sel.clamp(0, game_settings.max);
-        if game_settings.sel == 0 {
-            if inc {
-                game_settings.sensitivity += 0.1;
-            } else if dec {
-                game_settings.sensitivity -= 0.1;
+        return;
+    }
+    let step = if inc { 1.0 } else { -1.0 };
+    let mut settings = game_settings.clone();
+    match selection.0 {
+        0 => settings.sensitivity = (settings.sensitivity + step * 0.1).clamp(0.0, 10.0),
+        1 => settings.invert_y = inc,
+        2 => settings.fov = (settings.fov + step * 5.0).clamp(45.0, 90.0),
+        3 => settings.master_volume = (settings.master_volume + step * 0.1).clamp(0.0, 1.0),
+        4 => settings.music_volume = (settings.music_volume + step * 0.1).clamp(0.0, 1.0),
+        5 => settings.sfx_volume = (settings.sfx_volume + step * 0.1).clamp(0.0, 1.0),
+        6 => {
+            settings.render_scale = match (settings.render_scale, inc) {
+                (None, true) => Some(1),
+                (Some(scale), true) => Some((scale + 1).min(8)),
+                (Some(1), false) => None,
+                (Some(scale), false) => Some(scale - 1),
+                (None, false) => None,
             }
-            game_settings.sensitivity = game_settings.sensitivity.clamp(0.0, 10.0);
         }
-        if game_settings.sel == 1 {
-            if inc {
-                game_settings.monitor_fx = true;
-            } else if dec {
-                game_settings.monitor_fx = false;
+        7 => settings.vsync = inc,
+        8 => settings.monitor_fx = inc,
+        9 => settings.stick_sensitivity = (settings.stick_sensitivity + step * 0.1).clamp(0.1, 5.0),
+        10 => settings.stick_deadzone = (settings.stick_deadzone + step * 0.05).clamp(0.0, 0.5),
+        11 => {
+            if inc && **save_exists {
+                load_game_events.send(LoadGameEvent);
             }
         }
+        _ => {}
+    }
+
+    // Only trigger change detection if a value actually changed
+    if settings != *game_settings {
+        *game_settings = settings;
+        game_settings.save();
+    }
+}
 
-        for mut controller in &mut controllers {
-            controller.sensitivity = 0.001 * game_settings.sensitivity;
+fn apply_settings(
+    game_settings: Res<GameSettings>,
+    mut windows: ResMut<Windows>,
+    mut controllers: Query<&mut FpsController>,
+    mut projections: Query<&mut Projection, With<PlayerCamera>>,
+    mut post_processing_materials: ResMut<Assets<PostProcessingMaterial>>,
+    music: Res<AudioChannel<MusicChannel>>,
+    audio: Res<Audio>,
+    mut window_resized_events: EventWriter<WindowResized>,
+    mut applied_render_scale: Local<Option<u32>>,
+) {
+    if !game_settings.is_changed() {
+        return;
+    }
+
+    for mut controller in &mut controllers {
+        controller.sensitivity = 0.001 * game_settings.sensitivity;
+        controller.invert_y = game_settings.invert_y;
+        controller.gamepad_sensitivity = 3.0 * game_settings.stick_sensitivity;
+        controller.gamepad_deadzone = game_settings.stick_deadzone;
+    }
+
+    for mut projection in &mut projections {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = game_settings.fov.to_radians();
         }
+    }
 
-        let monitor_fx = game_settings.monitor_fx as u32 as f32;
+    let monitor_fx = game_settings.monitor_fx as u32 as f32;
 
-        for (_, mat) in post_processing_materials.iter_mut() {
-            if mat.monitor_fx.x != monitor_fx {
-                mat.monitor_fx.x = monitor_fx;
-            }
+    for (_, mat) in post_processing_materials.iter_mut() {
+        if mat.monitor_fx.x != monitor_fx {
+            mat.monitor_fx.x = monitor_fx;
         }
     }
+
+    music.set_volume(game_settings.music());
+    audio.set_volume(game_settings.sfx());
+
+    let window = windows.primary_mut();
+    window.set_present_mode(if game_settings.vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    });
+
+    if *applied_render_scale != game_settings.render_scale {
+        *applied_render_scale = game_settings.render_scale;
+        // Resize everything that depends on the display scale
+        window_resized_events.send(WindowResized {
+            id: window.id(),
+            width: window.physical_width() as f32,
+            height: window.physical_height() as f32,
+        });
+    }
 }
 
 fn render_menu(
     mut texts: Query<(&mut Text, &mut MenuText)>,
     mut windows: ResMut<Windows>,
     game_settings: Res<GameSettings>,
+    selection: Res<MenuSelection>,
+    bindings: Res<InputBindings>,
+    rebinding: Res<Rebinding>,
+    save_exists: Res<SaveExists>,
 ) {
     let window = windows.primary_mut();
     if window.cursor_visible() {
         if let Some((mut text, mut menu_text)) = texts.iter_mut().next() {
-            let new_menu_text = game_settings.render();
+            let mut new_menu_text = game_settings.render(selection.0, **save_exists);
+            render_bindings(&mut new_menu_text, &bindings, selection.0, &rebinding);
             if menu_text.0 != new_menu_text {
                 menu_text.0 = new_menu_text;
                 if let Some(section) = text.sections.iter_mut().next() {
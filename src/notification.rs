@@ -4,10 +4,13 @@ use iyes_loopless::prelude::*;
 
 use crate::{
     assets::{FontAssets, GameState, SoundAssets},
-    entity::trigger::NamedTriggerStatuses,
+    entity::{name::NameQuery, trigger::TriggerStates},
     get_display_scale,
     inventory::Icon,
-    levels::level2_lobby::GarageOpened,
+    levels::level2_lobby::GARAGE_OPENED_FLAG,
+    logic_graph::LogicFlags,
+    menu::GameSettings,
+    scene_lint::NameLookup,
 };
 use bevy_kira_audio::{prelude::Audio, AudioControl};
 
@@ -30,16 +33,36 @@ impl Plugin for NotificationPlugin {
 #[derive(Component)]
 struct NotificationUiContainer;
 
+/// How long notifications stay up unless told otherwise, in seconds.
+pub const NOTIFICATION_DURATION: f32 = 8.0;
+
+/// The notification text, with the seconds left until it's cleared.
 #[derive(Component)]
 pub struct NotificationText(pub f32);
 
+/// Replace the notification with `text` for `duration` seconds.
+pub fn notify(texts: &mut Query<(&mut Text, &mut NotificationText)>, text: &str, duration: f32) {
+    for (mut notification, mut note) in texts.iter_mut() {
+        note.0 = duration;
+        if let Some(section) = notification.sections.iter_mut().next() {
+            section.value = text.to_string();
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct EndText;
 
-fn create_notification_ui(mut cmds: Commands, windows: Res<Windows>, font_assets: Res<FontAssets>) {
+fn create_notification_ui(
+    mut cmds: Commands,
+    windows: Res<Windows>,
+    font_assets: Res<FontAssets>,
+    game_settings: Res<GameSettings>,
+) {
     let window = windows.get_primary().unwrap();
 
     let scale = get_display_scale(
+        game_settings.render_scale,
         window.physical_width() as f32,
         window.physical_height() as f32,
     );
@@ -109,10 +132,11 @@ fn create_notification_ui(mut cmds: Commands, windows: Res<Windows>, font_assets
 fn resize_notification_ui(
     mut ui: Query<&mut Style, With<NotificationUiContainer>>,
     mut window_resized_events: EventReader<WindowResized>,
+    game_settings: Res<GameSettings>,
 ) {
     if let Some(event) = window_resized_events.iter().last() {
         for mut style in ui.iter_mut() {
-            let scale = get_display_scale(event.width, event.height);
+            let scale = get_display_scale(game_settings.render_scale, event.width, event.height);
             style.size = Size::new(Val::Px(scale.x), Val::Px(scale.y));
         }
     }
@@ -131,23 +155,31 @@ fn clear_notification(time: Res<Time>, mut texts: Query<(&mut Text, &mut Notific
     }
 }
 
+/// Names looked up by [`fade_in_ending_white`], checked by [`crate::scene_lint`]. The garage
+/// renames its exit to this once it's opened, so it isn't in any scene file.
+pub const NAME_LOOKUPS: &[NameLookup] = &[NameLookup::All(END_WIN_AREA)];
+
+const END_WIN_AREA: NameQuery<'static> = NameQuery::contains("End Win Area");
+
 fn fade_in_ending_white(
     time: Res<Time>,
     mut fade: Local<f32>,
     mut ui: Query<&mut UiColor, With<NotificationUiContainer>>,
     mut end_text: Query<&mut Text, With<EndText>>,
-    triggers: Res<NamedTriggerStatuses>,
+    triggers: TriggerStates,
     mut player_entered_end: Local<bool>,
-    garage_opened: Option<Res<GarageOpened>>,
+    flags: Res<LogicFlags>,
     mut icons: Query<(&Icon, &mut Visibility)>,
     audio: Res<Audio>,
     sound_assets: Res<SoundAssets>,
 ) {
-    if garage_opened.is_some() && triggers.is_changed() && !*player_entered_end {
-        if let Some(_status) = triggers.any("End Win Area") {
-            *player_entered_end = true;
-            audio.play(sound_assets.ending.clone()).with_volume(0.7);
-        }
+    if flags.contains(GARAGE_OPENED_FLAG)
+        && !*player_entered_end
+        && triggers.is_occupied(END_WIN_AREA)
+    {
+        // The ending keeps fading in once started, even if the player steps back out
+        *player_entered_end = true;
+        audio.play(sound_assets.ending.clone()).with_volume(0.7);
     }
     if *player_entered_end {
         *fade += time.delta_seconds() * 0.1;
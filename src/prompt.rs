@@ -0,0 +1,158 @@
+//! The crosshair, with the hovered button's prompt under it.
+
+use bevy::{prelude::*, ui::FocusPolicy, window::WindowResized};
+use iyes_loopless::prelude::*;
+
+use crate::{
+    assets::{FontAssets, GameState},
+    entity::button::{Button, ButtonState},
+    get_display_scale,
+    menu::GameSettings,
+};
+
+pub struct PromptPlugin;
+
+impl Plugin for PromptPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_enter_system(GameState::RunLevel, create_prompt_ui);
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::RunLevel)
+                .with_system(resize_prompt_ui)
+                .with_system(update_prompt)
+                .into(),
+        );
+    }
+}
+
+const CROSSHAIR_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.3);
+const CROSSHAIR_HOVER_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.9);
+const LOCKED_PROMPT_COLOR: Color = Color::rgba(0.9, 0.3, 0.3, 1.0);
+
+#[derive(Component)]
+struct PromptUiContainer;
+
+#[derive(Component)]
+struct Crosshair;
+
+#[derive(Component)]
+struct PromptText;
+
+fn create_prompt_ui(
+    mut cmds: Commands,
+    windows: Res<Windows>,
+    font_assets: Res<FontAssets>,
+    game_settings: Res<GameSettings>,
+) {
+    let window = windows.get_primary().unwrap();
+
+    let scale = get_display_scale(
+        game_settings.render_scale,
+        window.physical_width() as f32,
+        window.physical_height() as f32,
+    );
+
+    cmds.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(scale.x), Val::Px(scale.y)),
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Percent(0.0),
+                left: Val::Percent(0.0),
+                ..default()
+            },
+            ..default()
+        },
+        color: Color::NONE.into(),
+        focus_policy: FocusPolicy::Pass,
+        ..default()
+    })
+    .with_children(|parent| {
+        parent
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(2.0), Val::Px(2.0)),
+                    ..default()
+                },
+                color: CROSSHAIR_COLOR.into(),
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            })
+            .insert(Crosshair);
+        parent
+            .spawn_bundle(
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_mono_medium.clone(),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_text_alignment(TextAlignment::CENTER)
+                .with_style(Style {
+                    // Below the crosshair without moving it off centre
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Percent(53.0),
+                        ..default()
+                    },
+                    ..default()
+                }),
+            )
+            .insert(PromptText);
+    })
+    .insert(PromptUiContainer);
+}
+
+fn resize_prompt_ui(
+    mut ui: Query<&mut Style, With<PromptUiContainer>>,
+    mut window_resized_events: EventReader<WindowResized>,
+    game_settings: Res<GameSettings>,
+) {
+    if let Some(event) = window_resized_events.iter().last() {
+        for mut style in ui.iter_mut() {
+            let scale = get_display_scale(game_settings.render_scale, event.width, event.height);
+            style.size = Size::new(Val::Px(scale.x), Val::Px(scale.y));
+        }
+    }
+}
+
+fn update_prompt(
+    buttons: Query<(&Button, &ButtonState)>,
+    mut crosshair: Query<&mut UiColor, With<Crosshair>>,
+    mut prompt: Query<&mut Text, With<PromptText>>,
+) {
+    let hovered = buttons.iter().find(|(_, state)| state.hovered);
+
+    let crosshair_color = if hovered.is_some() {
+        CROSSHAIR_HOVER_COLOR
+    } else {
+        CROSSHAIR_COLOR
+    };
+    let (value, color) = match hovered {
+        Some((button, state)) if state.locked => (button.prompt(state), LOCKED_PROMPT_COLOR),
+        Some((button, state)) => (button.prompt(state), Color::WHITE),
+        None => (None, Color::WHITE),
+    };
+    let value = value.unwrap_or_default();
+
+    for mut color in &mut crosshair {
+        if color.0 != crosshair_color {
+            color.0 = crosshair_color;
+        }
+    }
+    // Only write on changes, the text is laid out again whenever it's changed
+    for mut text in &mut prompt {
+        if let Some(section) = text.sections.first() {
+            if section.value != value || section.style.color != color {
+                let section = &mut text.sections[0];
+                section.value = value.to_string();
+                section.style.color = color;
+            }
+        }
+    }
+}
@@ -0,0 +1,443 @@
+//! Record player input to a file and play it back, for bug reports and regression tests.
+//!
+//! A replay starts from a [`SaveGame`] snapshot of the level and progress, followed by the
+//! controller input for every fixed tick and the [`Action`]s held each frame, which covers
+//! interaction clicks and phone keys. Press [`RECORD_KEY`] to start and stop recording to
+//! [`REPLAY_PATH`] and [`PLAY_KEY`] to play it back, or run with `--replay <path>`. Anything
+//! else can start one with [`PlayReplayEvent`].
+//!
+//! Movement is replayed tick for tick. The rest of the game still runs once per frame, so
+//! anything timed such as doors is only exact when played back at the recorded frame rate.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{prelude::*, time::FixedTimesteps, utils::HashSet};
+use bevy_fps_controller::controller::{
+    FpsController, FpsControllerInput, FpsControllerStage, FpsControllerSystems, LogicalPlayer,
+    FPS_CONTROLLER_TIMESTEP,
+};
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{Action, ActionsOverride, ActionsSystem},
+    assets::GameState,
+    save::{Progress, SaveError, SaveGame},
+    scene_hook::{SceneHook, SceneHooked},
+};
+
+pub const REPLAY_PATH: &str = "replay.json";
+
+pub const RECORD_KEY: KeyCode = KeyCode::F9;
+pub const PLAY_KEY: KeyCode = KeyCode::F10;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(path) = std::env::args().skip_while(|arg| arg != "--replay").nth(1) {
+            app.insert_resource(PendingReplay(path.into()));
+        }
+
+        app.init_resource::<ReplayState>()
+            .add_event::<PlayReplayEvent>()
+            .add_enter_system(GameState::RunLevel, play_pending_replay)
+            .add_system_set_to_stage(
+                CoreStage::PreUpdate,
+                ConditionSet::new()
+                    .run_in_state(GameState::RunLevel)
+                    .after(ActionsSystem)
+                    .with_system(replay_actions)
+                    .into(),
+            )
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::RunLevel)
+                    .with_system(replay_controls)
+                    .with_system(play_replay_events)
+                    .into(),
+            )
+            .stage(FpsControllerStage::Fixed, |schedule: &mut Schedule| {
+                schedule.add_system_set_to_stage(
+                    FpsControllerStage::Move,
+                    ConditionSet::new()
+                        .run_in_state(GameState::RunLevel)
+                        .before(FpsControllerSystems::Look)
+                        .with_system(replay_tick)
+                        .into(),
+                )
+            });
+    }
+}
+
+/// Replay from the command line, played once the game has loaded.
+struct PendingReplay(PathBuf);
+
+/// Play the replay file at this path, ignored while already recording or playing.
+pub struct PlayReplayEvent(pub PathBuf);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub start: SaveGame,
+    /// Controller velocity when recording started
+    pub velocity: [f32; 3],
+    pub ticks: Vec<ReplayTick>,
+}
+
+impl Replay {
+    pub fn read(path: &Path) -> Result<Replay, SaveError> {
+        let mut value: serde_json::Value = serde_json::from_slice(&fs::read(path)?)?;
+        // Migrate the snapshot like any other save
+        let start = SaveGame::from_value(value["start"].take())?;
+        value["start"] = serde_json::to_value(start)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SaveError> {
+        fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+}
+
+/// Input for one fixed tick of the player controller.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ReplayTick {
+    pub movement: [f32; 3],
+    pub pitch: f32,
+    pub yaw: f32,
+    pub jump: bool,
+    pub sprint: bool,
+    pub crouch: bool,
+    pub fly: bool,
+    /// Actions held in the frames since the previous tick, `None` for further ticks in the same frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions: Option<Vec<Action>>,
+}
+
+impl ReplayTick {
+    fn new(input: &FpsControllerInput, actions: Option<Vec<Action>>) -> Self {
+        ReplayTick {
+            movement: input.movement.into(),
+            pitch: input.pitch,
+            yaw: input.yaw,
+            jump: input.jump,
+            sprint: input.sprint,
+            crouch: input.crouch,
+            fly: input.fly,
+            actions,
+        }
+    }
+
+    fn apply(&self, input: &mut FpsControllerInput) {
+        *input = FpsControllerInput {
+            fly: self.fly,
+            sprint: self.sprint,
+            jump: self.jump,
+            crouch: self.crouch,
+            pitch: self.pitch,
+            yaw: self.yaw,
+            movement: self.movement.into(),
+        };
+    }
+}
+
+pub enum ReplayState {
+    Idle,
+    Recording {
+        replay: Replay,
+        /// Actions held since the last tick
+        pending: Option<Vec<Action>>,
+    },
+    Playing {
+        replay: Replay,
+        tick: usize,
+        level_load: LevelLoad,
+    },
+}
+
+impl Default for ReplayState {
+    fn default() -> Self {
+        ReplayState::Idle
+    }
+}
+
+/// Playback waits for the replay's level to be spawned before starting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LevelLoad {
+    Waiting,
+    Spawning,
+    Done,
+}
+
+fn play_replay(cmds: &mut Commands, state: &mut ReplayState, progress: &mut Progress, path: &Path) {
+    match Replay::read(path) {
+        Ok(replay) => {
+            info!(
+                ticks = replay.ticks.len(),
+                "Playing replay {}",
+                path.display()
+            );
+            // Reloads the level, so the replay starts from a fresh copy of it
+            progress.restore(replay.start.clone());
+            cmds.insert_resource(ActionsOverride);
+            *state = ReplayState::Playing {
+                replay,
+                tick: 0,
+                level_load: LevelLoad::Waiting,
+            };
+        }
+        Err(err) => warn!(%err, path = %path.display(), "Failed to load replay"),
+    }
+}
+
+fn play_pending_replay(
+    mut cmds: Commands,
+    pending: Option<Res<PendingReplay>>,
+    mut state: ResMut<ReplayState>,
+    mut progress: Progress,
+) {
+    if let Some(pending) = pending {
+        play_replay(&mut cmds, &mut state, &mut progress, &pending.0);
+        cmds.remove_resource::<PendingReplay>();
+    }
+}
+
+fn replay_controls(
+    mut cmds: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut state: ResMut<ReplayState>,
+    mut progress: Progress,
+    player: Query<&FpsController, With<LogicalPlayer>>,
+) {
+    if keys.just_pressed(RECORD_KEY) {
+        match &*state {
+            ReplayState::Idle => {
+                if let (Some(start), Ok(controller)) = (progress.snapshot(), player.get_single()) {
+                    info!("Recording replay");
+                    *state = ReplayState::Recording {
+                        replay: Replay {
+                            start,
+                            velocity: controller.velocity.into(),
+                            ticks: Vec::new(),
+                        },
+                        pending: None,
+                    };
+                }
+            }
+            ReplayState::Recording { replay, .. } => {
+                match replay.write(Path::new(REPLAY_PATH)) {
+                    Ok(()) => info!(ticks = replay.ticks.len(), "Saved replay to {REPLAY_PATH}"),
+                    Err(err) => warn!(%err, "Failed to save replay"),
+                }
+                *state = ReplayState::Idle;
+            }
+            ReplayState::Playing { .. } => {}
+        }
+    }
+
+    if keys.just_pressed(PLAY_KEY) && matches!(*state, ReplayState::Idle) {
+        play_replay(&mut cmds, &mut state, &mut progress, Path::new(REPLAY_PATH));
+    }
+}
+
+fn play_replay_events(
+    mut cmds: Commands,
+    mut play_replay_events: EventReader<PlayReplayEvent>,
+    mut state: ResMut<ReplayState>,
+    mut progress: Progress,
+) {
+    if let Some(PlayReplayEvent(path)) = play_replay_events.iter().last() {
+        if matches!(*state, ReplayState::Idle) {
+            play_replay(&mut cmds, &mut state, &mut progress, path);
+        }
+    }
+}
+
+/// Records held actions, or drives [`Input<Action>`] from the replay in place of devices.
+fn replay_actions(
+    mut state: ResMut<ReplayState>,
+    mut actions: ResMut<Input<Action>>,
+    time: Res<Time>,
+    fixed_timesteps: Res<FixedTimesteps>,
+    loading_scenes: Query<(), (With<SceneHook>, Without<SceneHooked>)>,
+) {
+    match &mut *state {
+        ReplayState::Idle => {}
+        ReplayState::Recording { pending, .. } => {
+            let pending = pending.get_or_insert_with(Vec::new);
+            for action in actions.get_pressed() {
+                if !pending.contains(action) {
+                    pending.push(*action);
+                }
+            }
+        }
+        ReplayState::Playing {
+            replay,
+            tick,
+            level_load,
+        } => {
+            if *level_load != LevelLoad::Done {
+                *level_load = match (*level_load, loading_scenes.is_empty()) {
+                    (LevelLoad::Waiting, false) => LevelLoad::Spawning,
+                    (LevelLoad::Spawning, true) => LevelLoad::Done,
+                    (level_load, _) => level_load,
+                };
+                return;
+            }
+
+            // Ticks this frame will run, while recording this frame's systems saw their actions
+            let ticks = fixed_timesteps
+                .get(FPS_CONTROLLER_TIMESTEP)
+                .map_or(1, |timestep| {
+                    (timestep.overstep_percentage() + time.delta_seconds_f64() / timestep.step())
+                        as usize
+                });
+            if ticks == 0 {
+                // Recorded with the next tick, keep holding what's held until then
+                return;
+            }
+            // Nothing recorded past the end, so everything is let go
+            let held: HashSet<Action> = replay
+                .ticks
+                .iter()
+                .skip(*tick)
+                .take(ticks)
+                .filter_map(|tick| tick.actions.as_ref())
+                .flatten()
+                .copied()
+                .collect();
+
+            for action in Action::ALL {
+                match (held.contains(&action), actions.pressed(action)) {
+                    (true, false) => actions.press(action),
+                    (false, true) => actions.release(action),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn replay_tick(
+    mut cmds: Commands,
+    mut state: ResMut<ReplayState>,
+    mut actions: ResMut<Input<Action>>,
+    mut player: Query<
+        (&mut Transform, &mut FpsController, &mut FpsControllerInput),
+        With<LogicalPlayer>,
+    >,
+) {
+    let (mut transform, mut controller, mut input) = match player.get_single_mut() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    let finished = match &mut *state {
+        ReplayState::Idle => false,
+        ReplayState::Recording { replay, pending } => {
+            replay.ticks.push(ReplayTick::new(&input, pending.take()));
+            false
+        }
+        ReplayState::Playing {
+            replay,
+            tick,
+            level_load,
+        } => {
+            if *level_load != LevelLoad::Done {
+                // Hold still, there's nothing to stand on yet
+                transform.translation = replay.start.player_translation.into();
+                controller.velocity = Vec3::ZERO;
+                *input = FpsControllerInput {
+                    pitch: input.pitch,
+                    yaw: input.yaw,
+                    ..default()
+                };
+                return;
+            }
+            if *tick == 0 {
+                transform.translation = replay.start.player_translation.into();
+                controller.velocity = replay.velocity.into();
+            }
+
+            match replay.ticks.get(*tick) {
+                Some(recorded) => {
+                    recorded.apply(&mut input);
+                    *tick += 1;
+                    false
+                }
+                None => true,
+            }
+        }
+    };
+
+    if finished {
+        info!("Replay finished");
+        for action in Action::ALL {
+            if actions.pressed(action) {
+                actions.release(action);
+            }
+        }
+        cmds.remove_resource::<ActionsOverride>();
+        *state = ReplayState::Idle;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness::TestGame;
+
+    const GARAGE_WALK: &str = "tests/replays/garage_walk.json";
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let replay = Replay::read(Path::new(GARAGE_WALK)).unwrap();
+        let path = std::env::temp_dir().join("subfuse_replay_round_trip.json");
+        replay.write(&path).unwrap();
+        let read = Replay::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            read.start.player_translation,
+            replay.start.player_translation
+        );
+        assert_eq!(read.ticks.len(), replay.ticks.len());
+        for (read, tick) in read.ticks.iter().zip(&replay.ticks) {
+            assert_eq!(read.movement, tick.movement);
+            assert_eq!(read.yaw, tick.yaw);
+            assert_eq!(read.actions, tick.actions);
+        }
+    }
+
+    #[test]
+    fn plays_back_from_the_recorded_start() {
+        let mut game = TestGame::new();
+        let start = Vec3::from(
+            Replay::read(Path::new(GARAGE_WALK))
+                .unwrap()
+                .start
+                .player_translation,
+        );
+        game.teleport(start + Vec3::new(20.0, 50.0, 0.0));
+
+        game.play_replay(GARAGE_WALK);
+
+        // Walked a few metres from the start along the garage floor
+        let walked = (game.player_position() - start) * Vec3::new(1.0, 0.0, 1.0);
+        assert!(
+            walked.length() > 1.0 && walked.length() < 10.0,
+            "{start} -> {}",
+            game.player_position()
+        );
+        assert!(
+            (game.player_position().y - start.y).abs() < 3.0,
+            "{start} -> {}",
+            game.player_position()
+        );
+        // Held to the end, let go after
+        assert!(!game.resource::<Input<Action>>().pressed(Action::Crouch));
+    }
+}
@@ -0,0 +1,378 @@
+//! Save and load game progress.
+//!
+//! Progress is autosaved every time the level changes and can be restored with [`LoadGameEvent`].
+//! Save files carry a [`SAVE_VERSION`] and older files are migrated forward when loaded.
+
+use std::{fmt, fs, path::Path};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_fps_controller::controller::{FpsControllerInput, LogicalPlayer};
+use iyes_loopless::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    assets::GameState,
+    entity::pickup::CollectedPickups,
+    inventory::Inventory,
+    item::SelectedItem,
+    levels::{Level, SelectedLevel, UnlockedLevels},
+    logic_graph::LogicFlags,
+};
+
+pub const SAVE_PATH: &str = "save.json";
+
+/// Current version of [`SaveGame`], bump this and add a migration when the format changes.
+pub const SAVE_VERSION: u32 = 4;
+
+/// Migrations from each version to the next, `MIGRATIONS[0]` upgrades version 1 to 2.
+const MIGRATIONS: &[fn(Value) -> Value] = &[item_slots, progress_flags, pickup_levels];
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LoadGameEvent>()
+            .insert_resource(SaveExists(SaveGame::exists()))
+            .add_system_set(
+                ConditionSet::new()
+                    .run_in_state(GameState::RunLevel)
+                    .with_system(autosave)
+                    .with_system(load_game)
+                    .into(),
+            );
+    }
+}
+
+/// Restore progress from the save file.
+pub struct LoadGameEvent;
+
+/// Whether there's a save file to load, kept up to date as it's saved and loaded so the menu
+/// doesn't check the disk every frame.
+#[derive(Clone, Copy, Debug, Deref)]
+pub struct SaveExists(pub bool);
+
+/// While this resource exists progress isn't autosaved, so tests leave the save file alone.
+pub struct AutosaveDisabled;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "io error: {err}"),
+            SaveError::Json(err) => write!(f, "invalid save file: {err}"),
+            SaveError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save version {version}")
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(err: serde_json::Error) -> Self {
+        SaveError::Json(err)
+    }
+}
+
+/// A snapshot of all progress.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub level: Level,
+    pub selected_level: Level,
+    pub unlocked_levels: Vec<Level>,
+    pub inventory: Inventory,
+    pub collected_pickups: Vec<(Level, String)>,
+    pub logic_flags: Vec<String>,
+    pub player_translation: [f32; 3],
+    pub player_pitch: f32,
+    pub player_yaw: f32,
+}
+
+impl SaveGame {
+    pub fn exists() -> bool {
+        Path::new(SAVE_PATH).exists()
+    }
+
+    pub fn read() -> Result<SaveGame, SaveError> {
+        Self::from_value(serde_json::from_slice(&fs::read(SAVE_PATH)?)?)
+    }
+
+    /// Parse a save from any version, such as one embedded in another file.
+    pub fn from_value(value: Value) -> Result<SaveGame, SaveError> {
+        Ok(serde_json::from_value(migrate(value)?)?)
+    }
+
+    pub fn write(&self) -> Result<(), SaveError> {
+        fs::write(SAVE_PATH, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Upgrade a save file to [`SAVE_VERSION`].
+fn migrate(mut value: Value) -> Result<Value, SaveError> {
+    let version = value
+        .get("version")
+        .and_then(|version| version.as_u64())
+        .unwrap_or(0) as u32;
+
+    if version == 0 || version > SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        value = migration(value);
+    }
+    if version != SAVE_VERSION {
+        debug!(from = version, to = SAVE_VERSION, "Migrated save file");
+        value["version"] = SAVE_VERSION.into();
+    }
+
+    Ok(value)
+}
+
+/// Version 2 replaced the `key` and `money` inventory flags with item slots, and remembers
+/// which pickups are gone rather than checking the inventory.
+fn item_slots(mut value: Value) -> Value {
+    let inventory = value["inventory"].take();
+    let mut slots = Vec::new();
+    let mut collected_pickups = Vec::new();
+    for (item, pickup) in [("key", "Keys"), ("money", "money")] {
+        if inventory[item].as_bool() == Some(true) {
+            slots.push(json!({ "item": item, "count": 1 }));
+            collected_pickups.push(pickup);
+        }
+    }
+    value["inventory"] = json!({ "slots": slots });
+    value["collected_pickups"] = json!(collected_pickups);
+    value
+}
+
+/// Version 3 keeps the rings' setup and whether the garage is open as logic flags, now that the
+/// levels' logic graphs set them.
+fn progress_flags(mut value: Value) -> Value {
+    let rings_setup = value["rings_setup"].take();
+    let garage_opened = value["garage_opened"].take();
+    let mut flags = match value["logic_flags"].take() {
+        Value::Array(flags) => flags,
+        _ => Vec::new(),
+    };
+    for (setting, flag) in [
+        ("direction", "ring direction"),
+        ("speed", "ring speed"),
+        ("color", "ring color"),
+    ] {
+        if rings_setup[setting].as_bool() == Some(true) {
+            flags.push(flag.into());
+        }
+    }
+    if garage_opened.as_bool() == Some(true) {
+        flags.push("garage opened".into());
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.remove("rings_setup");
+        object.remove("garage_opened");
+    }
+    value["logic_flags"] = flags.into();
+    value
+}
+
+/// Version 4 keeps the level of each collected pickup along with its id. The shipped pickups
+/// are each in one level, anything else is assumed to be in the level the game was saved in.
+fn pickup_levels(mut value: Value) -> Value {
+    let level = value["level"].clone();
+    let collected_pickups: Vec<_> = match value["collected_pickups"].take() {
+        Value::Array(pickups) => pickups
+            .into_iter()
+            .map(|id| {
+                let level = match id.as_str() {
+                    Some("Keys") => json!(Level::Level5GarageLobby),
+                    Some("money") => json!(Level::Level2Lobby),
+                    _ => level.clone(),
+                };
+                json!([level, id])
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    value["collected_pickups"] = collected_pickups.into();
+    value
+}
+
+/// The resources and player state a [`SaveGame`] is taken from and restored to.
+#[derive(SystemParam)]
+pub struct Progress<'w, 's> {
+    level: ResMut<'w, Level>,
+    selected_level: ResMut<'w, SelectedLevel>,
+    unlocked_levels: ResMut<'w, UnlockedLevels>,
+    inventory: ResMut<'w, Inventory>,
+    collected_pickups: ResMut<'w, CollectedPickups>,
+    selected_item: ResMut<'w, SelectedItem>,
+    logic_flags: ResMut<'w, LogicFlags>,
+    player: Query<
+        'w,
+        's,
+        (&'static mut Transform, &'static mut FpsControllerInput),
+        With<LogicalPlayer>,
+    >,
+}
+
+impl<'w, 's> Progress<'w, 's> {
+    /// Current progress, `None` before the player is spawned.
+    pub fn snapshot(&self) -> Option<SaveGame> {
+        let (transform, input) = self.player.iter().next()?;
+        Some(SaveGame {
+            version: SAVE_VERSION,
+            level: *self.level,
+            selected_level: self.selected_level.0,
+            unlocked_levels: self.unlocked_levels.0.iter().copied().collect(),
+            inventory: self.inventory.clone(),
+            collected_pickups: self.collected_pickups.iter().cloned().collect(),
+            logic_flags: self.logic_flags.iter().cloned().collect(),
+            player_translation: transform.translation.into(),
+            player_pitch: input.pitch,
+            player_yaw: input.yaw,
+        })
+    }
+
+    pub fn restore(&mut self, save: SaveGame) {
+        *self.level = save.level;
+        self.selected_level.0 = save.selected_level;
+        self.unlocked_levels.0 = save.unlocked_levels.into_iter().collect();
+        self.selected_item.0 = save.inventory.slots.first().map(|stack| stack.item.clone());
+        *self.inventory = save.inventory;
+        self.collected_pickups.0 = save.collected_pickups.into_iter().collect();
+        self.logic_flags.0 = save.logic_flags.into_iter().collect();
+
+        for (mut transform, mut input) in &mut self.player {
+            transform.translation = save.player_translation.into();
+            input.pitch = save.player_pitch;
+            input.yaw = save.player_yaw;
+        }
+    }
+}
+
+fn autosave(
+    progress: Progress,
+    disabled: Option<Res<AutosaveDisabled>>,
+    mut save_exists: ResMut<SaveExists>,
+) {
+    // Don't overwrite the save with a fresh game on startup
+    if disabled.is_some() || !progress.level.is_changed() || progress.level.is_added() {
+        return;
+    }
+
+    if let Some(save) = progress.snapshot() {
+        match save.write() {
+            Ok(()) => {
+                debug!(level = ?save.level, "Autosaved");
+                save_exists.0 = true;
+            }
+            Err(err) => warn!(%err, "Failed to autosave"),
+        }
+    }
+}
+
+fn load_game(
+    mut load_game_events: EventReader<LoadGameEvent>,
+    mut progress: Progress,
+    mut save_exists: ResMut<SaveExists>,
+) {
+    if load_game_events.iter().last().is_none() {
+        return;
+    }
+    // It may have been deleted since
+    save_exists.0 = SaveGame::exists();
+
+    let save = match SaveGame::read() {
+        Ok(save) => save,
+        Err(err) => {
+            warn!(%err, "Failed to load save");
+            return;
+        }
+    };
+
+    let level = save.level;
+    progress.restore(save);
+    debug!(?level, "Loaded save");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_inventory_flags_to_slots() {
+        let value = migrate(json!({
+            "version": 1,
+            "inventory": { "key": true, "money": false },
+        }))
+        .unwrap();
+
+        assert_eq!(value["version"], SAVE_VERSION);
+        assert_eq!(
+            value["inventory"],
+            json!({ "slots": [{ "item": "key", "count": 1 }] })
+        );
+        assert_eq!(
+            value["collected_pickups"],
+            json!([["Level5GarageLobby", "Keys"]])
+        );
+    }
+
+    #[test]
+    fn migrates_rings_and_garage_to_flags() {
+        let value = migrate(json!({
+            "version": 2,
+            "rings_setup": { "direction": true, "speed": false, "color": true },
+            "garage_opened": true,
+            "logic_flags": ["door unlocked"],
+        }))
+        .unwrap();
+
+        assert_eq!(value["version"], SAVE_VERSION);
+        assert_eq!(
+            value["logic_flags"],
+            json!([
+                "door unlocked",
+                "ring direction",
+                "ring color",
+                "garage opened"
+            ])
+        );
+        assert!(value.get("rings_setup").is_none());
+        assert!(value.get("garage_opened").is_none());
+    }
+
+    #[test]
+    fn migrates_pickups_to_levels() {
+        let value = migrate(json!({
+            "version": 3,
+            "level": "Level3Chair",
+            "collected_pickups": ["money", "Keys", "chair"],
+        }))
+        .unwrap();
+
+        assert_eq!(value["version"], SAVE_VERSION);
+        assert_eq!(
+            value["collected_pickups"],
+            json!([
+                ["Level2Lobby", "money"],
+                ["Level5GarageLobby", "Keys"],
+                ["Level3Chair", "chair"]
+            ])
+        );
+    }
+}
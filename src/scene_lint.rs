@@ -0,0 +1,684 @@
+//! Checks level scenes against the names the game looks entities up by.
+//!
+//! Entities are wired up by name. [`spawn_from_scene!`](crate::spawn_from_scene) adds components
+//! to names containing an uppercase prefix such as `TRIGGER`, and level code and logic graphs find
+//! entities by substrings such as `"BUTTON vending 1"`. A renamed object in Blender silently
+//! breaks a puzzle, so [`lint`] reports:
+//! - buttons, triggers and doors which nothing looks up
+//! - lookups which match nothing
+//! - lookups which only use the first match, but match several names
+//! - teleports to a destination which doesn't exist
+//! - glTF extras which don't parse as the entity's properties
+//!
+//! Issues are logged as each level loads. Run `cargo run -- --lint-scenes` to check every level
+//! from the files in `assets`, which exits with an error if there are any. It's a flag of the
+//! game rather than a binary of its own because the lookups are declared next to the level and
+//! entity code using them, and the game isn't split into a library a second binary could use.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::{gltf::GltfExtras, prelude::*, utils::HashSet};
+use iyes_loopless::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    assets::GameState,
+    entity::{
+        block::Block,
+        button::Button,
+        collider::Collider,
+        door_hinged::DoorHinged,
+        door_linear::DoorLinear,
+        error::{parse_properties, EntityError},
+        item_target::ItemTarget,
+        name::{EntityName, NameMatch, NameQuery},
+        phone::Phone,
+        pickup::Pickup,
+        prop::Prop,
+        sound::Sound,
+        teleport::Teleport,
+        teleport_destination::TeleportDestination,
+        trigger::Trigger,
+        tween::Tween,
+    },
+    levels::{level1_garage, level3_chair, Level},
+    logic_graph::LogicGraph,
+    materials, notification,
+};
+
+/// Scene files spawned in each level besides the elevator, see the levels' `setup` systems.
+const LEVEL_SCENES: &[(Level, &[&str])] = &[
+    (
+        Level::Level1Garage,
+        &[
+            "models/Level 1 Parking Garage Export.gltf",
+            "models/Lobby Sky.glb",
+        ],
+    ),
+    (
+        Level::Level2Lobby,
+        &[
+            "models/Level 2 Lobby.gltf",
+            "models/Level 2 Lobby Props.gltf",
+            "models/Lobby Sky.glb",
+        ],
+    ),
+    (Level::Level3Chair, &["models/Level 3 Chair Export.gltf"]),
+    (
+        Level::Level4ChairsPile,
+        &["models/Level 4 Chairs Pile Export.gltf"],
+    ),
+    (
+        Level::Level5GarageLobby,
+        &["models/Level 5 Parking Garage Lobby Export.gltf"],
+    ),
+    (Level::TestAreaLevel, &["models/test_area.gltf"]),
+];
+
+/// Spawned once and kept across levels.
+const ELEVATOR_SCENE: &str = "models/Elevator Export.gltf";
+
+/// Folder searched for `.logic` files.
+const LOGIC_GRAPHS: &str = "models";
+
+/// Name kinds [`spawn_from_scene!`](crate::spawn_from_scene) adds components for, see
+/// [`EntityName`], with a check that the extras parse as that component.
+const ENTITY_PREFIXES: &[(&str, fn(&str) -> Result<(), EntityError>)] = &[
+    ("BLOCK", parses::<Block>),
+    ("BUTTON", parses::<Button>),
+    ("COLLIDER", parses::<Collider>),
+    ("DOOR_HINGED", parses::<DoorHinged>),
+    ("DOOR_LINEAR", parses::<DoorLinear>),
+    ("PHONE", parses::<Phone>),
+    ("PICKUP", parses::<Pickup>),
+    ("PROP", parses::<Prop>),
+    ("SOUND", parses::<Sound>),
+    ("TELEPORT_DESTINATION", parses::<TeleportDestination>),
+    ("TELEPORT", parses::<Teleport>),
+    ("TRIGGER", parses::<Trigger>),
+    ("TWEEN", parses::<Tween>),
+];
+
+/// Prefixes of entities which don't do anything unless something looks them up.
+const LOOKED_UP_PREFIXES: &[&str] = &["BUTTON", "DOOR_HINGED", "DOOR_LINEAR", "TRIGGER"];
+
+/// Prefixes of entities which do something by themselves, even when also named with one of
+/// [`LOOKED_UP_PREFIXES`].
+const SELF_CONTAINED_PREFIXES: &[&str] = &["PICKUP"];
+
+fn parses<T: DeserializeOwned>(extras: &str) -> Result<(), EntityError> {
+    parse_properties::<T>(extras).map(|_| ())
+}
+
+/// A query which Rust code finds entities by.
+#[derive(Clone, Copy, Debug)]
+pub enum NameLookup {
+    /// Found with `find` or `any` on `NamedButtonStatuses` or `NamedTriggerStatuses`, only the
+    /// first match is used
+    First(NameQuery<'static>),
+    /// Found with `filter_name` or [`NameQuery::matches`], every match is used
+    All(NameQuery<'static>),
+}
+
+/// A lookup from code or a logic graph, checked against a level's names.
+#[derive(Clone, Debug)]
+pub struct Lookup {
+    pub pattern: String,
+    pub mode: NameMatch,
+    pub first_only: bool,
+    /// Report it when nothing matches, off for lookups shared by every level
+    pub required: bool,
+    /// Where the lookup comes from, for the report
+    pub source: String,
+}
+
+impl Lookup {
+    fn query(&self) -> NameQuery {
+        NameQuery::new(&self.pattern, self.mode)
+    }
+}
+
+/// A named entity in a scene.
+#[derive(Clone, Debug)]
+pub struct SceneEntity {
+    pub name: String,
+    /// Raw JSON of the glTF extras, see [`GltfExtras`]
+    pub extras: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LintIssue {
+    NotLookedUp {
+        name: String,
+    },
+    NoMatch {
+        pattern: String,
+        source: String,
+    },
+    Ambiguous {
+        pattern: String,
+        source: String,
+        matches: Vec<String>,
+    },
+    UnknownDestination {
+        name: String,
+        destination: String,
+    },
+    InvalidExtras {
+        name: String,
+        prefix: &'static str,
+        error: EntityError,
+    },
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintIssue::NotLookedUp { name } => write!(f, "{name:?} isn't looked up by anything"),
+            LintIssue::NoMatch { pattern, source } => {
+                write!(f, "{pattern:?} from {source} matches nothing")
+            }
+            LintIssue::Ambiguous {
+                pattern,
+                source,
+                matches,
+            } => write!(
+                f,
+                "{pattern:?} from {source} only uses the first match but matches {matches:?}"
+            ),
+            LintIssue::UnknownDestination { name, destination } => {
+                write!(
+                    f,
+                    "{name:?} teleports to unknown destination {destination:?}"
+                )
+            }
+            LintIssue::InvalidExtras {
+                name,
+                prefix,
+                error,
+            } => write!(f, "{name:?} as {prefix}: {error}"),
+        }
+    }
+}
+
+/// Check a level's scene entities against the lookups made in it.
+pub fn lint(entities: &[SceneEntity], lookups: &[Lookup]) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    // Sounds look up the triggers which start them
+    let sound_lookups: Vec<Lookup> = entities
+        .iter()
+        .filter(|entity| EntityName::parse(&entity.name).has_kind("SOUND"))
+        .filter_map(|entity| {
+            let extras = entity.extras.as_deref().unwrap_or("{}");
+            let trigger = parse_properties::<Sound>(extras).ok()?.trigger?;
+            Some(Lookup {
+                pattern: trigger,
+                mode: NameMatch::Contains,
+                first_only: false,
+                required: true,
+                source: entity.name.clone(),
+            })
+        })
+        .collect();
+    let lookups: Vec<&Lookup> = lookups.iter().chain(&sound_lookups).collect();
+
+    // Names can repeat, such as meshes used by several objects, count each once
+    let mut names: Vec<&str> = entities.iter().map(|entity| entity.name.as_str()).collect();
+    names.sort_unstable();
+    names.dedup();
+
+    for name in &names {
+        let parsed = EntityName::parse(name);
+        if LOOKED_UP_PREFIXES
+            .iter()
+            .any(|prefix| parsed.has_kind(prefix))
+            && !SELF_CONTAINED_PREFIXES
+                .iter()
+                .any(|prefix| parsed.has_kind(prefix))
+            && !lookups.iter().any(|lookup| lookup.query().matches(name))
+        {
+            issues.push(LintIssue::NotLookedUp {
+                name: name.to_string(),
+            });
+        }
+    }
+
+    // Logic graphs often use a pattern in several rules, report it once
+    let mut checked = HashSet::new();
+    for lookup in &lookups {
+        if !checked.insert((lookup.query(), lookup.first_only, &lookup.source)) {
+            continue;
+        }
+        let matches: Vec<&str> = names
+            .iter()
+            .copied()
+            .filter(|name| lookup.query().matches(name))
+            .collect();
+        if matches.is_empty() && lookup.required {
+            issues.push(LintIssue::NoMatch {
+                pattern: lookup.pattern.clone(),
+                source: lookup.source.clone(),
+            });
+        } else if matches.len() > 1 && lookup.first_only {
+            issues.push(LintIssue::Ambiguous {
+                pattern: lookup.pattern.clone(),
+                source: lookup.source.clone(),
+                matches: matches.iter().map(|name| name.to_string()).collect(),
+            });
+        }
+    }
+
+    let destinations: HashSet<&str> = names
+        .iter()
+        .copied()
+        .filter(|name| EntityName::parse(name).has_kind("TELEPORT_DESTINATION"))
+        .collect();
+
+    for entity in entities {
+        let extras = entity.extras.as_deref().unwrap_or("{}");
+        let name = EntityName::parse(&entity.name);
+        for &(prefix, parses) in ENTITY_PREFIXES {
+            if !name.has_kind(prefix) {
+                continue;
+            }
+            if let Err(err) = parses(extras) {
+                issues.push(LintIssue::InvalidExtras {
+                    name: entity.name.clone(),
+                    prefix,
+                    error: err,
+                });
+            }
+        }
+        // A button's mesh is looked up by the button itself
+        if name.has_kind("BUTTON") {
+            if let Ok(Button {
+                mesh: Some(mesh), ..
+            }) = parse_properties::<Button>(extras)
+            {
+                if !names.iter().any(|name| name.contains(mesh.as_str())) {
+                    issues.push(LintIssue::NoMatch {
+                        pattern: mesh,
+                        source: entity.name.clone(),
+                    });
+                }
+            }
+        }
+        // Item targets go by their extras rather than their name
+        if extras.contains("requires_item") {
+            if let Err(err) = parses::<ItemTarget>(extras) {
+                issues.push(LintIssue::InvalidExtras {
+                    name: entity.name.clone(),
+                    prefix: "item target",
+                    error: err,
+                });
+            }
+        }
+
+        if name.has_kind("TELEPORT") {
+            if let Ok(teleport) = serde_json::from_str::<Teleport>(extras) {
+                if !destinations.contains(teleport.destination.as_str()) {
+                    issues.push(LintIssue::UnknownDestination {
+                        name: entity.name.clone(),
+                        destination: teleport.destination,
+                    });
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+/// Lookups made in `level` by code and by `graphs`, each with its source.
+pub fn level_lookups<'a>(
+    level: Level,
+    graphs: impl IntoIterator<Item = (String, &'a LogicGraph)>,
+) -> Vec<Lookup> {
+    let (level_source, level_lookups) = match level {
+        Level::Level1Garage => ("levels::level1_garage", level1_garage::NAME_LOOKUPS),
+        Level::Level3Chair => ("levels::level3_chair", level3_chair::NAME_LOOKUPS),
+        Level::None
+        | Level::Level2Lobby
+        | Level::Level4ChairsPile
+        | Level::Level5GarageLobby
+        | Level::TestAreaLevel => ("", &[][..]),
+    };
+
+    let code = [
+        (level_source, level_lookups, true),
+        ("materials", materials::NAME_LOOKUPS, false),
+        ("notification", notification::NAME_LOOKUPS, false),
+    ];
+    let mut lookups: Vec<Lookup> = code
+        .into_iter()
+        .flat_map(|(source, lookups, required)| {
+            lookups.iter().map(move |lookup| {
+                let (query, first_only) = match lookup {
+                    NameLookup::First(query) => (query, true),
+                    NameLookup::All(query) => (query, false),
+                };
+                Lookup {
+                    pattern: query.pattern.to_string(),
+                    mode: query.mode,
+                    first_only,
+                    required,
+                    source: source.to_string(),
+                }
+            })
+        })
+        .collect();
+
+    for (source, graph) in graphs {
+        if !graph.is_active(level) {
+            continue;
+        }
+        lookups.extend(graph.name_patterns().into_iter().map(|pattern| Lookup {
+            pattern: pattern.pattern.clone(),
+            mode: pattern.mode,
+            first_only: false,
+            required: true,
+            source: source.clone(),
+        }));
+    }
+
+    lookups
+}
+
+pub struct SceneLintPlugin;
+
+impl Plugin for SceneLintPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            ConditionSet::new()
+                .run_in_state(GameState::RunLevel)
+                .with_system(lint_level_scenes)
+                .into(),
+        );
+    }
+}
+
+/// Lint the scenes as they were loaded, before hooks rename or despawn anything.
+fn lint_level_scenes(
+    level: Res<CurrentState<Level>>,
+    roots: Query<&Handle<Scene>>,
+    scenes: Res<Assets<Scene>>,
+    graphs: Res<Assets<LogicGraph>>,
+) {
+    if !level.is_changed() || level.0 == Level::None {
+        return;
+    }
+
+    let entities: Vec<SceneEntity> = roots
+        .iter()
+        .filter_map(|handle| scenes.get(handle))
+        .flat_map(|scene| scene_entities(&scene.world))
+        .collect();
+    let lookups = level_lookups(
+        level.0,
+        graphs
+            .iter()
+            .map(|(_, graph)| (String::from("logic graph"), graph)),
+    );
+
+    for issue in lint(&entities, &lookups) {
+        warn!(level = ?level.0, "{issue}");
+    }
+}
+
+fn scene_entities(world: &World) -> impl Iterator<Item = SceneEntity> + '_ {
+    world
+        .archetypes()
+        .iter()
+        .flat_map(|archetype| archetype.entities())
+        .filter_map(|&entity| {
+            Some(SceneEntity {
+                name: world.get::<Name>(entity)?.to_string(),
+                extras: world
+                    .get::<GltfExtras>(entity)
+                    .map(|extras| extras.value.clone()),
+            })
+        })
+}
+
+#[derive(Debug)]
+pub enum LintError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    InvalidGlb,
+}
+
+impl fmt::Display for LintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintError::Io(err) => write!(f, "io error: {err}"),
+            LintError::Json(err) => write!(f, "invalid json: {err}"),
+            LintError::InvalidGlb => write!(f, "invalid glb file"),
+        }
+    }
+}
+
+impl From<std::io::Error> for LintError {
+    fn from(err: std::io::Error) -> Self {
+        LintError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for LintError {
+    fn from(err: serde_json::Error) -> Self {
+        LintError::Json(err)
+    }
+}
+
+#[derive(Deserialize)]
+struct GltfFile {
+    #[serde(default)]
+    nodes: Vec<GltfNode>,
+    #[serde(default)]
+    meshes: Vec<GltfMesh>,
+}
+
+#[derive(Deserialize)]
+struct GltfNode {
+    name: Option<String>,
+    mesh: Option<usize>,
+    extras: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct GltfMesh {
+    name: Option<String>,
+}
+
+/// Named entities the glTF loader spawns for a `.gltf` or `.glb` file.
+fn read_scene_file(path: &Path) -> Result<Vec<SceneEntity>, LintError> {
+    let bytes = fs::read(path)?;
+    let json = if bytes.starts_with(b"glTF") {
+        // The JSON chunk follows the 12 byte header and its own 8 byte chunk header
+        let length = bytes
+            .get(12..16)
+            .map(|length| u32::from_le_bytes(length.try_into().unwrap()) as usize);
+        match (length, bytes.get(16..20)) {
+            (Some(length), Some(b"JSON")) => {
+                bytes.get(20..20 + length).ok_or(LintError::InvalidGlb)?
+            }
+            _ => return Err(LintError::InvalidGlb),
+        }
+    } else {
+        &bytes[..]
+    };
+    let gltf: GltfFile = serde_json::from_slice(json)?;
+
+    let mut entities = Vec::new();
+    for node in &gltf.nodes {
+        if let Some(name) = &node.name {
+            entities.push(SceneEntity {
+                name: name.clone(),
+                extras: node.extras.as_ref().map(|extras| extras.to_string()),
+            });
+        }
+        // Mesh primitives are spawned as children named after the mesh
+        let mesh = node.mesh.and_then(|mesh| gltf.meshes.get(mesh));
+        if let Some(name) = mesh.and_then(|mesh| mesh.name.as_ref()) {
+            entities.push(SceneEntity {
+                name: name.clone(),
+                extras: None,
+            });
+        }
+    }
+    Ok(entities)
+}
+
+fn read_logic_graphs(assets: &Path) -> Result<Vec<(String, LogicGraph)>, LintError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(assets.join(LOGIC_GRAPHS))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "logic"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let graph: LogicGraph = serde_json::from_slice(&fs::read(&path)?)?;
+            let source = path.file_name().unwrap().to_string_lossy().into_owned();
+            Ok((source, graph))
+        })
+        .collect()
+}
+
+/// Lint every level from the files in `assets` and print a report, returns the number of issues.
+pub fn lint_asset_files(assets: &Path) -> Result<usize, LintError> {
+    let graphs = read_logic_graphs(assets)?;
+    let elevator = read_scene_file(&assets.join(ELEVATOR_SCENE))?;
+
+    let mut total = 0;
+    for (level, scenes) in LEVEL_SCENES {
+        let mut entities = elevator.clone();
+        for scene in *scenes {
+            entities.extend(read_scene_file(&assets.join(scene))?);
+        }
+        let lookups = level_lookups(
+            *level,
+            graphs.iter().map(|(source, graph)| (source.clone(), graph)),
+        );
+
+        let issues = lint(&entities, &lookups);
+        println!("{level:?}: {} issues", issues.len());
+        for issue in &issues {
+            println!("  {issue}");
+        }
+        total += issues.len();
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(name: &str, extras: Option<&str>) -> SceneEntity {
+        SceneEntity {
+            name: name.to_string(),
+            extras: extras.map(String::from),
+        }
+    }
+
+    fn lookup(pattern: &str, first_only: bool) -> Lookup {
+        Lookup {
+            pattern: pattern.to_string(),
+            mode: NameMatch::Contains,
+            first_only,
+            required: true,
+            source: String::from("test"),
+        }
+    }
+
+    #[test]
+    fn clean_scene() {
+        let entities = [
+            entity("BUTTON Lift", None),
+            entity("Lift Mesh", None),
+            entity("Lift Mesh", None),
+            entity(
+                "TELEPORT Exit",
+                Some(r#"{"destination": "TELEPORT_DESTINATION Out"}"#),
+            ),
+            entity("TELEPORT_DESTINATION Out", None),
+            entity("TRIGGER Lobby", None),
+            entity(
+                "SOUND Lobby Hum",
+                Some(r#"{"sound": "sounds/drone1.flac", "trigger": "TRIGGER Lobby"}"#),
+            ),
+        ];
+        let lookups = [lookup("BUTTON Lift", true), lookup("Lift Mesh", true)];
+
+        let issues = lint(&entities, &lookups);
+        assert!(issues.is_empty(), "{issues:?}");
+    }
+
+    #[test]
+    fn kinds_are_whole_words_before_the_id() {
+        let entities = [
+            entity("BLOCK Lobby TRIGGER Plate", None),
+            entity("TELEPORT_DESTINATION Out", None),
+        ];
+
+        let issues = lint(&entities, &[]);
+        assert!(issues.is_empty(), "{issues:?}");
+    }
+
+    #[test]
+    fn reports_each_issue() {
+        let entities = [
+            entity("TRIGGER Hall", None),
+            entity("BUTTON Call 1", None),
+            entity("BUTTON Call 2", None),
+            entity("BUTTON Bell", Some(r#"{"mesh": "Bell Mesh"}"#)),
+            entity("TELEPORT Exit", Some(r#"{"destination": "Nowhere"}"#)),
+            entity("DOOR_LINEAR Gate", Some(r#"{"speed": "fast"}"#)),
+            entity(
+                "SOUND Drip",
+                Some(r#"{"sound": "sounds/drip.flac", "trigger": "TRIGGER Cellar"}"#),
+            ),
+        ];
+        let lookups = [
+            lookup("BUTTON Call", true),
+            lookup("BUTTON Bell", true),
+            lookup("Gate", false),
+            lookup("Missing", false),
+        ];
+
+        let issues = lint(&entities, &lookups);
+        assert!(issues.contains(&LintIssue::NotLookedUp {
+            name: String::from("TRIGGER Hall")
+        }));
+        assert!(issues.contains(&LintIssue::NoMatch {
+            pattern: String::from("Missing"),
+            source: String::from("test")
+        }));
+        assert!(issues.contains(&LintIssue::NoMatch {
+            pattern: String::from("Bell Mesh"),
+            source: String::from("BUTTON Bell")
+        }));
+        assert!(issues.contains(&LintIssue::NoMatch {
+            pattern: String::from("TRIGGER Cellar"),
+            source: String::from("SOUND Drip")
+        }));
+        assert!(issues.contains(&LintIssue::Ambiguous {
+            pattern: String::from("BUTTON Call"),
+            source: String::from("test"),
+            matches: vec![String::from("BUTTON Call 1"), String::from("BUTTON Call 2")]
+        }));
+        assert!(issues.contains(&LintIssue::UnknownDestination {
+            name: String::from("TELEPORT Exit"),
+            destination: String::from("Nowhere")
+        }));
+        assert!(issues
+            .iter()
+            .any(|issue| matches!(issue, LintIssue::InvalidExtras { name, .. } if name == "DOOR_LINEAR Gate")));
+        assert_eq!(issues.len(), 7);
+    }
+}
@@ -43,45 +43,41 @@ impl Plugin for SidecarAssetPlugin {
     }
 }
 
-/*
-// Didn't get test to work, it doesn't see Assets<SidecarAsset>
 #[cfg(test)]
 mod tests {
-    use bevy::{asset::AssetPlugin, ecs::system::SystemState};
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
 
-    use super::*;
+    use bevy::asset::{AssetPlugin, LoadState};
 
-    #[derive(Default)]
-    struct State {
-        handle: Handle<SidecarAsset>,
-    }
+    use super::*;
 
     #[test]
     fn test_sidecar_file() {
         let mut app = App::new();
-        app.add_plugin(AssetPlugin::default())
-            .add_plugins(MinimalPlugins)
-            .init_resource::<State>()
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
             .add_plugin(SidecarAssetPlugin);
 
-        let mut system_state: SystemState<(Res<AssetServer>, ResMut<State>)> =
-            SystemState::new(&mut app.world);
-
-        let (ass, mut state) = system_state.get_mut(&mut app.world);
-
-        state.handle = ass.load("data/asset.car");
+        let handle: Handle<SidecarAsset> =
+            app.world.resource::<AssetServer>().load("data/asset.car");
 
+        // Loading happens on the IO threads
+        let started = Instant::now();
+        while app.world.resource::<AssetServer>().get_load_state(&handle) != LoadState::Loaded {
+            assert!(started.elapsed() < Duration::from_secs(10), "didn't load");
+            thread::sleep(Duration::from_millis(1));
+            app.update();
+        }
         app.update();
 
-        let sidecar_assets = app
+        let sidecar_asset = app
             .world
-            .get_resource::<ResMut<Assets<SidecarAsset>>>()
+            .resource::<Assets<SidecarAsset>>()
+            .get(&handle)
             .unwrap();
-
-        let state = app.world.get_resource::<Res<State>>().unwrap();
-        let sidecar_asset = sidecar_assets.get(&state.handle).unwrap();
-
-        info!("Sidecar asset loaded: {:?}", sidecar_asset);
+        assert_eq!(sidecar_asset.value, 42);
     }
 }
-*/
@@ -0,0 +1,402 @@
+//! Headless app for testing level logic against the real levels.
+//!
+//! Uses [`DefaultPlugins`] with windowing, gamepads and the GPU turned off rather than
+//! `MinimalPlugins`, the glTF scenes carry meshes, materials and lights whose types have to be
+//! registered for the scenes to spawn. Time is stepped by the harness, one controller tick per
+//! frame, so tests run as fast as the machine allows and behave the same on every run.
+
+use std::{
+    mem,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::system::CommandQueue,
+    gilrs::GilrsPlugin,
+    log::LogPlugin,
+    prelude::*,
+    render::settings::WgpuSettings,
+    time::{FixedTimesteps, TimePlugin},
+    winit::WinitPlugin,
+};
+use bevy_fps_controller::controller::{FpsController, TIMESTEP};
+use bevy_rapier3d::prelude::Velocity;
+use iyes_loopless::prelude::*;
+
+use crate::{
+    actions::{Action, ActionsOverride, ActionsSystem},
+    add_game_logic,
+    assets::{DataAssets, GameState},
+    entity::{
+        button::Button,
+        door_hinged::DoorHinged,
+        door_linear::{DoorLinear, DoorState},
+        error::EntityErrors,
+        trigger::TriggerOccupancy,
+        NamedIterator,
+    },
+    inventory::Inventory,
+    item::{ItemDefinitions, SelectedItem},
+    levels::Level,
+    menu::GameSettings,
+    replay::{PlayReplayEvent, ReplayState},
+    save::AutosaveDisabled,
+    scene_hook::{SceneHook, SceneHooked},
+    spawn_logical_player, PlayerCamera,
+};
+
+/// Real time allowed for loading assets or a level.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Frames to let hooks, colliders and logic graphs settle after a level has spawned.
+const SETTLE_FRAMES: usize = 10;
+
+/// Where the camera waits when it isn't clicking something.
+const CAMERA_PARKED: Vec3 = Vec3::new(0.0, -1000.0, 0.0);
+
+pub struct TestGame {
+    pub app: App,
+    pub player: Entity,
+    camera: Entity,
+    now: Instant,
+}
+
+impl TestGame {
+    /// Loads all assets and starts in the garage, like a new game.
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app.insert_resource(WgpuSettings {
+            backends: None,
+            ..default()
+        })
+        .add_plugins_with(DefaultPlugins, |group| {
+            group
+                // Only one global logger per process, and tests share one
+                .disable::<LogPlugin>()
+                .disable::<TimePlugin>()
+                .disable::<WinitPlugin>()
+                .disable::<GilrsPlugin>()
+        })
+        .init_resource::<Time>()
+        .init_resource::<FixedTimesteps>()
+        .init_resource::<GameSettings>()
+        // Actions only come from the tests
+        .insert_resource(ActionsOverride)
+        .insert_resource(AutosaveDisabled)
+        .init_resource::<PendingActions>()
+        .add_system_to_stage(
+            CoreStage::PreUpdate,
+            press_pending_actions.after(ActionsSystem),
+        );
+        add_game_logic(&mut app);
+
+        let mut now = Instant::now();
+        let started = Instant::now();
+        while app.world.resource::<CurrentState<GameState>>().0 != GameState::RunLevel {
+            assert!(started.elapsed() < LOAD_TIMEOUT, "assets didn't load");
+            // Give the IO threads a chance
+            thread::sleep(Duration::from_millis(1));
+            advance(&mut app, &mut now);
+        }
+
+        let mut queue = CommandQueue::default();
+        let player = spawn_logical_player(&mut Commands::new(&mut queue, &app.world));
+        queue.apply(&mut app.world);
+        // There's no window to read input from
+        app.world
+            .get_mut::<FpsController>(player)
+            .unwrap()
+            .enable_input = false;
+
+        let camera = app
+            .world
+            .spawn()
+            .insert_bundle(TransformBundle::from(Transform::from_translation(
+                CAMERA_PARKED,
+            )))
+            .insert(PlayerCamera)
+            .id();
+
+        let mut game = TestGame {
+            app,
+            player,
+            camera,
+            now,
+        };
+        let start = game.player_position();
+        game.wait_for_level(Level::Level1Garage);
+        // Put back in case the player fell before the garage had colliders
+        game.teleport(start);
+        game
+    }
+
+    /// Advance one frame, which runs exactly one fixed tick.
+    pub fn update(&mut self) {
+        advance(&mut self.app, &mut self.now);
+    }
+
+    pub fn run_frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.update();
+        }
+    }
+
+    /// Run up to `frames` frames until `condition` holds, returns whether it did.
+    pub fn run_until(
+        &mut self,
+        frames: usize,
+        mut condition: impl FnMut(&mut World) -> bool,
+    ) -> bool {
+        for _ in 0..frames {
+            self.update();
+            if condition(&mut self.app.world) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn resource<R: Resource>(&self) -> &R {
+        self.app.world.resource::<R>()
+    }
+
+    pub fn resource_mut<R: Resource>(&mut self) -> Mut<R> {
+        self.app.world.resource_mut::<R>()
+    }
+
+    /// Switch to `level` and wait for it to spawn.
+    pub fn load_level(&mut self, level: Level) {
+        *self.resource_mut::<Level>() = level;
+        self.wait_for_level(level);
+    }
+
+    fn wait_for_level(&mut self, level: Level) {
+        let started = Instant::now();
+        loop {
+            assert!(started.elapsed() < LOAD_TIMEOUT, "{level:?} didn't spawn");
+            thread::sleep(Duration::from_millis(1));
+            self.update();
+
+            // The level's scenes are added as the state changes, so once it has changed
+            // they're spawned when none are left waiting for their hooks
+            let world = &mut self.app.world;
+            if world.resource::<CurrentState<Level>>().0 == level
+                && world
+                    .query_filtered::<(), (With<SceneHook>, Without<SceneHooked>)>()
+                    .iter(world)
+                    .next()
+                    .is_none()
+            {
+                break;
+            }
+        }
+        self.run_frames(SETTLE_FRAMES);
+    }
+
+    /// The first entity whose name contains `name`.
+    pub fn find(&mut self, name: &str) -> Option<Entity> {
+        let world = &mut self.app.world;
+        world
+            .query::<(Entity, &Name)>()
+            .iter(world)
+            .find(|(_, entity_name)| entity_name.contains(name))
+            .map(|(entity, _)| entity)
+    }
+
+    pub fn position_of(&mut self, name: &str) -> Vec3 {
+        let entity = self
+            .find(name)
+            .unwrap_or_else(|| panic!("no entity named {name:?}"));
+        self.app
+            .world
+            .get::<GlobalTransform>(entity)
+            .unwrap()
+            .translation()
+    }
+
+    pub fn player_position(&self) -> Vec3 {
+        self.app
+            .world
+            .get::<Transform>(self.player)
+            .unwrap()
+            .translation
+    }
+
+    /// Move the player's feet to `position` and stop them.
+    pub fn teleport(&mut self, position: Vec3) {
+        let mut player = self.app.world.entity_mut(self.player);
+        player.get_mut::<Transform>().unwrap().translation = position;
+        player.get_mut::<FpsController>().unwrap().velocity = Vec3::ZERO;
+        *player.get_mut::<Velocity>().unwrap() = Velocity::zero();
+    }
+
+    /// Move the player so their body is centred on the named entity, such as a trigger.
+    pub fn teleport_into(&mut self, name: &str) {
+        // The player's capsule spans 0.5 below to 1.2 above their feet
+        let position = self.position_of(name) - Vec3::Y * 0.35;
+        self.teleport(position);
+    }
+
+    /// Run up to `frames` frames until the player is inside the trigger named `name`.
+    pub fn wait_for_trigger(&mut self, name: &str, frames: usize) -> bool {
+        let player = self.player;
+        self.run_until(frames, |world| {
+            world
+                .query::<(&Name, &TriggerOccupancy)>()
+                .iter(world)
+                .filter_name_contains(name)
+                .any(|(_, occupancy)| occupancy.contains(player))
+        })
+    }
+
+    /// Whether anything is inside a trigger whose name contains `name`.
+    pub fn trigger_occupied(&mut self, name: &str) -> bool {
+        let world = &mut self.app.world;
+        world
+            .query::<(&Name, &TriggerOccupancy)>()
+            .iter(world)
+            .filter_name_contains(name)
+            .any(|(_, occupancy)| occupancy.is_occupied())
+    }
+
+    /// Look at the button named `name` from inside it and press interact.
+    pub fn click(&mut self, name: &str) {
+        let world = &mut self.app.world;
+        let position = world
+            .query_filtered::<(&Name, &GlobalTransform), With<Button>>()
+            .iter(world)
+            .find(|(button_name, _)| button_name.contains(name))
+            .map(|(_, transform)| transform.translation())
+            .unwrap_or_else(|| panic!("no button named {name:?}"));
+
+        self.set_camera(position);
+        self.press(Action::Interact);
+        // Keep looking for a frame like a player would, a pressed status lingers for a frame
+        // after looking away
+        self.update();
+        self.set_camera(CAMERA_PARKED);
+        self.run_frames(SETTLE_FRAMES);
+    }
+
+    /// Hold `action` for one frame.
+    pub fn press(&mut self, action: Action) {
+        self.resource_mut::<PendingActions>().0.push(action);
+        self.update();
+    }
+
+    /// Play the replay file at `path` from its start to its last tick.
+    pub fn play_replay(&mut self, path: impl AsRef<Path>) {
+        self.resource_mut::<Events<PlayReplayEvent>>()
+            .send(PlayReplayEvent(path.as_ref().into()));
+        self.update();
+        assert!(
+            !matches!(self.resource::<ReplayState>(), ReplayState::Idle),
+            "replay {} didn't start",
+            path.as_ref().display()
+        );
+
+        let started = Instant::now();
+        while !matches!(self.resource::<ReplayState>(), ReplayState::Idle) {
+            assert!(started.elapsed() < LOAD_TIMEOUT, "replay didn't finish");
+            // The replay's level loads first
+            thread::sleep(Duration::from_millis(1));
+            self.update();
+        }
+        // Taken away when the replay finishes
+        self.app.world.insert_resource(ActionsOverride);
+    }
+
+    /// Move the camera to `position`, it always faces down -Z.
+    pub fn set_camera(&mut self, position: Vec3) {
+        self.app
+            .world
+            .get_mut::<Transform>(self.camera)
+            .unwrap()
+            .translation = position;
+    }
+
+    /// Put one of an item in the inventory without picking it up.
+    pub fn give_item(&mut self, item: &str) {
+        let world = &mut self.app.world;
+        let definition = world
+            .resource::<Assets<ItemDefinitions>>()
+            .get(&world.resource::<DataAssets>().items)
+            .and_then(|definitions| definitions.get(item))
+            .cloned()
+            .unwrap_or_else(|| panic!("no item {item:?}"));
+        world.resource_mut::<Inventory>().add(&definition, 1);
+    }
+
+    /// Select an item to use on item targets, held or not.
+    pub fn select_item(&mut self, item: &str) {
+        self.resource_mut::<SelectedItem>().0 = Some(item.to_string());
+    }
+
+    pub fn door_state(&mut self, name: &str) -> DoorState {
+        let world = &mut self.app.world;
+        let linear = world
+            .query::<(&Name, &DoorLinear)>()
+            .iter(world)
+            .find(|(door_name, _)| door_name.contains(name))
+            .map(|(_, door)| door.state);
+        linear
+            .or_else(|| {
+                world
+                    .query::<(&Name, &DoorHinged)>()
+                    .iter(world)
+                    .find(|(door_name, _)| door_name.contains(name))
+                    .map(|(_, door)| door.state)
+            })
+            .unwrap_or_else(|| panic!("no door named {name:?}"))
+    }
+}
+
+fn advance(app: &mut App, now: &mut Instant) {
+    *now += Duration::from_secs_f64(TIMESTEP);
+    app.world.resource_mut::<Time>().update_with_instant(*now);
+    app.update();
+}
+
+/// Actions held for the next frame only, and those held this frame.
+///
+/// Only the actions pressed here are released, so a replay can hold its own.
+#[derive(Default)]
+struct PendingActions(Vec<Action>, Vec<Action>);
+
+fn press_pending_actions(mut pending: ResMut<PendingActions>, mut actions: ResMut<Input<Action>>) {
+    for action in pending.1.drain(..) {
+        actions.release(action);
+    }
+    let next = mem::take(&mut pending.0);
+    for &action in &next {
+        actions.press(action);
+    }
+    pending.1 = next;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_standing_in_the_garage() {
+        let mut game = TestGame::new();
+
+        assert!(game.find("BUTTON vending 1").is_some());
+        let errors: Vec<_> = game.resource::<EntityErrors>().iter().collect();
+        assert!(errors.is_empty(), "{errors:?}");
+        let start = game.player_position();
+        game.run_frames(120);
+
+        // On the garage floor rather than falling through it
+        let controller = game.app.world.get::<FpsController>(game.player).unwrap();
+        assert!(controller.ground_tick > 0, "not on the ground");
+        assert!(
+            game.player_position().distance(start) < 3.0,
+            "{start} -> {}",
+            game.player_position()
+        );
+    }
+}